@@ -222,5 +222,7 @@ fn main() {
     )
     .unwrap();
 
-    wave_file_outputter.save("toid.wav".to_string(), 12.0);
+    wave_file_outputter
+        .save("toid.wav".to_string(), 12.0)
+        .unwrap();
 }
@@ -61,9 +61,17 @@ impl Beat {
         self.num as f32 / BEAT_LENGTH as f32
     }
 
+    pub fn to_f64(self) -> f64 {
+        self.num as f64 / BEAT_LENGTH as f64
+    }
+
     pub fn get_num(self) -> i64 {
         self.num
     }
+
+    pub fn from_num(num: i64) -> Self {
+        Beat { num }
+    }
 }
 
 impl FromFraction<i32> for Beat {
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+// attack, decay, releaseは秒、sustainは0.0 ~ 1.0
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Envelope {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Envelope {
+    pub fn new(attack: f32, decay: f32, sustain: f32, release: f32) -> Self {
+        Self {
+            attack,
+            decay,
+            sustain,
+            release,
+        }
+    }
+
+    pub fn get_release_samples(&self, sample_rate: f32) -> u64 {
+        (self.release.max(0.0) * sample_rate).ceil() as u64
+    }
+
+    // note onからsec秒後の値。note_off_secより後はその時点の値からreleaseする
+    pub fn get_value(&self, sec: f32, note_off_sec: f32) -> f32 {
+        if sec < note_off_sec {
+            return self.get_held_value(sec);
+        }
+        if self.release <= 0.0 {
+            return 0.0;
+        }
+        let released = ((sec - note_off_sec) / self.release).min(1.0);
+        self.get_held_value(note_off_sec) * (1.0 - released)
+    }
+
+    fn get_held_value(&self, sec: f32) -> f32 {
+        if sec < self.attack {
+            sec / self.attack
+        } else if sec < self.attack + self.decay {
+            1.0 - (1.0 - self.sustain) * (sec - self.attack) / self.decay
+        } else {
+            self.sustain
+        }
+    }
+}
+
+// 古いstateでもクリックしないように、短いattackとreleaseを付ける
+impl Default for Envelope {
+    fn default() -> Self {
+        Self {
+            attack: 0.005,
+            decay: 0.0,
+            sustain: 1.0,
+            release: 0.01,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_value() {
+        let envelope = Envelope::new(0.1, 0.1, 0.5, 0.2);
+        assert_eq!(envelope.get_value(0.0, 1.0), 0.0);
+        assert!((envelope.get_value(0.05, 1.0) - 0.5).abs() < 1e-6);
+        assert!((envelope.get_value(0.15, 1.0) - 0.75).abs() < 1e-6);
+        assert_eq!(envelope.get_value(0.5, 1.0), 0.5);
+        assert!((envelope.get_value(1.1, 1.0) - 0.25).abs() < 1e-6);
+        assert_eq!(envelope.get_value(1.5, 1.0), 0.0);
+
+        // attackの途中で離すと、その値からreleaseする
+        assert!((envelope.get_value(0.15, 0.05) - 0.25).abs() < 1e-6);
+        assert_eq!(envelope.get_release_samples(44100.0), 8820);
+    }
+}
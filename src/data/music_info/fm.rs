@@ -0,0 +1,208 @@
+use serde::de;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::super::super::error::ToidError;
+use super::Envelope;
+
+const MIN_FM_OPERATOR_NUM: usize = 2;
+const MAX_FM_OPERATOR_NUM: usize = 4;
+
+// ratioはnoteの周波数に対する倍率。
+// indexはmodulatorなら変調の深さ(radian)、carrierなら出力の大きさ
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FmOperator {
+    pub ratio: f32,
+    pub index: f32,
+    pub envelope: Envelope,
+}
+
+impl FmOperator {
+    pub fn new(ratio: f32, index: f32, envelope: Envelope) -> Self {
+        Self {
+            ratio,
+            index,
+            envelope,
+        }
+    }
+}
+
+// operatorは0が出力側。modulatorは必ず自分より番号の大きいoperator
+//   Stack: n-1 -> ... -> 1 -> 0
+//   Parallel: すべてcarrier
+//   TwoStacks: 1 -> 0, 3 -> 2 (carrierは0, 2)
+//   Branch: 1..n -> 0
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FmAlgorithm {
+    Stack,
+    Parallel,
+    TwoStacks,
+    Branch,
+}
+
+impl FmAlgorithm {
+    // operatorごとのmodulatorと、carrierのリスト
+    pub fn get_connections(&self, operator_num: usize) -> (Vec<Vec<usize>>, Vec<usize>) {
+        let mut modulators = vec![vec![]; operator_num];
+        let carriers = match self {
+            FmAlgorithm::Stack => {
+                for (i, modulator) in modulators.iter_mut().enumerate() {
+                    if i + 1 < operator_num {
+                        modulator.push(i + 1);
+                    }
+                }
+                vec![0]
+            }
+            FmAlgorithm::Parallel => (0..operator_num).collect(),
+            FmAlgorithm::TwoStacks => {
+                if operator_num > 1 {
+                    modulators[0].push(1);
+                }
+                if operator_num > 3 {
+                    modulators[2].push(3);
+                }
+                (0..operator_num).filter(|&i| i % 2 == 0).collect()
+            }
+            FmAlgorithm::Branch => {
+                if operator_num > 0 {
+                    modulators[0] = (1..operator_num).collect();
+                }
+                vec![0]
+            }
+        };
+        let carriers = carriers.into_iter().filter(|&i| i < operator_num).collect();
+        (modulators, carriers)
+    }
+}
+
+// operatorは2 ~ 4個。feedbackは一番番号の大きいoperatorが自分を変調する深さ。
+// 音量のenvelopeはcarrierのenvelopeなので、Trackのenvelopeは使わない
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Fm {
+    #[serde(deserialize_with = "deserialize_operators")]
+    pub operators: Vec<FmOperator>,
+    pub algorithm: FmAlgorithm,
+    #[serde(default)]
+    pub feedback: f32,
+}
+
+fn validate_operator_num(operator_num: usize) -> Result<(), ToidError> {
+    if (MIN_FM_OPERATOR_NUM..=MAX_FM_OPERATOR_NUM).contains(&operator_num) {
+        Ok(())
+    } else {
+        Err(ToidError::OutOfRange(format!(
+            "fm operator num must be {} ~ {} : {}",
+            MIN_FM_OPERATOR_NUM, MAX_FM_OPERATOR_NUM, operator_num
+        )))
+    }
+}
+
+fn deserialize_operators<'de, D>(deserializer: D) -> Result<Vec<FmOperator>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let operators = Vec::<FmOperator>::deserialize(deserializer)?;
+    validate_operator_num(operators.len()).map_err(de::Error::custom)?;
+    Ok(operators)
+}
+
+impl Fm {
+    pub fn new(
+        operators: Vec<FmOperator>,
+        algorithm: FmAlgorithm,
+        feedback: f32,
+    ) -> Result<Self, ToidError> {
+        validate_operator_num(operators.len())?;
+        Ok(Self {
+            operators,
+            algorithm,
+            feedback,
+        })
+    }
+
+    pub fn electric_piano() -> Self {
+        Self {
+            operators: vec![
+                FmOperator::new(1.0, 1.0, Envelope::new(0.002, 1.5, 0.3, 0.4)),
+                FmOperator::new(14.0, 1.2, Envelope::new(0.001, 0.3, 0.0, 0.1)),
+                FmOperator::new(1.0, 1.0, Envelope::new(0.002, 2.0, 0.2, 0.4)),
+                FmOperator::new(1.0, 1.5, Envelope::new(0.002, 1.0, 0.3, 0.4)),
+            ],
+            algorithm: FmAlgorithm::TwoStacks,
+            feedback: 0.0,
+        }
+    }
+
+    pub fn bell() -> Self {
+        Self {
+            operators: vec![
+                FmOperator::new(1.0, 1.0, Envelope::new(0.001, 4.0, 0.0, 2.0)),
+                FmOperator::new(3.5, 3.0, Envelope::new(0.001, 3.0, 0.0, 2.0)),
+            ],
+            algorithm: FmAlgorithm::Stack,
+            feedback: 0.0,
+        }
+    }
+
+    pub fn bass() -> Self {
+        Self {
+            operators: vec![
+                FmOperator::new(1.0, 1.0, Envelope::new(0.002, 0.5, 0.6, 0.05)),
+                FmOperator::new(1.0, 2.5, Envelope::new(0.001, 0.2, 0.3, 0.05)),
+                FmOperator::new(2.0, 1.0, Envelope::new(0.001, 0.1, 0.0, 0.05)),
+            ],
+            algorithm: FmAlgorithm::Stack,
+            feedback: 0.3,
+        }
+    }
+
+    pub fn get_release(&self) -> f32 {
+        self.operators
+            .iter()
+            .map(|operator| operator.envelope.release)
+            .fold(0.0, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_connections() {
+        assert_eq!(
+            FmAlgorithm::Stack.get_connections(3),
+            (vec![vec![1], vec![2], vec![]], vec![0])
+        );
+        assert_eq!(
+            FmAlgorithm::Parallel.get_connections(2),
+            (vec![vec![], vec![]], vec![0, 1])
+        );
+        assert_eq!(
+            FmAlgorithm::TwoStacks.get_connections(4),
+            (vec![vec![1], vec![], vec![3], vec![]], vec![0, 2])
+        );
+        assert_eq!(
+            FmAlgorithm::Branch.get_connections(4),
+            (vec![vec![1, 2, 3], vec![], vec![], vec![]], vec![0])
+        );
+    }
+
+    #[test]
+    fn test_operator_num() {
+        let operator = FmOperator::new(1.0, 1.0, Envelope::new(0.0, 0.0, 1.0, 0.0));
+        assert!(Fm::new(vec![operator], FmAlgorithm::Stack, 0.0).is_err());
+        assert!(Fm::new(vec![operator; 2], FmAlgorithm::Stack, 0.0).is_ok());
+        assert!(Fm::new(vec![operator; 4], FmAlgorithm::Stack, 0.0).is_ok());
+        assert!(Fm::new(vec![operator; 5], FmAlgorithm::Stack, 0.0).is_err());
+
+        let mut fm = Fm::bell();
+        assert_eq!(
+            serde_json::from_str::<Fm>(&serde_json::to_string(&fm).unwrap()).unwrap(),
+            fm
+        );
+        fm.operators.push(operator);
+        fm.operators.push(operator);
+        fm.operators.push(operator);
+        assert!(serde_json::from_str::<Fm>(&serde_json::to_string(&fm).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+// timeは前のnoteのpitchから次のnoteのpitchまで動く秒数。0.0ならglideしない。
+// legatoのときは、前のnoteが鳴っている間に始まったnoteだけglideして、envelopeを最初からやり直さない。
+// legatoでないときは、envelopeをやり直して、毎回直前のnoteのpitchからglideする
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub struct Glide {
+    pub time: f32,
+    pub legato: bool,
+}
+
+impl Glide {
+    pub fn new(time: f32, legato: bool) -> Self {
+        Self { time, legato }
+    }
+
+    pub fn get_samples(&self, sample_rate: f32) -> u64 {
+        (self.time.max(0.0) * sample_rate).round() as u64
+    }
+}
@@ -1,10 +1,18 @@
 use serde::{Deserialize, Serialize};
 
+use super::{Fm, Synth};
+
 #[derive(Serialize, Deserialize, Clone)]
 pub enum Instrument {
     SF2(String, usize),
     Sin,
     Tri,
     Saw,
+    Square,
+    // 帯域制限をしない以前の波形
+    LofiTri,
+    LofiSaw,
     Sample(String),
+    Synth(Synth),
+    FM(Fm),
 }
@@ -1,6 +1,9 @@
 mod beat;
 mod chord;
 mod chord_progression;
+mod envelope;
+mod fm;
+mod glide;
 mod instrument;
 mod note;
 mod phrase;
@@ -10,13 +13,18 @@ mod pitch_interval;
 mod pitch_note;
 mod sample_note;
 mod scale;
+mod synth;
+mod time_signature;
 mod track;
 
 pub use beat::Beat;
 pub use chord::Chord;
 pub use chord_progression::ChordProgression;
+pub use envelope::Envelope;
+pub use fm::{Fm, FmAlgorithm, FmOperator};
+pub use glide::Glide;
 pub use instrument::Instrument;
-pub use note::Note;
+pub use note::{Note, ACCENT_VELOCITY, DEFAULT_VELOCITY};
 pub use phrase::Phrase;
 pub use pitch::Pitch;
 pub use pitch_in_octave::PitchInOctave;
@@ -24,4 +32,6 @@ pub use pitch_interval::PitchInterval;
 pub use pitch_note::PitchNote;
 pub use sample_note::SampleNote;
 pub use scale::Scale;
+pub use synth::{FilterType, Lfo, Synth, SynthFilter, SynthOscillator, Waveform};
+pub use time_signature::{BarPosition, Position, TimeSignature};
 pub use track::Track;
@@ -1,6 +1,20 @@
 use super::beat::Beat;
 
+// velocityはMIDIと同じ0から127
+pub const DEFAULT_VELOCITY: u8 = 100;
+pub const ACCENT_VELOCITY: u8 = 127;
+
+pub fn default_velocity() -> u8 {
+    DEFAULT_VELOCITY
+}
+
 pub trait Note {
     fn get_start(&self) -> Beat;
     fn set_start(&self, start: Beat) -> Self;
+    fn get_velocity(&self) -> u8;
+
+    // velocityによる音量の倍率。velocityが無かった頃と同じ音量になるように、DEFAULT_VELOCITYで1にする
+    fn get_gain(&self) -> f32 {
+        self.get_velocity().min(ACCENT_VELOCITY) as f32 / DEFAULT_VELOCITY as f32
+    }
 }
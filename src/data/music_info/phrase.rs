@@ -63,7 +63,7 @@ impl<N: Note + Eq + Ord + Clone> Eq for Phrase<N> {}
 
 #[cfg(test)]
 mod tests {
-    use super::super::{Beat, Pitch, PitchNote};
+    use super::super::{Beat, Pitch, PitchNote, DEFAULT_VELOCITY};
     use super::*;
 
     #[test]
@@ -74,17 +74,20 @@ mod tests {
             pitch: Pitch::from(60),
             start: Beat::from(0.0),
             duration: Beat::from(1.0),
+            velocity: DEFAULT_VELOCITY,
         });
         let phrase1 = phrase1.add_note(PitchNote {
             pitch: Pitch::from(62),
             start: Beat::from(1.0),
             duration: Beat::from(1.0),
+            velocity: DEFAULT_VELOCITY,
         });
         let phrase2 = phrase1.clone();
         let phrase3 = phrase2.add_note(PitchNote {
             pitch: Pitch::from(64),
             start: Beat::from(2.0),
             duration: Beat::from(1.0),
+            velocity: DEFAULT_VELOCITY,
         });
 
         assert_eq!(phrase1, phrase2);
@@ -3,7 +3,7 @@ use std::cmp::Ordering;
 use serde::{Deserialize, Serialize};
 
 use super::beat::Beat;
-use super::note::Note;
+use super::note::{default_velocity, Note};
 use super::pitch::Pitch;
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
@@ -11,6 +11,8 @@ pub struct PitchNote {
     pub pitch: Pitch,
     pub duration: Beat,
     pub start: Beat,
+    #[serde(default = "default_velocity")]
+    pub velocity: u8,
 }
 
 impl Note for PitchNote {
@@ -22,15 +24,22 @@ impl Note for PitchNote {
             pitch: self.pitch,
             duration: self.duration,
             start,
+            velocity: self.velocity,
         }
     }
+    fn get_velocity(&self) -> u8 {
+        self.velocity
+    }
 }
 
 impl Eq for PitchNote {}
 
 impl PartialEq for PitchNote {
     fn eq(&self, other: &Self) -> bool {
-        self.pitch == other.pitch && self.duration == other.duration && self.start == other.start
+        self.pitch == other.pitch
+            && self.duration == other.duration
+            && self.start == other.start
+            && self.velocity == other.velocity
     }
 }
 
@@ -40,8 +49,10 @@ impl Ord for PitchNote {
             self.pitch.cmp(&other.pitch)
         } else if self.start != other.start {
             self.start.cmp(&other.start)
-        } else {
+        } else if self.duration != other.duration {
             self.duration.cmp(&other.duration)
+        } else {
+            self.velocity.cmp(&other.velocity)
         }
     }
 }
@@ -52,8 +63,10 @@ impl PartialOrd for PitchNote {
             self.pitch.partial_cmp(&other.pitch)
         } else if self.start != other.start {
             self.start.partial_cmp(&other.start)
-        } else {
+        } else if self.duration != other.duration {
             self.duration.partial_cmp(&other.duration)
+        } else {
+            self.velocity.partial_cmp(&other.velocity)
         }
     }
 }
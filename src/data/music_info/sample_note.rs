@@ -3,12 +3,14 @@ use std::cmp::Ordering;
 use serde::{Deserialize, Serialize};
 
 use super::beat::Beat;
-use super::note::Note;
+use super::note::{default_velocity, Note};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SampleNote {
     pub sound: String,
     pub start: Beat,
+    #[serde(default = "default_velocity")]
+    pub velocity: u8,
 }
 
 impl Note for SampleNote {
@@ -19,15 +21,19 @@ impl Note for SampleNote {
         SampleNote {
             sound: self.sound.clone(),
             start,
+            velocity: self.velocity,
         }
     }
+    fn get_velocity(&self) -> u8 {
+        self.velocity
+    }
 }
 
 impl Eq for SampleNote {}
 
 impl PartialEq for SampleNote {
     fn eq(&self, other: &Self) -> bool {
-        self.sound == other.sound && self.start == other.start
+        self.sound == other.sound && self.start == other.start && self.velocity == other.velocity
     }
 }
 
@@ -35,8 +41,10 @@ impl Ord for SampleNote {
     fn cmp(&self, other: &Self) -> Ordering {
         if self.sound != other.sound {
             self.sound.cmp(&other.sound)
-        } else {
+        } else if self.start != other.start {
             self.start.cmp(&other.start)
+        } else {
+            self.velocity.cmp(&other.velocity)
         }
     }
 }
@@ -45,8 +53,10 @@ impl PartialOrd for SampleNote {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         if self.sound != other.sound {
             self.sound.partial_cmp(&other.sound)
-        } else {
+        } else if self.start != other.start {
             self.start.partial_cmp(&other.start)
+        } else {
+            self.velocity.partial_cmp(&other.velocity)
         }
     }
 }
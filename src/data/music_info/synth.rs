@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+use super::Envelope;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Saw,
+    Square,
+    Pulse(f32), // pulse width 0.0 ~ 1.0
+    Noise,
+}
+
+// detune, unison_detuneはcent。unison_detuneはunisonの両端の幅
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SynthOscillator {
+    pub waveform: Waveform,
+    pub level: f32,
+    pub detune: f32,
+    pub unison: usize,
+    pub unison_detune: f32,
+}
+
+impl SynthOscillator {
+    pub fn new(waveform: Waveform) -> Self {
+        Self {
+            waveform,
+            level: 1.0,
+            detune: 0.0,
+            unison: 1,
+            unison_detune: 0.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FilterType {
+    LowPass,
+    HighPass,
+}
+
+// cutoffはHz、resonanceは0.0 ~ 1.0。envelope_amountはenvelopeが1のときに上げるoctave数
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SynthFilter {
+    pub filter_type: FilterType,
+    pub cutoff: f32,
+    pub resonance: f32,
+    pub envelope: Envelope,
+    pub envelope_amount: f32,
+}
+
+// rateはHz。pitch_depthはcent、cutoff_depthはoctave、amp_depthは0.0 ~ 1.0
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Lfo {
+    pub rate: f32,
+    pub pitch_depth: f32,
+    pub cutoff_depth: f32,
+    pub amp_depth: f32,
+}
+
+// 音量のenvelopeはTrackのenvelopeを使う
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Synth {
+    pub oscillators: Vec<SynthOscillator>,
+    pub filter: SynthFilter,
+    pub lfo: Lfo,
+}
+
+impl Default for Synth {
+    fn default() -> Self {
+        Self {
+            oscillators: vec![SynthOscillator::new(Waveform::Saw)],
+            filter: SynthFilter {
+                filter_type: FilterType::LowPass,
+                cutoff: 2000.0,
+                resonance: 0.2,
+                envelope: Envelope::new(0.005, 0.3, 0.0, 0.3),
+                envelope_amount: 2.0,
+            },
+            lfo: Lfo {
+                rate: 5.0,
+                pitch_depth: 0.0,
+                cutoff_depth: 0.0,
+                amp_depth: 0.0,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        let oscillator = SynthOscillator::new(Waveform::Pulse(0.25));
+        assert_eq!(oscillator.level, 1.0);
+        assert_eq!(oscillator.unison, 1);
+
+        let synth = Synth {
+            oscillators: vec![
+                oscillator,
+                SynthOscillator {
+                    detune: -7.0,
+                    ..SynthOscillator::new(Waveform::Square)
+                },
+            ],
+            filter: SynthFilter {
+                filter_type: FilterType::HighPass,
+                ..Synth::default().filter
+            },
+            ..Synth::default()
+        };
+        let serialized = serde_json::to_string(&synth).unwrap();
+        assert_eq!(serde_json::from_str::<Synth>(&serialized).unwrap(), synth);
+    }
+}
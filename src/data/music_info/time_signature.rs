@@ -0,0 +1,136 @@
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+use super::super::super::error::ToidError;
+use super::Beat;
+
+// Beatは4分音符を1とするので、denominatorが8なら1拍はBeat 0.5になる。
+// 1拍がBeatのtickで割り切れるように、denominatorは2の累乗に限る
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "RawTimeSignature")]
+pub struct TimeSignature {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+#[derive(Deserialize)]
+struct RawTimeSignature {
+    numerator: u32,
+    denominator: u32,
+}
+
+impl TryFrom<RawTimeSignature> for TimeSignature {
+    type Error = ToidError;
+
+    fn try_from(raw: RawTimeSignature) -> Result<Self, Self::Error> {
+        Self::new(raw.numerator, raw.denominator)
+    }
+}
+
+impl TimeSignature {
+    pub fn new(numerator: u32, denominator: u32) -> Result<Self, ToidError> {
+        let time_signature = Self {
+            numerator,
+            denominator,
+        };
+        time_signature.validate()?;
+        Ok(time_signature)
+    }
+
+    pub fn validate(&self) -> Result<(), ToidError> {
+        if self.numerator == 0 {
+            return Err(ToidError::OutOfRange(
+                "time signature numerator must be positive".to_string(),
+            ));
+        }
+        if !self.denominator.is_power_of_two()
+            || Beat::from(4).get_num() % self.denominator as i64 != 0
+        {
+            return Err(ToidError::OutOfRange(format!(
+                "invalid time signature denominator : {}",
+                self.denominator
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn get_beat_length(&self) -> Beat {
+        Beat::from_num(Beat::from(4).get_num() / self.denominator as i64)
+    }
+
+    pub fn get_bar_length(&self) -> Beat {
+        Beat::from_num(self.get_beat_length().get_num() * self.numerator as i64)
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            numerator: 4,
+            denominator: 4,
+        }
+    }
+}
+
+// bar, beatは0始まり。tickは拍の頭からのBeatのtick数
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BarPosition {
+    pub bar: i64,
+    pub beat: i64,
+    pub tick: i64,
+}
+
+impl BarPosition {
+    pub fn new(bar: i64, beat: i64, tick: i64) -> Self {
+        Self { bar, beat, tick }
+    }
+
+    pub fn from_bar(bar: i64) -> Self {
+        Self {
+            bar,
+            beat: 0,
+            tick: 0,
+        }
+    }
+}
+
+// BeatでもBarPositionでも位置を指定できるようにする
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Position {
+    Beat(Beat),
+    Bar(BarPosition),
+}
+
+impl From<Beat> for Position {
+    fn from(beat: Beat) -> Self {
+        Position::Beat(beat)
+    }
+}
+
+impl From<BarPosition> for Position {
+    fn from(bar_position: BarPosition) -> Self {
+        Position::Bar(bar_position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(TimeSignature::new(4, 4).unwrap(), TimeSignature::default());
+        assert!(TimeSignature::new(7, 8).is_ok());
+        assert!(TimeSignature::new(0, 4).is_err());
+        assert!(TimeSignature::new(4, 0).is_err());
+        assert!(TimeSignature::new(4, 3).is_err());
+        assert!(
+            serde_json::from_str::<TimeSignature>(r#"{"numerator":3,"denominator":0}"#).is_err()
+        );
+        assert_eq!(
+            serde_json::from_str::<TimeSignature>(r#"{"numerator":3,"denominator":4}"#).unwrap(),
+            TimeSignature::new(3, 4).unwrap()
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-use super::super::super::music_state::effects::EffectInfo;
+use super::super::super::music_state::effects::{self, EffectInfo};
+use super::Envelope;
+use super::Glide;
 use super::Instrument;
 use super::Note;
 use super::Phrase;
@@ -12,6 +14,12 @@ pub struct Track<N: Note + Ord + Eq + Clone> {
     pub effects: Vec<EffectInfo>,
     pub vol: f32, // 0.0 ~ 1.0
     pub pan: f32, // -1.0(L) ~ 1.0(R)
+    #[serde(default)]
+    pub envelope: Envelope,
+    #[serde(default)]
+    pub glide: Glide,
+    #[serde(default)]
+    pub mod_wheel: u8, // 0 ~ 127
 }
 
 impl<N: Note + Ord + Eq + Clone> Track<N> {
@@ -22,6 +30,9 @@ impl<N: Note + Ord + Eq + Clone> Track<N> {
             effects: vec![],
             vol: 1.0,
             pan: 0.0,
+            envelope: Envelope::default(),
+            glide: Glide::default(),
+            mod_wheel: 0,
         }
     }
 
@@ -32,6 +43,9 @@ impl<N: Note + Ord + Eq + Clone> Track<N> {
             effects: self.effects.clone(),
             vol: self.vol,
             pan: self.pan,
+            envelope: self.envelope,
+            glide: self.glide,
+            mod_wheel: self.mod_wheel,
         }
     }
 
@@ -42,38 +56,82 @@ impl<N: Note + Ord + Eq + Clone> Track<N> {
             effects: self.effects.clone(),
             vol: self.vol,
             pan: self.pan,
+            envelope: self.envelope,
+            glide: self.glide,
+            mod_wheel: self.mod_wheel,
         }
     }
 
-    pub fn set_vol(&self, vol: f32) -> Self {
+    fn set_effects(&self, effects: Vec<EffectInfo>) -> Self {
         Self {
             phrase: self.phrase.clone(),
             instrument: self.instrument.clone(),
-            effects: self.effects.clone(),
-            vol,
+            effects,
+            vol: self.vol,
             pan: self.pan,
+            envelope: self.envelope,
+            glide: self.glide,
+            mod_wheel: self.mod_wheel,
         }
     }
 
+    // 以下はCopyのfieldだけを書き換えるので、cloneしてから上書きする
+    pub fn set_vol(&self, vol: f32) -> Self {
+        let mut new_track = self.clone();
+        new_track.vol = vol;
+        new_track
+    }
+
     pub fn set_pan(&self, pan: f32) -> Self {
-        Self {
-            phrase: self.phrase.clone(),
-            instrument: self.instrument.clone(),
-            effects: self.effects.clone(),
-            vol: self.vol,
-            pan,
-        }
+        let mut new_track = self.clone();
+        new_track.pan = pan;
+        new_track
+    }
+
+    pub fn set_envelope(&self, envelope: Envelope) -> Self {
+        let mut new_track = self.clone();
+        new_track.envelope = envelope;
+        new_track
+    }
+
+    pub fn set_glide(&self, glide: Glide) -> Self {
+        let mut new_track = self.clone();
+        new_track.glide = glide;
+        new_track
     }
 
+    pub fn set_mod_wheel(&self, mod_wheel: u8) -> Self {
+        let mut new_track = self.clone();
+        new_track.mod_wheel = mod_wheel;
+        new_track
+    }
+
+    // effectsはcloneしたものをその場で編集する
     pub fn add_effect(&self, effect: EffectInfo) -> Self {
-        let mut new_effects = self.effects.clone();
-        new_effects.push(effect);
-        Self {
-            phrase: self.phrase.clone(),
-            instrument: self.instrument.clone(),
-            effects: new_effects,
-            vol: self.vol,
-            pan: self.pan,
-        }
+        let mut new_track = self.clone();
+        new_track.effects.push(effect);
+        new_track
+    }
+
+    pub fn remove_effect(&self, idx: usize) -> Self {
+        let mut new_track = self.clone();
+        effects::remove_effect(&mut new_track.effects, idx);
+        new_track
+    }
+
+    pub fn move_effect(&self, from: usize, to: usize) -> Self {
+        let mut new_track = self.clone();
+        effects::move_effect(&mut new_track.effects, from, to);
+        new_track
+    }
+
+    pub fn replace_effect(&self, idx: usize, effect: EffectInfo) -> Self {
+        let mut new_track = self.clone();
+        effects::replace_effect(&mut new_track.effects, idx, effect);
+        new_track
+    }
+
+    pub fn clear_effects(&self) -> Self {
+        self.set_effects(vec![])
     }
 }
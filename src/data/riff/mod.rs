@@ -5,6 +5,8 @@ use nom::bytes::complete::take;
 use nom::number::streaming::le_u32;
 use nom::IResult;
 
+use super::super::error::ToidError;
+
 pub enum RiffData {
     Data(Vec<u8>),
     Chunks(Vec<RiffChunk>),
@@ -18,10 +20,10 @@ pub struct RiffChunk {
 }
 
 impl RiffChunk {
-    pub fn parse(i: &[u8]) -> Result<Self, String> {
+    pub fn parse(i: &[u8]) -> Result<Self, ToidError> {
         let chunk = match Self::parse_riff(i) {
             Ok((_, chunk)) => chunk,
-            Err(e) => return Err(e.to_string()),
+            Err(e) => return Err(ToidError::RiffParse(e.to_string())),
         };
         Ok(chunk)
     }
@@ -1,4 +1,6 @@
 pub mod own;
 pub mod parsed;
 
+pub use own::modulator::ModulatorInput;
+pub use own::voice::SF2Voice;
 pub use own::SF2;
@@ -1,15 +1,20 @@
 use std::sync::Arc;
 
 use super::instrument::Instrument;
+use super::modulator::Modulator;
 use super::sample::Sample;
 
+#[derive(Clone)]
 pub struct Range {
     pub min: u8,
     pub max: u8,
 }
 
+// operにはzoneに書かれていた値をそのまま持つ。presetの値はinstrumentの値に足すため
 pub struct PresetGenerator {
     pub generator: Generator,
+    pub opers: Vec<(GeneratorEnum, i16)>,
+    pub modulators: Vec<Modulator>,
     pub instrument: Option<Arc<Instrument>>,
 }
 
@@ -17,21 +22,42 @@ impl PresetGenerator {
     pub fn new() -> Self {
         PresetGenerator {
             generator: Generator::new(),
+            opers: Vec::new(),
+            modulators: Vec::new(),
             instrument: None,
         }
     }
 
+    // global zoneの値の上に、このzoneの値を上書きしたもの
+    pub fn inherit(&self, global: &PresetGenerator) -> Self {
+        let mut generator = PresetGenerator::new();
+        for &(oper, amount) in global.opers.iter().chain(self.opers.iter()) {
+            generator.set_oper(oper, amount);
+        }
+        generator.modulators = Modulator::merge(&global.modulators, &self.modulators);
+        generator.instrument = self.instrument.clone();
+        generator
+    }
+
+    pub fn add_modulator(&mut self, modulator: Modulator) {
+        self.modulators.push(modulator);
+    }
+
     pub fn set_instrument(&mut self, instrument: Arc<Instrument>) {
         self.instrument = Some(Arc::clone(&instrument));
     }
 
     pub fn set_oper(&mut self, generator: GeneratorEnum, amount: i16) {
         self.generator.set_oper(generator, amount);
+        self.opers.retain(|&(oper, _)| oper != generator);
+        self.opers.push((generator, amount));
     }
 }
 
 pub struct InstrumentGenerator {
     pub generator: Generator,
+    pub opers: Vec<(GeneratorEnum, i16)>,
+    pub modulators: Vec<Modulator>,
     pub sample: Option<Arc<Sample>>,
 }
 
@@ -39,25 +65,45 @@ impl InstrumentGenerator {
     pub fn new() -> Self {
         InstrumentGenerator {
             generator: Generator::new(),
+            opers: Vec::new(),
+            modulators: Vec::new(),
             sample: None,
         }
     }
 
+    // global zoneの値の上に、このzoneの値を上書きしたもの
+    pub fn inherit(&self, global: &InstrumentGenerator) -> Self {
+        let mut generator = InstrumentGenerator::new();
+        for &(oper, amount) in global.opers.iter().chain(self.opers.iter()) {
+            generator.set_oper(oper, amount);
+        }
+        generator.modulators = Modulator::merge(&global.modulators, &self.modulators);
+        generator.sample = self.sample.clone();
+        generator
+    }
+
+    pub fn add_modulator(&mut self, modulator: Modulator) {
+        self.modulators.push(modulator);
+    }
+
     pub fn set_sample(&mut self, sample: Arc<Sample>) {
         self.sample = Some(Arc::clone(&sample));
     }
 
     pub fn set_oper(&mut self, generator: GeneratorEnum, amount: i16) {
         self.generator.set_oper(generator, amount);
+        self.opers.retain(|&(oper, _)| oper != generator);
+        self.opers.push((generator, amount));
     }
 }
 
+#[derive(Clone)]
 pub struct Generator {
-    pub start_addrs_offset: u16,
-    pub end_addrs_offset: u16,
-    pub startloop_addrs_offset: u16,
-    pub endloop_addrs_offset: u16,
-    pub start_addrs_coarse_offset: u16,
+    pub start_addrs_offset: i16,
+    pub end_addrs_offset: i16,
+    pub startloop_addrs_offset: i16,
+    pub endloop_addrs_offset: i16,
+    pub start_addrs_coarse_offset: i16,
     pub mod_lfo_to_pitch: f32,
     pub vib_lfo_to_pitch: f32,
     pub mod_env_to_pitch: f32,
@@ -65,7 +111,7 @@ pub struct Generator {
     pub initial_filter_q: f32,
     pub mod_lfo_to_filter_fc: f32,
     pub mod_env_to_filter_fc: f32,
-    pub end_addrs_coarse_offset: u16,
+    pub end_addrs_coarse_offset: i16,
     pub mod_lfo_to_volume: f32,
     // unused1: Option<()>,
     pub chorus_effects_send: f32,
@@ -98,12 +144,12 @@ pub struct Generator {
     // reserved1: Option<()>,
     pub key_range: Range,
     pub vel_range: Range,
-    pub startloop_addrs_coarse_offset: u16,
+    pub startloop_addrs_coarse_offset: i16,
     pub keynum: Option<u8>,
     pub velocity: Option<u8>,
     pub initial_attenuation: f32,
     // reserved2: Option<()>,
-    pub endloop_addrs_coarse_offset: u16,
+    pub endloop_addrs_coarse_offset: i16,
     pub coarse_tune: f32,
     pub fine_tune: i16,
     // sample_id: Option<usize>,
@@ -185,19 +231,19 @@ impl Generator {
     pub fn set_oper(&mut self, generator: GeneratorEnum, amount: i16) {
         match generator {
             GeneratorEnum::StartAddrsOffset => {
-                self.start_addrs_offset = amount as u16;
+                self.start_addrs_offset = amount;
             }
             GeneratorEnum::EndAddrsOffset => {
-                self.end_addrs_offset = amount as u16;
+                self.end_addrs_offset = amount;
             }
             GeneratorEnum::StartloopAddrsOffset => {
-                self.startloop_addrs_offset = amount as u16;
+                self.startloop_addrs_offset = amount;
             }
             GeneratorEnum::EndloopAddrsOffset => {
-                self.endloop_addrs_offset = amount as u16;
+                self.endloop_addrs_offset = amount;
             }
             GeneratorEnum::StartAddrsCoarseOffset => {
-                self.start_addrs_coarse_offset = amount as u16;
+                self.start_addrs_coarse_offset = amount;
             }
             GeneratorEnum::ModLfoToPitch => {
                 self.mod_lfo_to_pitch = (amount as f32) / 100.0;
@@ -221,7 +267,7 @@ impl Generator {
                 self.mod_env_to_filter_fc = (amount as f32) / 100.0;
             }
             GeneratorEnum::EndAddrsCoarseOffset => {
-                self.end_addrs_coarse_offset = amount as u16;
+                self.end_addrs_coarse_offset = amount;
             }
             GeneratorEnum::ModLfoToVolume => {
                 self.mod_lfo_to_volume = (amount as f32) / 10.0;
@@ -316,7 +362,7 @@ impl Generator {
                 };
             }
             GeneratorEnum::StartloopAddrsCoarseOffset => {
-                self.startloop_addrs_coarse_offset = amount as u16;
+                self.startloop_addrs_coarse_offset = amount;
             }
             GeneratorEnum::Keynum => {
                 self.keynum = Some(amount as u8);
@@ -329,13 +375,13 @@ impl Generator {
             }
             GeneratorEnum::Reserved2 => {}
             GeneratorEnum::EndloopAddrsCoarseOffset => {
-                self.endloop_addrs_coarse_offset = amount as u16;
+                self.endloop_addrs_coarse_offset = amount;
             }
             GeneratorEnum::CoarseTune => {
-                self.coarse_tune = (amount as f32) / 10.0;
+                self.coarse_tune = amount as f32;
             }
             GeneratorEnum::FineTune => {
-                self.fine_tune = amount as i16;
+                self.fine_tune = amount;
             }
             GeneratorEnum::SampleID => {
                 // self.sample_id = Some(amount as usize);
@@ -351,15 +397,69 @@ impl Generator {
                 self.exclusive_class = Some(amount as u8);
             }
             GeneratorEnum::OverridingRootKey => {
-                self.overriding_root_key = Some(amount as u8);
+                // -1は指定なし
+                self.overriding_root_key = if (0..=127).contains(&amount) {
+                    Some(amount as u8)
+                } else {
+                    None
+                };
             }
             GeneratorEnum::Unused5 => {}
             GeneratorEnum::EndOper => {}
         }
     }
+
+    // modulatorの値を足す。amountはset_operと同じ単位(cent, timecent, centibelなど)。
+    // presetのzoneの値もこれで足す。
+    // 範囲やsampleの指定、address offsetなど、presetで使えないgeneratorは変えない
+    pub fn add_oper(&mut self, generator: GeneratorEnum, amount: f32) {
+        let ratio = f32::powf(2.0, amount / 1200.0);
+        match generator {
+            GeneratorEnum::ModLfoToPitch => self.mod_lfo_to_pitch += amount / 100.0,
+            GeneratorEnum::VibLfoToPitch => self.vib_lfo_to_pitch += amount / 100.0,
+            GeneratorEnum::ModEnvToPitch => self.mod_env_to_pitch += amount / 100.0,
+            GeneratorEnum::InitialFilterFc => self.initial_filter_fc *= ratio,
+            GeneratorEnum::InitialFilterQ => self.initial_filter_q += amount / 10.0,
+            GeneratorEnum::ModLfoToFilterFc => self.mod_lfo_to_filter_fc += amount / 100.0,
+            GeneratorEnum::ModEnvToFilterFc => self.mod_env_to_filter_fc += amount / 100.0,
+            GeneratorEnum::ModLfoToVolume => self.mod_lfo_to_volume += amount / 10.0,
+            GeneratorEnum::ChorusEffectsSend => self.chorus_effects_send += amount / 10.0,
+            GeneratorEnum::ReverbEffectsSend => self.reverb_effects_send += amount / 10.0,
+            GeneratorEnum::Pan => self.pan += amount / 10.0,
+            GeneratorEnum::DelayModLFO => self.delay_mod_lfo *= ratio,
+            GeneratorEnum::FreqModLFO => self.freq_mod_lfo *= ratio,
+            GeneratorEnum::DelayVibLFO => self.delay_vib_lfo *= ratio,
+            GeneratorEnum::FreqVibLFO => self.freq_vib_lfo *= ratio,
+            GeneratorEnum::DelayModEnv => self.delay_mod_env *= ratio,
+            GeneratorEnum::AttackModEnv => self.attack_mod_env *= ratio,
+            GeneratorEnum::HoldModEnv => self.hold_mod_env *= ratio,
+            GeneratorEnum::DecayModEnv => self.decay_mod_env *= ratio,
+            GeneratorEnum::SustainModEnv => self.sustain_mod_env += amount / 10.0,
+            GeneratorEnum::ReleaseModEnv => self.release_mod_env *= ratio,
+            GeneratorEnum::KeynumToModEnvHold => self.keynum_to_mod_env_hold += amount / 100.0,
+            GeneratorEnum::KeynumToModEnvDecay => self.keynum_to_mod_env_decay += amount / 100.0,
+            GeneratorEnum::DelayVolEnv => self.delay_vol_env *= ratio,
+            GeneratorEnum::AttackVolEnv => self.attack_vol_env *= ratio,
+            GeneratorEnum::HoldVolEnv => self.hold_vol_env *= ratio,
+            GeneratorEnum::DecayVolEnv => self.decay_vol_env *= ratio,
+            GeneratorEnum::SustainVolEnv => self.sustain_vol_env += amount / 10.0,
+            GeneratorEnum::ReleaseVolEnv => self.release_vol_env *= ratio,
+            GeneratorEnum::KeynumToVolEnvHold => self.keynum_to_vol_env_hold += amount / 100.0,
+            GeneratorEnum::KeynumToVolEnvDecay => self.keynum_to_vol_env_decay += amount / 100.0,
+            GeneratorEnum::InitialAttenuation => self.initial_attenuation += amount / 10.0,
+            GeneratorEnum::CoarseTune => self.coarse_tune += amount,
+            GeneratorEnum::FineTune => {
+                self.fine_tune = self.fine_tune.saturating_add(amount.round() as i16);
+            }
+            GeneratorEnum::ScaleTuning => {
+                self.scale_tuning = (self.scale_tuning as f32 + amount).clamp(0.0, 1200.0) as u16;
+            }
+            _ => {}
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GeneratorEnum {
     StartAddrsOffset,
     EndAddrsOffset,
@@ -492,3 +592,42 @@ impl GeneratorEnum {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inherit() {
+        let mut global = InstrumentGenerator::new();
+        global.set_oper(GeneratorEnum::Pan, 200);
+        global.set_oper(GeneratorEnum::CoarseTune, 2);
+        let mut local = InstrumentGenerator::new();
+        local.set_oper(GeneratorEnum::CoarseTune, -1);
+        local.set_oper(GeneratorEnum::CoarseTune, -3);
+
+        let generator = local.inherit(&global);
+        assert_eq!(generator.generator.pan, 20.0);
+        assert_eq!(generator.generator.coarse_tune, -3.0);
+        assert_eq!(generator.opers.len(), 2);
+    }
+
+    #[test]
+    fn test_add_preset_oper() {
+        let mut generator = Generator::new();
+        generator.set_oper(GeneratorEnum::CoarseTune, -3);
+        generator.set_oper(GeneratorEnum::ScaleTuning, 50);
+        generator.set_oper(GeneratorEnum::OverridingRootKey, -1);
+        generator.add_oper(GeneratorEnum::CoarseTune, 12.0);
+        generator.add_oper(GeneratorEnum::ScaleTuning, 50.0);
+        generator.add_oper(GeneratorEnum::AttackVolEnv, 1200.0);
+        // presetでは使えない
+        generator.add_oper(GeneratorEnum::StartAddrsOffset, 100.0);
+
+        assert_eq!(generator.coarse_tune, 9.0);
+        assert_eq!(generator.scale_tuning, 100);
+        assert!((generator.attack_vol_env - 0.002).abs() < 1e-6);
+        assert_eq!(generator.start_addrs_offset, 0);
+        assert_eq!(generator.overriding_root_key, None);
+    }
+}
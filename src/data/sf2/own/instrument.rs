@@ -2,7 +2,11 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Bound::Included;
 use std::sync::{Arc, RwLock};
 
-use super::generator::InstrumentGenerator;
+use super::super::super::super::error::ToidError;
+use super::generator::{GeneratorEnum, InstrumentGenerator};
+use super::modulator::{Modulator, ModulatorInput};
+use super::sample::{Sample, SampleType};
+use super::voice::SF2Voice;
 
 pub struct Instrument {
     name: String,
@@ -46,10 +50,10 @@ impl Instrument {
         self.prepare_max_vel_range_of_gen();
     }
 
-    pub fn get_sample(&self, key: u8, idx: usize) -> Result<f32, String> {
+    pub fn get_sample(&self, key: u8, vel: u8, idx: usize) -> Result<f32, ToidError> {
         let mut sample = 0.0;
 
-        let gen_set = self.get_generator_from_key_vel(key, 64)?;
+        let gen_set = self.get_generator_from_key_vel(key, vel)?;
         for gen in gen_set.iter() {
             if let Some(sample_obj) = &gen.sample {
                 sample += sample_obj.get_sample(key, idx)?;
@@ -59,22 +63,73 @@ impl Instrument {
         Ok(sample)
     }
 
-    pub fn get_samples(&self, key: u8, start: usize, end: usize) -> Result<Vec<f32>, String> {
-        let mut sample = Vec::new();
-        sample.resize(end - start, 0.0);
+    // note_offはnote onからnote offまでのsample数。その後はvolume envelopeのreleaseになる
+    pub fn get_samples(
+        &self,
+        key: u8,
+        vel: u8,
+        start: usize,
+        end: usize,
+        note_off: usize,
+        sample_rate: f32,
+    ) -> Result<(Vec<f32>, Vec<f32>), ToidError> {
+        let mut left_sample = Vec::new();
+        let mut right_sample = Vec::new();
+        left_sample.resize(end - start, 0.0);
+        right_sample.resize(end - start, 0.0);
+
+        let mut voices = self.get_voices(&ModulatorInput::new(key, vel), &[], &[])?;
+        for voice in voices.iter_mut() {
+            let (left_sample_, right_sample_) =
+                voice.get_samples(start, end, note_off, sample_rate)?;
+
+            for i in 0..end - start {
+                left_sample[i] += left_sample_[i];
+                right_sample[i] += right_sample_[i];
+            }
+        }
+
+        Ok((left_sample, right_sample))
+    }
+
+    // sampleのあるzoneごとに、presetのzoneの値と、
+    // default modulator, zoneのmodulator, presetのmodulatorを足す
+    pub fn get_voices(
+        &self,
+        input: &ModulatorInput,
+        preset_opers: &[(GeneratorEnum, i16)],
+        preset_modulators: &[Modulator],
+    ) -> Result<Vec<SF2Voice>, ToidError> {
+        let mut voices = Vec::new();
 
-        let gen_set = self.get_generator_from_key_vel(key, 64)?;
+        let gen_set = self.get_generator_from_key_vel(input.key, input.vel)?;
         for gen in gen_set.iter() {
             if let Some(sample_obj) = &gen.sample {
-                let sample_ = sample_obj.get_samples(key, start, end)?;
-
-                for i in 0..end - start {
-                    sample[i] += sample_[i];
+                let mut generator = gen.generator.clone();
+                for &(oper, amount) in preset_opers.iter() {
+                    generator.add_oper(oper, amount as f32);
+                }
+                let modulators = Modulator::merge(&Modulator::get_defaults(), &gen.modulators);
+                for modulator in modulators.iter().chain(preset_modulators.iter()) {
+                    modulator.apply(&mut generator, input);
                 }
+                voices.push(SF2Voice::new(input.key, Arc::clone(sample_obj), generator));
             }
         }
 
-        Ok(sample)
+        // 左右の組が両方鳴るときは、左のvoiceが右も鳴らす
+        let left_samples: Vec<Arc<Sample>> = voices
+            .iter()
+            .filter(|voice| voice.sample.typee == SampleType::Left)
+            .map(|voice| Arc::clone(&voice.sample))
+            .collect();
+        voices.retain(|voice| {
+            !left_samples
+                .iter()
+                .any(|left_sample| left_sample.is_linked_to(&voice.sample))
+        });
+
+        Ok(voices)
     }
 
     fn prepare_min_key_range_of_gen(&mut self) {
@@ -153,7 +208,7 @@ impl Instrument {
         &self,
         key: u8,
         vel: u8,
-    ) -> Result<Vec<Arc<InstrumentGenerator>>, String> {
+    ) -> Result<Vec<Arc<InstrumentGenerator>>, ToidError> {
         match self.generator_cache.read() {
             Ok(generator_cache) => match generator_cache.get(&(key, vel)) {
                 Some(instgen_vec) => {
@@ -161,35 +216,51 @@ impl Instrument {
                 }
                 None => {}
             },
-            Err(e) => {
-                return Err(e.to_string());
+            Err(_) => {
+                return Err(ToidError::LockPoisoned);
             }
         };
 
         let mut gen_idx_set_for_min_key = HashSet::new();
-        for (_, value) in Arc::clone(self.min_key_range_of_gen.as_ref().ok_or("as_ref failed")?)
-            .range((Included(&0), Included(&key)))
+        for (_, value) in Arc::clone(
+            self.min_key_range_of_gen
+                .as_ref()
+                .ok_or_else(|| ToidError::SF2Parse("gen range is not prepared".to_string()))?,
+        )
+        .range((Included(&0), Included(&key)))
         {
             gen_idx_set_for_min_key = gen_idx_set_for_min_key.union(value).cloned().collect();
         }
 
         let mut gen_idx_set_for_max_key = HashSet::new();
-        for (_, value) in Arc::clone(self.max_key_range_of_gen.as_ref().ok_or("as_ref failed")?)
-            .range((Included(&key), Included(&127)))
+        for (_, value) in Arc::clone(
+            self.max_key_range_of_gen
+                .as_ref()
+                .ok_or_else(|| ToidError::SF2Parse("gen range is not prepared".to_string()))?,
+        )
+        .range((Included(&key), Included(&127)))
         {
             gen_idx_set_for_max_key = gen_idx_set_for_max_key.union(value).cloned().collect();
         }
 
         let mut gen_idx_set_for_min_vel = HashSet::new();
-        for (_, value) in Arc::clone(self.min_vel_range_of_gen.as_ref().ok_or("as_ref failed")?)
-            .range((Included(&0), Included(&vel)))
+        for (_, value) in Arc::clone(
+            self.min_vel_range_of_gen
+                .as_ref()
+                .ok_or_else(|| ToidError::SF2Parse("gen range is not prepared".to_string()))?,
+        )
+        .range((Included(&0), Included(&vel)))
         {
             gen_idx_set_for_min_vel = gen_idx_set_for_min_vel.union(value).cloned().collect();
         }
 
         let mut gen_idx_set_for_max_vel = HashSet::new();
-        for (_, value) in Arc::clone(self.max_vel_range_of_gen.as_ref().ok_or("as_ref failed")?)
-            .range((Included(&vel), Included(&127)))
+        for (_, value) in Arc::clone(
+            self.max_vel_range_of_gen
+                .as_ref()
+                .ok_or_else(|| ToidError::SF2Parse("gen range is not prepared".to_string()))?,
+        )
+        .range((Included(&vel), Included(&127)))
         {
             gen_idx_set_for_max_vel = gen_idx_set_for_max_vel.union(value).cloned().collect();
         }
@@ -207,15 +278,65 @@ impl Instrument {
 
         let mut gen_set = Vec::new();
         for &gen_idx in gen_idx_set.iter() {
-            gen_set.push(Arc::clone(
-                self.generators.get(gen_idx).ok_or("get failed")?,
-            ));
+            gen_set.push(Arc::clone(self.generators.get(gen_idx).ok_or_else(
+                || ToidError::OutOfRange(format!("gen_idx {}", gen_idx)),
+            )?));
         }
 
         self.generator_cache
-            .write()
-            .map_err(|e| e.to_string())?
+            .write()?
             .insert((key, vel), gen_set.clone());
         Ok(gen_set)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::generator::GeneratorEnum;
+    use super::*;
+
+    fn make_sample(name: &str, start: u32, typee: SampleType) -> Sample {
+        Sample {
+            sample_access: Arc::new(vec![0.0; 2100]),
+            name: name.to_string(),
+            start,
+            end: start + 1000,
+            loopstart: start,
+            loopend: start + 1000,
+            sample_rate: 44100,
+            original_key: 60,
+            correction: 0,
+            sample_link: None,
+            typee,
+        }
+    }
+
+    #[test]
+    fn test_get_voices_of_stereo_link() {
+        let right = make_sample("right", 1000, SampleType::Right);
+        let left = make_sample("left", 0, SampleType::Left);
+        let left = Sample {
+            sample_link: Some(Arc::new(right.clone())),
+            ..left
+        };
+        let right = Sample {
+            sample_link: Some(Arc::new(make_sample("left", 0, SampleType::Left))),
+            ..right
+        };
+
+        let mut instrument = Instrument::new();
+        for (sample, pan) in [(left, -500), (right, 500)] {
+            let mut generator = InstrumentGenerator::new();
+            generator.set_oper(GeneratorEnum::Pan, pan);
+            generator.set_sample(Arc::new(sample));
+            instrument.add_generator(Arc::new(generator));
+        }
+        instrument.prepare_gen_range();
+
+        let voices = instrument
+            .get_voices(&ModulatorInput::new(60, 100), &[], &[])
+            .unwrap();
+        assert_eq!(voices.len(), 1);
+        assert_eq!(voices[0].sample.typee, SampleType::Left);
+    }
+}
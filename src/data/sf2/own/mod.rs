@@ -1,16 +1,23 @@
 pub mod generator;
 pub mod instrument;
+pub mod modulation_envelope;
+pub mod modulator;
 pub mod preset;
 pub mod sample;
+pub mod voice;
+pub mod volume_envelope;
 
 use std::iter::FromIterator;
 use std::sync::Arc;
 
+use super::super::super::error::ToidError;
 use super::parsed;
 use generator::{GeneratorEnum, InstrumentGenerator, PresetGenerator};
 use instrument::Instrument;
+use modulator::{Modulator, ModulatorInput};
 use preset::Preset;
 use sample::{Sample, SampleType};
+use voice::SF2Voice;
 
 pub struct SF2 {
     pub presets: Vec<Arc<Preset>>,
@@ -27,36 +34,56 @@ impl SF2 {
         self.presets.push(preset);
     }
 
-    pub fn parse(i: &[u8]) -> Result<Self, String> {
+    pub fn parse(i: &[u8]) -> Result<Self, ToidError> {
         let parsed_sf2 = parsed::SF2::parse(i)?;
         parsed_sf2_to_own_sf2(parsed_sf2)
     }
 
-    pub fn get_sample(&self, preset_idx: usize, key: u8, idx: usize) -> Result<f32, String> {
+    pub fn get_sample(
+        &self,
+        preset_idx: usize,
+        key: u8,
+        vel: u8,
+        idx: usize,
+    ) -> Result<f32, ToidError> {
         self.presets
             .get(preset_idx)
-            .ok_or("get failed")?
-            .get_sample(key, idx)
+            .ok_or_else(|| ToidError::OutOfRange(format!("preset_idx {}", preset_idx)))?
+            .get_sample(key, vel, idx)
     }
 
     pub fn get_samples(
         &self,
         preset_idx: usize,
         key: u8,
+        vel: u8,
         start: usize,
         end: usize,
-    ) -> Result<Vec<f32>, String> {
+        note_off: usize,
+        sample_rate: f32,
+    ) -> Result<(Vec<f32>, Vec<f32>), ToidError> {
+        self.presets
+            .get(preset_idx)
+            .ok_or_else(|| ToidError::OutOfRange(format!("preset_idx {}", preset_idx)))?
+            .get_samples(key, vel, start, end, note_off, sample_rate)
+    }
+
+    pub fn get_voices(
+        &self,
+        preset_idx: usize,
+        input: &ModulatorInput,
+    ) -> Result<Vec<SF2Voice>, ToidError> {
         self.presets
             .get(preset_idx)
-            .ok_or("invalid preset_idx")?
-            .get_samples(key, start, end)
+            .ok_or_else(|| ToidError::OutOfRange(format!("preset_idx {}", preset_idx)))?
+            .get_voices(input)
     }
 
-    pub fn get_preset_name(&self, preset_idx: usize) -> Result<String, String> {
+    pub fn get_preset_name(&self, preset_idx: usize) -> Result<String, ToidError> {
         let name = self
             .presets
             .get(preset_idx)
-            .ok_or("invalid preset_idx")?
+            .ok_or_else(|| ToidError::OutOfRange(format!("preset_idx {}", preset_idx)))?
             .name
             .clone();
         Ok(name)
@@ -70,7 +97,7 @@ impl SF2 {
     }
 }
 
-fn parsed_sf2_to_own_sf2(parsed_sf2: parsed::SF2) -> Result<SF2, String> {
+fn parsed_sf2_to_own_sf2(parsed_sf2: parsed::SF2) -> Result<SF2, ToidError> {
     let mut own_sf2 = SF2::new();
     let sample_access = Arc::new(Vec::from_iter(
         parsed_sf2
@@ -93,26 +120,47 @@ fn parsed_sf2_to_own_sf2(parsed_sf2: parsed::SF2) -> Result<SF2, String> {
             original_key: sample_header.original_key,
             correction: sample_header.correction,
             sample_link: None,
-            typee: SampleType::from_flg(sample_header.typee).ok_or("from_flg failed")?,
+            typee: SampleType::from_flg(sample_header.typee)
+                .ok_or_else(|| ToidError::SF2Parse("from_flg failed".to_string()))?,
         };
-        let sample = Arc::new(sample);
         samples.push(sample);
     }
 
+    // 左右の組になっているsampleには相手を持たせる
+    let samples: Vec<Arc<Sample>> = samples
+        .iter()
+        .zip(parsed_sf2.pdta.shdr.iter())
+        .map(|(sample, sample_header)| {
+            let mut sample = sample.clone();
+            if let SampleType::Left | SampleType::Right = sample.typee {
+                sample.sample_link = samples
+                    .get(sample_header.sample_link as usize)
+                    .map(|sample_link| Arc::new(sample_link.clone()));
+            }
+            Arc::new(sample)
+        })
+        .collect();
+
     let mut inst_gen_info_sections = Vec::new();
     for ibag in parsed_sf2.pdta.ibag.iter() {
         inst_gen_info_sections.push(ibag.gen_index as usize);
     }
     inst_gen_info_sections.push(parsed_sf2.pdta.igen.len());
 
+    let mut inst_mod_info_sections = Vec::new();
+    for ibag in parsed_sf2.pdta.ibag.iter() {
+        inst_mod_info_sections.push(ibag.mod_index as usize);
+    }
+    inst_mod_info_sections.push(parsed_sf2.pdta.imod.len());
+
     let mut inst_generators = Vec::new();
     for inst_gen_idx in 0..parsed_sf2.pdta.ibag.len() {
         let inst_gen_info_start = inst_gen_info_sections
             .get(inst_gen_idx)
-            .ok_or("get failed")?;
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
         let inst_gen_info_end = inst_gen_info_sections
             .get(inst_gen_idx + 1)
-            .ok_or("get failed")?;
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
 
         let mut generator = InstrumentGenerator::new();
 
@@ -121,17 +169,37 @@ fn parsed_sf2_to_own_sf2(parsed_sf2: parsed::SF2) -> Result<SF2, String> {
                 .pdta
                 .igen
                 .get(inst_gen_info_idx)
-                .ok_or("get failed")?;
-            let gen_oper = GeneratorEnum::from_id(inst_gen_info.gen_oper).ok_or("get failed")?;
+                .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+            let gen_oper = GeneratorEnum::from_id(inst_gen_info.gen_oper)
+                .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
             let gen_amount = inst_gen_info.gen_amount;
 
             generator.set_oper(gen_oper, gen_amount);
             if let GeneratorEnum::SampleID = gen_oper {
                 let sample_idx = gen_amount as usize;
-                generator.set_sample(Arc::clone(samples.get(sample_idx).ok_or("get failed")?));
+                generator.set_sample(Arc::clone(
+                    samples
+                        .get(sample_idx)
+                        .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?,
+                ));
             }
         }
 
+        let inst_mod_info_start = inst_mod_info_sections
+            .get(inst_gen_idx)
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+        let inst_mod_info_end = inst_mod_info_sections
+            .get(inst_gen_idx + 1)
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+        for inst_mod_info_idx in *inst_mod_info_start..*inst_mod_info_end {
+            let inst_mod_info = parsed_sf2
+                .pdta
+                .imod
+                .get(inst_mod_info_idx)
+                .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+            generator.add_modulator(Modulator::from_sf_mod(inst_mod_info));
+        }
+
         let generator = Arc::new(generator);
         inst_generators.push(generator);
     }
@@ -144,15 +212,31 @@ fn parsed_sf2_to_own_sf2(parsed_sf2: parsed::SF2) -> Result<SF2, String> {
 
     let mut instruments = Vec::new();
     for (inst_idx, inst) in parsed_sf2.pdta.inst.iter().enumerate() {
-        let inst_gen_start = inst_gen_sections.get(inst_idx).ok_or("get failed")?;
-        let inst_gen_end = inst_gen_sections.get(inst_idx + 1).ok_or("get failed")?;
+        let inst_gen_start = inst_gen_sections
+            .get(inst_idx)
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+        let inst_gen_end = inst_gen_sections
+            .get(inst_idx + 1)
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
 
         let mut instrument = Instrument::new();
         instrument.set_name(inst.name.clone());
+        // sampleのない最初のzoneはglobal zoneで、他のzoneの初期値になる
+        let mut global_generator = None;
         for inst_gen_idx in *inst_gen_start..*inst_gen_end {
-            instrument.add_generator(Arc::clone(
-                inst_generators.get(inst_gen_idx).ok_or("get failed")?,
-            ));
+            let generator = inst_generators
+                .get(inst_gen_idx)
+                .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+            if inst_gen_idx == *inst_gen_start && generator.sample.is_none() {
+                global_generator = Some(Arc::clone(generator));
+                continue;
+            }
+            match &global_generator {
+                Some(global_generator) => {
+                    instrument.add_generator(Arc::new(generator.inherit(global_generator)));
+                }
+                None => instrument.add_generator(Arc::clone(generator)),
+            }
         }
         instrument.prepare_gen_range();
         instruments.push(Arc::new(instrument));
@@ -164,14 +248,20 @@ fn parsed_sf2_to_own_sf2(parsed_sf2: parsed::SF2) -> Result<SF2, String> {
     }
     preset_gen_info_sections.push(parsed_sf2.pdta.pgen.len());
 
+    let mut preset_mod_info_sections = Vec::new();
+    for pbag in parsed_sf2.pdta.pbag.iter() {
+        preset_mod_info_sections.push(pbag.mod_index as usize);
+    }
+    preset_mod_info_sections.push(parsed_sf2.pdta.pmod.len());
+
     let mut preset_generators = Vec::new();
     for preset_gen_idx in 0..parsed_sf2.pdta.pbag.len() {
         let preset_gen_info_start = preset_gen_info_sections
             .get(preset_gen_idx)
-            .ok_or("get failed")?;
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
         let preset_gen_info_end = preset_gen_info_sections
             .get(preset_gen_idx + 1)
-            .ok_or("get failed")?;
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
 
         let mut generator = PresetGenerator::new();
 
@@ -180,20 +270,37 @@ fn parsed_sf2_to_own_sf2(parsed_sf2: parsed::SF2) -> Result<SF2, String> {
                 .pdta
                 .pgen
                 .get(preset_gen_info_idx)
-                .ok_or("get failed")?;
-            let gen_oper =
-                GeneratorEnum::from_id(preset_gen_info.gen_oper).ok_or("from id failed")?;
+                .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+            let gen_oper = GeneratorEnum::from_id(preset_gen_info.gen_oper)
+                .ok_or_else(|| ToidError::SF2Parse("from id failed".to_string()))?;
             let gen_amount = preset_gen_info.gen_amount;
 
             generator.set_oper(gen_oper, gen_amount);
             if let GeneratorEnum::Instrument = gen_oper {
                 let instrument_idx = gen_amount as usize;
                 generator.set_instrument(Arc::clone(
-                    instruments.get(instrument_idx).ok_or("get failed")?,
+                    instruments
+                        .get(instrument_idx)
+                        .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?,
                 ));
             }
         }
 
+        let preset_mod_info_start = preset_mod_info_sections
+            .get(preset_gen_idx)
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+        let preset_mod_info_end = preset_mod_info_sections
+            .get(preset_gen_idx + 1)
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+        for preset_mod_info_idx in *preset_mod_info_start..*preset_mod_info_end {
+            let preset_mod_info = parsed_sf2
+                .pdta
+                .pmod
+                .get(preset_mod_info_idx)
+                .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+            generator.add_modulator(Modulator::from_sf_mod(preset_mod_info));
+        }
+
         let generator = Arc::new(generator);
         preset_generators.push(generator);
     }
@@ -205,17 +312,31 @@ fn parsed_sf2_to_own_sf2(parsed_sf2: parsed::SF2) -> Result<SF2, String> {
     preset_gen_sections.push(parsed_sf2.pdta.pbag.len());
 
     for (preset_idx, phdr) in parsed_sf2.pdta.phdr.iter().enumerate() {
-        let preset_gen_start = preset_gen_sections.get(preset_idx).ok_or("get failed")?;
+        let preset_gen_start = preset_gen_sections
+            .get(preset_idx)
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
         let preset_gen_end = preset_gen_sections
             .get(preset_idx + 1)
-            .ok_or("get failed")?;
+            .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
 
         let mut preset = Preset::new();
         preset.set_name(phdr.name.clone());
+        // instrumentのない最初のzoneはglobal zoneで、他のzoneの初期値になる
+        let mut global_generator = None;
         for preset_gen_idx in *preset_gen_start..*preset_gen_end {
-            preset.add_generator(Arc::clone(
-                preset_generators.get(preset_gen_idx).ok_or("get failed")?,
-            ));
+            let generator = preset_generators
+                .get(preset_gen_idx)
+                .ok_or_else(|| ToidError::SF2Parse("get failed".to_string()))?;
+            if preset_gen_idx == *preset_gen_start && generator.instrument.is_none() {
+                global_generator = Some(Arc::clone(generator));
+                continue;
+            }
+            match &global_generator {
+                Some(global_generator) => {
+                    preset.add_generator(Arc::new(generator.inherit(global_generator)));
+                }
+                None => preset.add_generator(Arc::clone(generator)),
+            }
         }
         preset.prepare_gen_range();
         own_sf2.add_preset(Arc::new(preset));
@@ -0,0 +1,83 @@
+use super::generator::Generator;
+
+// volume envelopeと同じ形だが、値は0.0 ~ 1.0で直線に変わる。
+// 時間は秒、sustainは1.0から下げる割合
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModulationEnvelope {
+    pub delay: f32,
+    pub attack: f32,
+    pub hold: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl ModulationEnvelope {
+    pub fn from_generator(generator: &Generator, key: u8) -> Self {
+        let key_distance = 60.0 - key as f32;
+        Self {
+            delay: generator.delay_mod_env,
+            attack: generator.attack_mod_env,
+            hold: generator.hold_mod_env
+                * f32::powf(2.0, generator.keynum_to_mod_env_hold * key_distance / 12.0),
+            decay: generator.decay_mod_env
+                * f32::powf(2.0, generator.keynum_to_mod_env_decay * key_distance / 12.0),
+            sustain: (generator.sustain_mod_env / 100.0).clamp(0.0, 1.0),
+            release: generator.release_mod_env,
+        }
+    }
+
+    // decay, releaseは1.0から0.0まで下がる秒数
+    pub fn get_value(&self, sec: f32, note_off_sec: f32) -> f32 {
+        if sec < note_off_sec {
+            return self.get_held_value(sec);
+        }
+        if self.release <= 0.0 {
+            return 0.0;
+        }
+        (self.get_held_value(note_off_sec) - (sec - note_off_sec) / self.release).max(0.0)
+    }
+
+    fn get_held_value(&self, sec: f32) -> f32 {
+        if sec < self.delay {
+            return 0.0;
+        }
+        let sec = sec - self.delay;
+        if sec < self.attack {
+            return sec / self.attack;
+        }
+        let sec = sec - self.attack;
+        if sec < self.hold {
+            return 1.0;
+        }
+        let sec = sec - self.hold;
+        let level = 1.0 - self.sustain;
+        if self.decay > 0.0 {
+            (1.0 - sec / self.decay).max(level)
+        } else {
+            level
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_value() {
+        let mut generator = Generator::new();
+        generator.delay_mod_env = 0.0;
+        generator.attack_mod_env = 0.1;
+        generator.hold_mod_env = 0.0;
+        generator.decay_mod_env = 1.0;
+        generator.sustain_mod_env = 40.0;
+        generator.release_mod_env = 1.0;
+        let envelope = ModulationEnvelope::from_generator(&generator, 60);
+        assert!((envelope.get_value(0.05, 2.0) - 0.5).abs() < 1e-5);
+        assert!((envelope.get_value(0.3, 2.0) - 0.8).abs() < 1e-5);
+        assert!((envelope.get_value(1.0, 2.0) - 0.6).abs() < 1e-5);
+        assert!((envelope.get_value(2.2, 2.0) - 0.4).abs() < 1e-5);
+        assert_eq!(envelope.get_value(3.0, 2.0), 0.0);
+    }
+}
@@ -0,0 +1,279 @@
+use super::super::parsed::pdta::sf_mod::SFMod;
+use super::generator::{Generator, GeneratorEnum};
+
+// modulatorの入力。note onのkey, velocityとMIDIのcontrollerの値
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModulatorInput {
+    pub key: u8,
+    pub vel: u8,
+    pub mod_wheel: u8,
+}
+
+impl ModulatorInput {
+    pub fn new(key: u8, vel: u8) -> Self {
+        Self {
+            key,
+            vel,
+            mod_wheel: 0,
+        }
+    }
+
+    // mod wheel以外のcontrollerはMIDIのreset後の値
+    fn get_cc(&self, cc: u8) -> u8 {
+        match cc {
+            1 => self.mod_wheel,
+            7 => 100,
+            10 => 64,
+            11 => 127,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ModulatorCurve {
+    Linear,
+    Concave,
+    Convex,
+    Switch,
+}
+
+// sfModSrcOperとsfModAmtSrcOper。
+// 0 ~ 6bitがcontroller、7bitがMIDI CCかどうか、8bitが向き、9bitが極性、10 ~ 15bitが曲線
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ModulatorSource {
+    oper: u16,
+}
+
+impl ModulatorSource {
+    fn new(oper: u16) -> Self {
+        Self { oper }
+    }
+
+    fn get_curve(&self) -> Option<ModulatorCurve> {
+        match self.oper >> 10 {
+            0 => Some(ModulatorCurve::Linear),
+            1 => Some(ModulatorCurve::Concave),
+            2 => Some(ModulatorCurve::Convex),
+            3 => Some(ModulatorCurve::Switch),
+            _ => None,
+        }
+    }
+
+    // controllerの値を0.0 ~ 1.0にしたもの。使えないcontrollerはNone
+    fn get_controller_value(&self, input: &ModulatorInput) -> Option<f32> {
+        let index = (self.oper & 0x007F) as u8;
+        if self.oper & 0x0080 != 0 {
+            return Some(input.get_cc(index) as f32 / 128.0);
+        }
+        match index {
+            // No Controllerは1として扱う
+            0 => Some(1.0),
+            2 => Some(input.vel as f32 / 128.0),
+            3 => Some(input.key as f32 / 128.0),
+            // Poly Pressure, Channel Pressure
+            10 | 13 => Some(0.0),
+            // Pitch Wheelは中央、Pitch Wheel Sensitivityは2半音
+            14 => Some(0.5),
+            16 => Some(2.0 / 128.0),
+            _ => None,
+        }
+    }
+
+    fn get_value(&self, input: &ModulatorInput) -> Option<f32> {
+        let value = self.get_controller_value(input)?;
+        let curve = self.get_curve()?;
+        let value = if self.oper & 0x0100 != 0 {
+            1.0 - value
+        } else {
+            value
+        };
+        if self.oper & 0x0200 != 0 {
+            // bipolarは-1.0 ~ 1.0。中央から両側に曲線をかける
+            let value = value * 2.0 - 1.0;
+            Some(value.signum() * apply_curve(curve, value.abs()))
+        } else {
+            Some(apply_curve(curve, value))
+        }
+    }
+}
+
+fn apply_curve(curve: ModulatorCurve, value: f32) -> f32 {
+    match curve {
+        ModulatorCurve::Linear => value,
+        ModulatorCurve::Concave => concave(value),
+        ModulatorCurve::Convex => 1.0 - concave(1.0 - value),
+        ModulatorCurve::Switch => {
+            if value >= 0.5 {
+                1.0
+            } else {
+                0.0
+            }
+        }
+    }
+}
+
+// 音量の変化がdBで直線になる曲線
+fn concave(value: f32) -> f32 {
+    if value >= 1.0 {
+        return 1.0;
+    }
+    (-40.0 / 96.0 * (1.0 - value).log10()).clamp(0.0, 1.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Modulator {
+    source: ModulatorSource,
+    destination: u16,
+    amount: i16,
+    amount_source: ModulatorSource,
+    transform: u16,
+}
+
+impl Modulator {
+    pub fn new(
+        source_oper: u16,
+        destination: u16,
+        amount: i16,
+        amount_source_oper: u16,
+        transform: u16,
+    ) -> Self {
+        Self {
+            source: ModulatorSource::new(source_oper),
+            destination,
+            amount,
+            amount_source: ModulatorSource::new(amount_source_oper),
+            transform,
+        }
+    }
+
+    pub fn from_sf_mod(sf_mod: &SFMod) -> Self {
+        Self::new(
+            sf_mod.src_oper,
+            sf_mod.dest_oper,
+            sf_mod.mod_amount,
+            sf_mod.amt_src_oper,
+            sf_mod.mod_trans_oper,
+        )
+    }
+
+    // SF2 2.04の8.4で決められている、どのzoneにもあるmodulator
+    pub fn get_defaults() -> Vec<Modulator> {
+        vec![
+            // velocity -> InitialAttenuation
+            Self::new(0x0502, 48, 960, 0x0000, 0),
+            // velocity -> InitialFilterFc
+            Self::new(0x0102, 8, -2400, 0x0000, 0),
+            // Channel Pressure -> VibLfoToPitch
+            Self::new(0x000D, 6, 50, 0x0000, 0),
+            // mod wheel -> VibLfoToPitch
+            Self::new(0x0081, 6, 50, 0x0000, 0),
+            // CC7 volume -> InitialAttenuation
+            Self::new(0x0587, 48, 960, 0x0000, 0),
+            // CC10 pan -> Pan
+            Self::new(0x028A, 17, 1000, 0x0000, 0),
+            // CC11 expression -> InitialAttenuation
+            Self::new(0x058B, 48, 960, 0x0000, 0),
+            // CC91 -> ReverbEffectsSend
+            Self::new(0x00DB, 16, 200, 0x0000, 0),
+            // CC93 -> ChorusEffectsSend
+            Self::new(0x00DD, 15, 200, 0x0000, 0),
+            // Pitch Wheel -> FineTune
+            Self::new(0x020E, 52, 12700, 0x0010, 0),
+        ]
+    }
+
+    // source, destination, amount sourceが同じmodulatorは、後から来た方で置き換える
+    pub fn is_identical(&self, other: &Modulator) -> bool {
+        self.source == other.source
+            && self.destination == other.destination
+            && self.amount_source == other.amount_source
+            && self.transform == other.transform
+    }
+
+    pub fn merge(modulators: &[Modulator], overriding_modulators: &[Modulator]) -> Vec<Modulator> {
+        let mut merged: Vec<Modulator> = modulators
+            .iter()
+            .filter(|modulator| {
+                !overriding_modulators
+                    .iter()
+                    .any(|overriding_modulator| overriding_modulator.is_identical(modulator))
+            })
+            .cloned()
+            .collect();
+        merged.extend_from_slice(overriding_modulators);
+        merged
+    }
+
+    // destinationの単位での値。使えないsourceやdestinationなら0
+    pub fn get_value(&self, input: &ModulatorInput) -> f32 {
+        let source = self.source.get_value(input);
+        let amount_source = self.amount_source.get_value(input);
+        match (source, amount_source) {
+            (Some(source), Some(amount_source)) => {
+                let value = self.amount as f32 * source * amount_source;
+                if self.transform == 2 {
+                    value.abs()
+                } else {
+                    value
+                }
+            }
+            _ => 0.0,
+        }
+    }
+
+    // 他のmodulatorにつなぐもの(destinationの15bitが立っているもの)は使わない
+    pub fn apply(&self, generator: &mut Generator, input: &ModulatorInput) {
+        if self.destination & 0x8000 != 0 {
+            return;
+        }
+        if let Some(destination) = GeneratorEnum::from_id(self.destination) {
+            generator.add_oper(destination, self.get_value(input));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_velocity_to_attenuation() {
+        let modulator = Modulator::get_defaults()[0];
+        let attenuation = |vel| modulator.get_value(&ModulatorInput::new(60, vel));
+        assert!(attenuation(127) < 2.0);
+        assert!(attenuation(64) > 100.0);
+        assert!(attenuation(64) < attenuation(32));
+        assert_eq!(attenuation(0), 960.0);
+
+        let mut generator = Generator::new();
+        modulator.apply(&mut generator, &ModulatorInput::new(60, 64));
+        assert!((generator.initial_attenuation - attenuation(64) / 10.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_mod_wheel_to_vibrato() {
+        let mut generator = Generator::new();
+        let input = ModulatorInput {
+            mod_wheel: 128 - 1,
+            ..ModulatorInput::new(60, 100)
+        };
+        for modulator in Modulator::get_defaults().iter() {
+            modulator.apply(&mut generator, &input);
+        }
+        // 50centで、半音の単位で入る
+        assert!((generator.vib_lfo_to_pitch - 0.5 * 127.0 / 128.0).abs() < 1e-5);
+        // CC10とPitch Wheelは中央なので変わらない
+        assert_eq!(generator.pan, 0.0);
+        assert_eq!(generator.fine_tune, 0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let overriding = Modulator::new(0x0502, 48, 480, 0x0000, 0);
+        let merged = Modulator::merge(&Modulator::get_defaults(), &[overriding]);
+        assert_eq!(merged.len(), Modulator::get_defaults().len());
+        assert!(merged.contains(&overriding));
+        assert!(!merged.contains(&Modulator::get_defaults()[0]));
+    }
+}
@@ -2,7 +2,10 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Bound::Included;
 use std::sync::{Arc, RwLock};
 
+use super::super::super::super::error::ToidError;
 use super::generator::PresetGenerator;
+use super::modulator::ModulatorInput;
+use super::voice::SF2Voice;
 
 pub struct Preset {
     pub name: String,
@@ -46,53 +49,78 @@ impl Preset {
         self.prepare_max_vel_range_of_gen();
     }
 
-    pub fn get_sample(&self, key: u8, idx: usize) -> Result<f32, String> {
+    pub fn get_sample(&self, key: u8, vel: u8, idx: usize) -> Result<f32, ToidError> {
         let mut sample = 0.0;
 
-        let gen_set = self.get_generator_from_key_vel(key, 64);
+        let gen_set = self.get_generator_from_key_vel(key, vel);
         match gen_set {
             Ok(gen_set) => {
                 for gen in gen_set.iter() {
                     if let Some(instrument_obj) = &gen.instrument {
-                        sample += instrument_obj.get_sample(key, idx)?;
+                        sample += instrument_obj.get_sample(key, vel, idx)?;
                     }
                 }
             }
+            Err(ToidError::OutOfRange(_)) => {}
             Err(e) => {
-                if e != "Out Of Range".to_string() {
-                    return Err(e);
-                }
+                return Err(e);
             }
         }
 
         Ok(sample)
     }
 
-    pub fn get_samples(&self, key: u8, start: usize, end: usize) -> Result<Vec<f32>, String> {
-        let mut sample = Vec::new();
-        sample.resize(end - start, 0.0);
+    pub fn get_samples(
+        &self,
+        key: u8,
+        vel: u8,
+        start: usize,
+        end: usize,
+        note_off: usize,
+        sample_rate: f32,
+    ) -> Result<(Vec<f32>, Vec<f32>), ToidError> {
+        let mut left_sample = Vec::new();
+        let mut right_sample = Vec::new();
+        left_sample.resize(end - start, 0.0);
+        right_sample.resize(end - start, 0.0);
+
+        let mut voices = self.get_voices(&ModulatorInput::new(key, vel))?;
+        for voice in voices.iter_mut() {
+            let (left_sample_, right_sample_) =
+                voice.get_samples(start, end, note_off, sample_rate)?;
+
+            for i in 0..end - start {
+                left_sample[i] += left_sample_[i];
+                right_sample[i] += right_sample_[i];
+            }
+        }
 
-        let gen_set = self.get_generator_from_key_vel(key, 64);
+        Ok((left_sample, right_sample))
+    }
+
+    pub fn get_voices(&self, input: &ModulatorInput) -> Result<Vec<SF2Voice>, ToidError> {
+        let mut voices = Vec::new();
+
+        let gen_set = self.get_generator_from_key_vel(input.key, input.vel);
         match gen_set {
             Ok(gen_set) => {
                 for gen in gen_set.iter() {
                     if let Some(instrument_obj) = &gen.instrument {
-                        let sample_ = instrument_obj.get_samples(key, start, end)?;
-
-                        for i in 0..end - start {
-                            sample[i] += sample_[i];
-                        }
+                        voices.extend(instrument_obj.get_voices(
+                            input,
+                            &gen.opers,
+                            &gen.modulators,
+                        )?);
                     }
                 }
             }
+            Err(ToidError::OutOfRange(_)) => {}
             Err(e) => {
-                if e != "Out Of Range".to_string() {
-                    return Err(e);
-                }
+                return Err(e);
             }
         }
 
-        Ok(sample)
+        Ok(voices)
     }
 
     fn prepare_min_key_range_of_gen(&mut self) {
@@ -171,11 +199,11 @@ impl Preset {
         &self,
         key: u8,
         vel: u8,
-    ) -> Result<Vec<Arc<PresetGenerator>>, String> {
+    ) -> Result<Vec<Arc<PresetGenerator>>, ToidError> {
         if
         /* key < 0 || */
         key > 127 || /* vel < 0 || */vel > 127 {
-            return Err("Out Of Range".to_string());
+            return Err(ToidError::OutOfRange(format!("key {} vel {}", key, vel)));
         }
 
         match self.generator_cache.read() {
@@ -185,35 +213,51 @@ impl Preset {
                 }
                 None => {}
             },
-            Err(e) => {
-                return Err(e.to_string());
+            Err(_) => {
+                return Err(ToidError::LockPoisoned);
             }
         };
 
         let mut gen_idx_set_for_min_key = HashSet::new();
-        for (_, value) in Arc::clone(self.min_key_range_of_gen.as_ref().ok_or("as_ref failed")?)
-            .range((Included(&0), Included(&key)))
+        for (_, value) in Arc::clone(
+            self.min_key_range_of_gen
+                .as_ref()
+                .ok_or_else(|| ToidError::SF2Parse("gen range is not prepared".to_string()))?,
+        )
+        .range((Included(&0), Included(&key)))
         {
             gen_idx_set_for_min_key = gen_idx_set_for_min_key.union(value).cloned().collect();
         }
 
         let mut gen_idx_set_for_max_key = HashSet::new();
-        for (_, value) in Arc::clone(self.max_key_range_of_gen.as_ref().ok_or("as_ref failed")?)
-            .range((Included(&key), Included(&127)))
+        for (_, value) in Arc::clone(
+            self.max_key_range_of_gen
+                .as_ref()
+                .ok_or_else(|| ToidError::SF2Parse("gen range is not prepared".to_string()))?,
+        )
+        .range((Included(&key), Included(&127)))
         {
             gen_idx_set_for_max_key = gen_idx_set_for_max_key.union(value).cloned().collect();
         }
 
         let mut gen_idx_set_for_min_vel = HashSet::new();
-        for (_, value) in Arc::clone(self.min_vel_range_of_gen.as_ref().ok_or("as_ref failed")?)
-            .range((Included(&0), Included(&vel)))
+        for (_, value) in Arc::clone(
+            self.min_vel_range_of_gen
+                .as_ref()
+                .ok_or_else(|| ToidError::SF2Parse("gen range is not prepared".to_string()))?,
+        )
+        .range((Included(&0), Included(&vel)))
         {
             gen_idx_set_for_min_vel = gen_idx_set_for_min_vel.union(value).cloned().collect();
         }
 
         let mut gen_idx_set_for_max_vel = HashSet::new();
-        for (_, value) in Arc::clone(self.max_vel_range_of_gen.as_ref().ok_or("as_ref failed")?)
-            .range((Included(&vel), Included(&127)))
+        for (_, value) in Arc::clone(
+            self.max_vel_range_of_gen
+                .as_ref()
+                .ok_or_else(|| ToidError::SF2Parse("gen range is not prepared".to_string()))?,
+        )
+        .range((Included(&vel), Included(&127)))
         {
             gen_idx_set_for_max_vel = gen_idx_set_for_max_vel.union(value).cloned().collect();
         }
@@ -231,14 +275,13 @@ impl Preset {
 
         let mut gen_set = Vec::new();
         for &gen_idx in gen_idx_set.iter() {
-            gen_set.push(Arc::clone(
-                self.generators.get(gen_idx).ok_or("get failed")?,
-            ));
+            gen_set.push(Arc::clone(self.generators.get(gen_idx).ok_or_else(
+                || ToidError::OutOfRange(format!("gen_idx {}", gen_idx)),
+            )?));
         }
 
         self.generator_cache
-            .write()
-            .map_err(|e| e.to_string())?
+            .write()?
             .insert((key, vel), gen_set.clone());
         Ok(gen_set)
     }
@@ -1,5 +1,9 @@
 use std::sync::Arc;
 
+use super::super::super::super::error::ToidError;
+use super::generator::Generator;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SampleType {
     Monoral,
     Right,
@@ -19,6 +23,57 @@ impl SampleType {
     }
 }
 
+// SampleModesの値。2はloopしないものとして扱う
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopMode {
+    NoLoop,
+    Continuous,
+    UntilRelease,
+}
+
+impl LoopMode {
+    pub fn from_sample_modes(sample_modes: u8) -> LoopMode {
+        match sample_modes & 0x03 {
+            1 => LoopMode::Continuous,
+            3 => LoopMode::UntilRelease,
+            _ => LoopMode::NoLoop,
+        }
+    }
+
+    // UntilReleaseはnote offの後はloopを抜けて、endまで鳴らす
+    pub fn is_looping(&self, released: bool) -> bool {
+        match self {
+            LoopMode::NoLoop => false,
+            LoopMode::Continuous => true,
+            LoopMode::UntilRelease => !released,
+        }
+    }
+}
+
+// sample_accessの中で読む範囲。generatorのaddress offsetを足したもの
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleRegion {
+    pub start: u32,
+    pub end: u32,
+    pub loopstart: u32,
+    pub loopend: u32,
+}
+
+impl SampleRegion {
+    // startからのsample数で、loopendを越えた分をloopの中に戻したもの
+    pub fn wrap_position(&self, position: f64) -> f64 {
+        let loop_offset = (self.loopstart - self.start) as f64;
+        let loop_length = (self.loopend - self.loopstart) as f64;
+        if loop_length <= 0.0 || position < loop_offset + loop_length {
+            position
+        } else {
+            loop_offset + (position - loop_offset) % loop_length
+        }
+    }
+}
+
+// sample_linkは左右の組になっている相手。相手からはlinkしない
+#[derive(Clone)]
 pub struct Sample {
     pub sample_access: Arc<Vec<f32>>,
     pub name: String,
@@ -34,48 +89,234 @@ pub struct Sample {
 }
 
 impl Sample {
-    pub fn get_sample(&self, key: u8, idx: usize) -> Result<f32, String> {
+    pub fn get_sample(&self, key: u8, idx: usize) -> Result<f32, ToidError> {
         let pitch_shift =
             (key as i16 - self.original_key as i16) as f32 + (self.correction as f32) / 100.0;
         let freq_shift = f32::powf(2.0, pitch_shift / 12.0);
-        let sample_link_idx = self.calculate_idx_of_sample_access(idx as f32 * freq_shift);
-        self.sample_for_float_sample_link_idx(sample_link_idx)
+        self.get_sample_at(idx as f32 * freq_shift)
     }
 
-    pub fn get_samples(&self, key: u8, start: usize, end: usize) -> Result<Vec<f32>, String> {
+    // sample_rateは出力側のsample rate
+    pub fn get_samples(
+        &self,
+        key: u8,
+        start: usize,
+        end: usize,
+        sample_rate: f32,
+    ) -> Result<Vec<f32>, ToidError> {
         let mut sample = Vec::new();
         sample.resize(end - start, 0.0);
 
+        let freq_shift = self.get_pitch_ratio(key, sample_rate);
+
+        for idx in start..end {
+            sample[idx - start] = self.get_sample_at(idx as f32 * freq_shift)?;
+        }
+
+        Ok(sample)
+    }
+
+    // 出力の1sampleで進む、このsampleのsample数
+    pub fn get_pitch_ratio(&self, key: u8, sample_rate: f32) -> f32 {
         let pitch_shift =
             (key as i16 - self.original_key as i16) as f32 + (self.correction as f32) / 100.0;
         let freq_shift = f32::powf(2.0, pitch_shift / 12.0);
-        let freq_shift = freq_shift * self.sample_rate as f32 / 44100.0;
+        freq_shift * self.sample_rate as f32 / sample_rate
+    }
 
-        for idx in start..end {
-            let sample_link_idx = self.calculate_idx_of_sample_access(idx as f32 * freq_shift);
-            sample[idx - start] = self.sample_for_float_sample_link_idx(sample_link_idx)?;
+    // offsetは細かい単位と32768sample単位の組。startからendの外には出ないようにする
+    pub fn get_region(&self, generator: &Generator) -> SampleRegion {
+        let add_offset = |addr: u32, offset: i16, coarse_offset: i16| {
+            (addr as i64 + offset as i64 + coarse_offset as i64 * 32768).max(0) as u32
+        };
+        let max_idx = self.sample_access.len().saturating_sub(1) as u32;
+        let end = add_offset(
+            self.end,
+            generator.end_addrs_offset,
+            generator.end_addrs_coarse_offset,
+        )
+        .min(max_idx);
+        let start = add_offset(
+            self.start,
+            generator.start_addrs_offset,
+            generator.start_addrs_coarse_offset,
+        )
+        .min(end);
+        let loopstart = add_offset(
+            self.loopstart,
+            generator.startloop_addrs_offset,
+            generator.startloop_addrs_coarse_offset,
+        )
+        .clamp(start, end);
+        let loopend = add_offset(
+            self.loopend,
+            generator.endloop_addrs_offset,
+            generator.endloop_addrs_coarse_offset,
+        )
+        .clamp(loopstart, end);
+        SampleRegion {
+            start,
+            end,
+            loopstart,
+            loopend,
         }
+    }
 
-        Ok(sample)
+    // positionはstartからのsample数。loopの中は繰り返す
+    pub fn get_sample_at(&self, position: f32) -> Result<f32, ToidError> {
+        self.get_sample_in_region(&self.get_header_region(), position as f64, true)
     }
 
-    fn calculate_idx_of_sample_access(&self, idx: f32) -> f32 {
-        if idx < (self.loopstart - self.start) as f32 {
-            self.start as f32 + idx
-        } else {
-            self.loopstart as f32
-                + (idx - ((self.loopstart - self.start) as f32))
-                    % ((self.loopend - self.loopstart) as f32)
+    // loopしないときは、endより後は0
+    pub fn get_sample_in_region(
+        &self,
+        region: &SampleRegion,
+        position: f64,
+        looping: bool,
+    ) -> Result<f32, ToidError> {
+        match calculate_idx_of_sample_access(region, position, looping) {
+            Some(sample_link_idx) => self.sample_for_float_sample_link_idx(sample_link_idx),
+            None => Ok(0.0),
+        }
+    }
+
+    // 左右の組の片方で、otherがその相手か
+    pub fn is_linked_to(&self, other: &Sample) -> bool {
+        match (&self.sample_link, self.typee, other.typee) {
+            (Some(sample_link), SampleType::Left, SampleType::Right)
+            | (Some(sample_link), SampleType::Right, SampleType::Left) => {
+                sample_link.start == other.start && sample_link.end == other.end
+            }
+            _ => false,
+        }
+    }
+
+    // sample headerに書かれた範囲
+    fn get_header_region(&self) -> SampleRegion {
+        SampleRegion {
+            start: self.start,
+            end: self.end,
+            loopstart: self.loopstart,
+            loopend: self.loopend,
         }
     }
 
-    fn sample_for_float_sample_link_idx(&self, idx: f32) -> Result<f32, String> {
+    fn sample_for_float_sample_link_idx(&self, idx: f64) -> Result<f32, ToidError> {
         let floor_idx = idx.floor() as usize;
         let ceil_idx = floor_idx + 1;
-        let ratio = 1.0 - (idx % 1.0);
+        let ratio = 1.0 - (idx % 1.0) as f32;
 
-        let floor_sample = *self.sample_access.get(floor_idx).ok_or("get faild")? as f32;
-        let ceil_sample = *self.sample_access.get(ceil_idx).ok_or("get faild")? as f32;
+        let floor_sample = *self
+            .sample_access
+            .get(floor_idx)
+            .ok_or_else(|| ToidError::OutOfRange(format!("sample idx {}", floor_idx)))?;
+        let ceil_sample = *self
+            .sample_access
+            .get(ceil_idx)
+            .ok_or_else(|| ToidError::OutOfRange(format!("sample idx {}", ceil_idx)))?;
         Ok(floor_sample * ratio + ceil_sample * (1.0 - ratio))
     }
 }
+
+fn calculate_idx_of_sample_access(region: &SampleRegion, idx: f64, looping: bool) -> Option<f64> {
+    let idx = if looping {
+        region.wrap_position(idx)
+    } else {
+        idx
+    };
+    if idx < (region.end - region.start) as f64 {
+        Some(region.start as f64 + idx)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::generator::GeneratorEnum;
+    use super::*;
+
+    #[test]
+    fn test_get_region() {
+        let sample = Sample {
+            sample_access: Arc::new((0..100000).map(|i| i as f32).collect()),
+            name: "ramp".to_string(),
+            start: 1000,
+            end: 50000,
+            loopstart: 2000,
+            loopend: 40000,
+            sample_rate: 44100,
+            original_key: 60,
+            correction: 0,
+            sample_link: None,
+            typee: SampleType::Monoral,
+        };
+        let mut generator = Generator::new();
+        generator.set_oper(GeneratorEnum::StartAddrsOffset, 100);
+        generator.set_oper(GeneratorEnum::EndAddrsOffset, -10);
+        generator.set_oper(GeneratorEnum::EndAddrsCoarseOffset, 1);
+        generator.set_oper(GeneratorEnum::StartloopAddrsOffset, -2000);
+        let region = sample.get_region(&generator);
+        assert_eq!(
+            region,
+            SampleRegion {
+                start: 1100,
+                end: 50000 - 10 + 32768,
+                loopstart: 1100,
+                loopend: 40000,
+            }
+        );
+        assert_eq!(
+            sample.get_sample_in_region(&region, 10.5, false).unwrap(),
+            1110.5
+        );
+    }
+
+    #[test]
+    fn test_get_sample_at_far_position() {
+        let sample = Sample {
+            sample_access: Arc::new((0..1000010).map(|i| (i % 2) as f32).collect()),
+            name: "square".to_string(),
+            start: 0,
+            end: 1000005,
+            loopstart: 0,
+            loopend: 1000005,
+            sample_rate: 44100,
+            original_key: 60,
+            correction: 0,
+            sample_link: None,
+            typee: SampleType::Monoral,
+        };
+        // f32だと1000000.3は1000000.3125に丸められてしまう
+        let value = sample
+            .get_sample_in_region(&sample.get_header_region(), 1000000.3, false)
+            .unwrap();
+        assert!((value - 0.3).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_loop() {
+        let region = SampleRegion {
+            start: 100,
+            end: 400,
+            loopstart: 200,
+            loopend: 300,
+        };
+        assert_eq!(region.wrap_position(150.0), 150.0);
+        assert_eq!(region.wrap_position(250.0), 150.0);
+        assert_eq!(region.wrap_position(420.0), 120.0);
+        assert_eq!(
+            calculate_idx_of_sample_access(&region, 250.0, true),
+            Some(250.0)
+        );
+        assert_eq!(
+            calculate_idx_of_sample_access(&region, 250.0, false),
+            Some(350.0)
+        );
+        assert_eq!(calculate_idx_of_sample_access(&region, 320.0, false), None);
+
+        assert_eq!(LoopMode::from_sample_modes(2), LoopMode::NoLoop);
+        assert!(LoopMode::from_sample_modes(3).is_looping(false));
+        assert!(!LoopMode::from_sample_modes(3).is_looping(true));
+    }
+}
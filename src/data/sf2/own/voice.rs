@@ -0,0 +1,387 @@
+use std::sync::Arc;
+
+use super::super::super::super::error::ToidError;
+use super::super::super::super::music_state::synth::filter::StateVariableFilter;
+use super::super::super::music_info::FilterType;
+use super::generator::Generator;
+use super::modulation_envelope::ModulationEnvelope;
+use super::sample::{LoopMode, Sample, SampleRegion, SampleType};
+use super::volume_envelope::VolumeEnvelope;
+
+// 1つのsampleを鳴らすための値。generatorはmodulatorを反映したもの。
+// pitchやfilterが途中で変わるので、どこまで読んだかとfilterの状態を持つ。
+// 左右の組になっているsampleは、相手も同じ位置を読んで両側に出す
+pub struct SF2Voice {
+    pub key: u8,
+    pub sample: Arc<Sample>,
+    pub generator: Generator,
+    volume_envelope: VolumeEnvelope,
+    modulation_envelope: ModulationEnvelope,
+    loop_mode: LoopMode,
+    region: SampleRegion,
+    link_region: Option<SampleRegion>,
+    position: f64,
+    next_idx: usize,
+    filter: StateVariableFilter,
+    link_filter: StateVariableFilter,
+}
+
+impl SF2Voice {
+    pub fn new(key: u8, sample: Arc<Sample>, generator: Generator) -> Self {
+        let volume_envelope = VolumeEnvelope::from_generator(&generator, key);
+        let modulation_envelope = ModulationEnvelope::from_generator(&generator, key);
+        let loop_mode = LoopMode::from_sample_modes(generator.sample_modes);
+        let region = sample.get_region(&generator);
+        let link_region = sample
+            .sample_link
+            .as_ref()
+            .map(|sample_link| sample_link.get_region(&generator));
+        Self {
+            key,
+            sample,
+            generator,
+            volume_envelope,
+            modulation_envelope,
+            loop_mode,
+            region,
+            link_region,
+            position: 0.0,
+            next_idx: 0,
+            filter: StateVariableFilter::new(),
+            link_filter: StateVariableFilter::new(),
+        }
+    }
+
+    // note offの後に鳴る秒数
+    pub fn get_release(&self) -> f32 {
+        self.volume_envelope.release
+    }
+
+    // 出力の1sampleで進む、sampleのsample数。
+    // root keyからの距離をscale tuningで広げ、coarse tune, fine tune, sampleの補正を足す
+    pub fn get_pitch_ratio(&self, sample_rate: f32) -> f64 {
+        let generator = &self.generator;
+        let root_key = generator
+            .overriding_root_key
+            .unwrap_or(self.sample.original_key);
+        let cents = generator.scale_tuning as f64 * (self.key as f64 - root_key as f64)
+            + generator.coarse_tune as f64 * 100.0
+            + generator.fine_tune as f64
+            + self.sample.correction as f64;
+        f64::powf(2.0, cents / 1200.0) * self.sample.sample_rate as f64 / sample_rate as f64
+    }
+
+    // start, end, note_offはnote onからのsample数。左右の組を返す。
+    // 前に読んだ続きでなければ、pitchの揺れとfilterの状態は無視して読み始める
+    pub fn get_samples(
+        &mut self,
+        start: usize,
+        end: usize,
+        note_off: usize,
+        sample_rate: f32,
+    ) -> Result<(Vec<f32>, Vec<f32>), ToidError> {
+        let pitch_ratio = self.get_pitch_ratio(sample_rate);
+        if start != self.next_idx {
+            self.position = start as f64 * pitch_ratio;
+            if self.loop_mode != LoopMode::NoLoop {
+                self.position = self.region.wrap_position(self.position);
+            }
+            self.filter = StateVariableFilter::new();
+            self.link_filter = StateVariableFilter::new();
+        }
+
+        let generator = &self.generator;
+        let gain = f32::powf(10.0, -generator.initial_attenuation.max(0.0) / 20.0);
+        let resonance = get_resonance(generator.initial_filter_q);
+        let note_off_sec = note_off as f32 / sample_rate;
+        // panは-50% ~ 50%。trackのpanと同じく、中央で左右とも1倍
+        let pan = (generator.pan / 50.0).clamp(-1.0, 1.0);
+
+        let mut left_samples = Vec::with_capacity(end - start);
+        let mut right_samples = Vec::with_capacity(end - start);
+        for idx in start..end {
+            let sec = idx as f32 / sample_rate;
+            let vib_lfo = get_lfo_value(sec, generator.delay_vib_lfo, generator.freq_vib_lfo);
+            let mod_lfo = get_lfo_value(sec, generator.delay_mod_lfo, generator.freq_mod_lfo);
+            let mod_env = self.modulation_envelope.get_value(sec, note_off_sec);
+
+            // 単位はどれも半音
+            let pitch = vib_lfo * generator.vib_lfo_to_pitch
+                + mod_lfo * generator.mod_lfo_to_pitch
+                + mod_env * generator.mod_env_to_pitch;
+            let looping = self.loop_mode.is_looping(idx >= note_off);
+            if looping {
+                self.position = self.region.wrap_position(self.position);
+            }
+            let sample = self
+                .sample
+                .get_sample_in_region(&self.region, self.position, looping)?;
+            let link_sample = match (&self.sample.sample_link, &self.link_region) {
+                (Some(sample_link), Some(link_region)) => {
+                    Some(sample_link.get_sample_in_region(link_region, self.position, looping)?)
+                }
+                _ => None,
+            };
+            self.position += pitch_ratio * f64::powf(2.0, pitch as f64 / 12.0);
+
+            let cutoff = generator.initial_filter_fc
+                * f32::powf(
+                    2.0,
+                    (mod_lfo * generator.mod_lfo_to_filter_fc
+                        + mod_env * generator.mod_env_to_filter_fc)
+                        / 12.0,
+                );
+            let sample =
+                self.filter
+                    .process(sample, FilterType::LowPass, cutoff, resonance, sample_rate);
+
+            let link_sample = match link_sample {
+                Some(link_sample) => Some(self.link_filter.process(
+                    link_sample,
+                    FilterType::LowPass,
+                    cutoff,
+                    resonance,
+                    sample_rate,
+                )),
+                None => None,
+            };
+
+            let volume = gain
+                * self.volume_envelope.get_value(sec, note_off_sec)
+                * f32::powf(10.0, mod_lfo * generator.mod_lfo_to_volume / 20.0);
+            match (self.sample.typee, link_sample) {
+                (SampleType::Left, Some(link_sample)) => {
+                    left_samples.push(sample * volume);
+                    right_samples.push(link_sample * volume);
+                }
+                (SampleType::Right, Some(link_sample)) => {
+                    left_samples.push(link_sample * volume);
+                    right_samples.push(sample * volume);
+                }
+                _ => {
+                    left_samples.push((1.0 - pan) * sample * volume);
+                    right_samples.push((1.0 + pan) * sample * volume);
+                }
+            }
+        }
+        self.next_idx = end;
+
+        Ok((left_samples, right_samples))
+    }
+}
+
+// 0から上がり始める三角波。delayまでは0
+fn get_lfo_value(sec: f32, delay: f32, freq: f32) -> f32 {
+    if sec < delay {
+        return 0.0;
+    }
+    let phase = ((sec - delay) * freq + 0.25).fract();
+    1.0 - 4.0 * (phase - 0.5).abs()
+}
+
+// InitialFilterQはcutoffでの持ち上がり(dB)。0dBで山のないfilterになるように3dB引く
+fn get_resonance(filter_q: f32) -> f32 {
+    let q = f32::powf(10.0, (filter_q - 3.01) / 20.0);
+    1.0 - 0.5 / q
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::generator::GeneratorEnum;
+    use super::*;
+
+    fn make_voice(generator: Generator) -> SF2Voice {
+        // 100sampleで1周期のsin
+        let sample_access: Vec<f32> = (0..44200)
+            .map(|i| (2.0 * std::f32::consts::PI * i as f32 / 100.0).sin())
+            .collect();
+        let sample = Sample {
+            sample_access: Arc::new(sample_access),
+            name: "sin".to_string(),
+            start: 0,
+            end: 44100,
+            loopstart: 100,
+            loopend: 44100,
+            sample_rate: 44100,
+            original_key: 60,
+            correction: 0,
+            sample_link: None,
+            typee: SampleType::Monoral,
+        };
+        SF2Voice::new(60, Arc::new(sample), generator)
+    }
+
+    fn get_peak(voice: &mut SF2Voice) -> f32 {
+        let (samples, _) = voice.get_samples(0, 22050, 44100, 44100.0).unwrap();
+        samples[11025..]
+            .iter()
+            .fold(0.0, |peak, x| x.abs().max(peak))
+    }
+
+    #[test]
+    fn test_low_pass_filter() {
+        let open = get_peak(&mut make_voice(Generator::new()));
+        assert!(open > 0.9);
+
+        // 441Hzより十分低いcutoffでは小さくなる
+        let mut generator = Generator::new();
+        generator.set_oper(GeneratorEnum::InitialFilterFc, 5400);
+        let closed = get_peak(&mut make_voice(generator.clone()));
+        assert!(closed < open * 0.2);
+
+        // resonanceがあるとcutoff付近が持ち上がる
+        generator.set_oper(GeneratorEnum::InitialFilterFc, 6900);
+        let flat = get_peak(&mut make_voice(generator.clone()));
+        generator.set_oper(GeneratorEnum::InitialFilterQ, 120);
+        let resonant = get_peak(&mut make_voice(generator));
+        assert!(resonant > flat * 2.0);
+    }
+
+    #[test]
+    fn test_tuning() {
+        let sample_rate = 44100.0;
+        assert_eq!(
+            make_voice(Generator::new()).get_pitch_ratio(sample_rate),
+            1.0
+        );
+
+        let mut generator = Generator::new();
+        generator.set_oper(GeneratorEnum::CoarseTune, 12);
+        generator.set_oper(GeneratorEnum::FineTune, -50);
+        let ratio = make_voice(generator).get_pitch_ratio(sample_rate);
+        assert!((ratio - f64::powf(2.0, 11.5 / 12.0)).abs() < 1e-9);
+
+        // root keyを上げると低くなる。scale tuningが0ならkeyによらない
+        let mut generator = Generator::new();
+        generator.set_oper(GeneratorEnum::OverridingRootKey, 72);
+        assert!((make_voice(generator.clone()).get_pitch_ratio(sample_rate) - 0.5).abs() < 1e-9);
+        generator.set_oper(GeneratorEnum::ScaleTuning, 0);
+        assert_eq!(make_voice(generator).get_pitch_ratio(sample_rate), 1.0);
+    }
+
+    #[test]
+    fn test_pan() {
+        let mut generator = Generator::new();
+        generator.set_oper(GeneratorEnum::Pan, -500);
+        let (left, right) = make_voice(generator)
+            .get_samples(0, 1000, 44100, 44100.0)
+            .unwrap();
+        assert!(left.iter().any(|x| x.abs() > 0.5));
+        assert!(right.iter().all(|&x| x == 0.0));
+    }
+
+    // 0 ~ 1999が1.0で、500 ~ 999がloop
+    fn make_loop_voice(sample_modes: i16) -> SF2Voice {
+        let sample_access: Vec<f32> = (0..2100)
+            .map(|i| if i < 2000 { 1.0 } else { 0.0 })
+            .collect();
+        let sample = Sample {
+            sample_access: Arc::new(sample_access),
+            name: "loop".to_string(),
+            start: 0,
+            end: 2000,
+            loopstart: 500,
+            loopend: 1000,
+            sample_rate: 44100,
+            original_key: 60,
+            correction: 0,
+            sample_link: None,
+            typee: SampleType::Monoral,
+        };
+        let mut generator = Generator::new();
+        generator.set_oper(GeneratorEnum::SampleModes, sample_modes);
+        generator.set_oper(GeneratorEnum::ReleaseVolEnv, 0);
+        SF2Voice::new(60, Arc::new(sample), generator)
+    }
+
+    #[test]
+    fn test_loop_modes() {
+        let (no_loop, _) = make_loop_voice(0)
+            .get_samples(0, 5000, 2500, 44100.0)
+            .unwrap();
+        assert!(no_loop[1900] > 0.0);
+        assert!(no_loop[2100].abs() < 1e-6);
+
+        let (continuous, _) = make_loop_voice(1)
+            .get_samples(0, 5000, 2500, 44100.0)
+            .unwrap();
+        assert!(continuous[100..].iter().all(|&x| x > 0.0));
+
+        // note offのときはloopendにいるので、そこから1000sampleでendまで行く
+        let (until_release, _) = make_loop_voice(3)
+            .get_samples(0, 5000, 2500, 44100.0)
+            .unwrap();
+        assert!(until_release[100..3400].iter().all(|&x| x > 0.0));
+        assert!(until_release[3600].abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_stereo_link() {
+        let sample_access: Vec<f32> = (0..2100)
+            .map(|i| if i < 1000 { 1.0 } else { -1.0 })
+            .collect();
+        let right = Sample {
+            sample_access: Arc::new(sample_access),
+            name: "right".to_string(),
+            start: 1000,
+            end: 2000,
+            loopstart: 1000,
+            loopend: 2000,
+            sample_rate: 44100,
+            original_key: 60,
+            correction: 0,
+            sample_link: None,
+            typee: SampleType::Right,
+        };
+        let left = Sample {
+            name: "left".to_string(),
+            start: 0,
+            end: 1000,
+            loopstart: 0,
+            loopend: 1000,
+            sample_link: Some(Arc::new(right.clone())),
+            typee: SampleType::Left,
+            ..right
+        };
+        // 左右の組ではpanは使わない
+        let mut generator = Generator::new();
+        generator.set_oper(GeneratorEnum::Pan, 500);
+        let (left_samples, right_samples) = SF2Voice::new(60, Arc::new(left), generator)
+            .get_samples(0, 500, 44100, 44100.0)
+            .unwrap();
+        assert!(left_samples[100..].iter().all(|&x| x > 0.9));
+        assert!(right_samples[100..].iter().all(|&x| x < -0.9));
+    }
+
+    #[test]
+    fn test_vibrato() {
+        // 1Hzで1オクターブ揺らす。512sampleずつ続けて読む
+        let mut generator = Generator::new();
+        generator.delay_vib_lfo = 0.0;
+        generator.freq_vib_lfo = 1.0;
+        generator.vib_lfo_to_pitch = 12.0;
+        let mut voice = make_voice(generator);
+        let mut samples = Vec::new();
+        for start in (0..44032).step_by(512) {
+            samples.extend(
+                voice
+                    .get_samples(start, start + 512, 44100, 44100.0)
+                    .unwrap()
+                    .0,
+            );
+        }
+        let count_zero_crossing = |start: usize, end: usize| {
+            samples[start..end]
+                .windows(2)
+                .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+                .count()
+        };
+
+        // 0.25秒で1オクターブ上、0.75秒で1オクターブ下になる
+        let high = count_zero_crossing(8820, 13230);
+        let low = count_zero_crossing(30870, 35280);
+        assert!(high > 70);
+        assert!(low < 30);
+        assert!(samples.windows(2).all(|w| (w[1] - w[0]).abs() < 0.2));
+    }
+}
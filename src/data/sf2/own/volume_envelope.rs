@@ -0,0 +1,110 @@
+use super::generator::Generator;
+
+// 減衰はdBで直線に変わる。decay, releaseは96dB下がるまでの秒数
+const MAX_ATTENUATION: f32 = 96.0;
+
+// 時間は秒、sustainは下げるdB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolumeEnvelope {
+    pub delay: f32,
+    pub attack: f32,
+    pub hold: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl VolumeEnvelope {
+    // holdとdecayはkeyが60から離れるほど伸び縮みする
+    pub fn from_generator(generator: &Generator, key: u8) -> Self {
+        let key_distance = 60.0 - key as f32;
+        Self {
+            delay: generator.delay_vol_env,
+            attack: generator.attack_vol_env,
+            hold: generator.hold_vol_env
+                * f32::powf(2.0, generator.keynum_to_vol_env_hold * key_distance / 12.0),
+            decay: generator.decay_vol_env
+                * f32::powf(2.0, generator.keynum_to_vol_env_decay * key_distance / 12.0),
+            sustain: generator.sustain_vol_env.clamp(0.0, MAX_ATTENUATION),
+            release: generator.release_vol_env,
+        }
+    }
+
+    // note onからsec秒後の音量(0.0 ~ 1.0)。note_off_secより後はその時点の音量からreleaseする
+    pub fn get_value(&self, sec: f32, note_off_sec: f32) -> f32 {
+        if sec < note_off_sec {
+            return self.get_held_value(sec);
+        }
+        let value = self.get_held_value(note_off_sec);
+        if value <= 0.0 || self.release <= 0.0 {
+            return 0.0;
+        }
+        let attenuation =
+            -20.0 * value.log10() + MAX_ATTENUATION * (sec - note_off_sec) / self.release;
+        if attenuation >= MAX_ATTENUATION {
+            0.0
+        } else {
+            f32::powf(10.0, -attenuation / 20.0)
+        }
+    }
+
+    fn get_held_value(&self, sec: f32) -> f32 {
+        if sec < self.delay {
+            return 0.0;
+        }
+        let sec = sec - self.delay;
+        if sec < self.attack {
+            return sec / self.attack;
+        }
+        let sec = sec - self.attack;
+        if sec < self.hold {
+            return 1.0;
+        }
+        let sec = sec - self.hold;
+        let attenuation = if self.decay > 0.0 {
+            (MAX_ATTENUATION * sec / self.decay).min(self.sustain)
+        } else {
+            self.sustain
+        };
+        f32::powf(10.0, -attenuation / 20.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_value() {
+        let envelope = VolumeEnvelope {
+            delay: 0.1,
+            attack: 0.2,
+            hold: 0.1,
+            decay: 0.96,
+            sustain: 20.0,
+            release: 0.96,
+        };
+        assert_eq!(envelope.get_value(0.05, 2.0), 0.0);
+        assert!((envelope.get_value(0.2, 2.0) - 0.5).abs() < 1e-5);
+        assert_eq!(envelope.get_value(0.35, 2.0), 1.0);
+        // decayは1秒で10dB下がって、20dBで止まる
+        assert!((envelope.get_value(0.5, 2.0) - f32::powf(10.0, -0.5)).abs() < 1e-3);
+        assert!((envelope.get_value(1.5, 2.0) - 0.1).abs() < 1e-5);
+        // releaseも1秒で10dB下がって、96dBで0になる
+        assert!((envelope.get_value(2.1, 2.0) - f32::powf(10.0, -1.5)).abs() < 1e-3);
+        assert_eq!(envelope.get_value(2.0 + 0.96, 2.0), 0.0);
+        // attackの途中で離しても、そこから下がる
+        assert!(envelope.get_value(0.21, 0.2) < 0.5);
+        assert!(envelope.get_value(0.21, 0.2) > 0.0);
+    }
+
+    #[test]
+    fn test_keynum_scaling() {
+        let mut generator = Generator::new();
+        generator.hold_vol_env = 1.0;
+        generator.keynum_to_vol_env_hold = 1.0;
+        assert_eq!(VolumeEnvelope::from_generator(&generator, 60).hold, 1.0);
+        assert_eq!(VolumeEnvelope::from_generator(&generator, 72).hold, 0.5);
+        assert_eq!(VolumeEnvelope::from_generator(&generator, 48).hold, 2.0);
+    }
+}
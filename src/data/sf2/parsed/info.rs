@@ -3,6 +3,7 @@ use std::fmt;
 use nom::number::streaming::le_u16;
 use nom::IResult;
 
+use super::super::super::super::error::ToidError;
 use super::super::super::riff::{RiffChunk, RiffData};
 
 pub struct SF2Info {
@@ -72,7 +73,7 @@ fn parse_sfversion(i: &[u8]) -> IResult<&[u8], SFVersion> {
     Ok((i, SFVersion { major, minor }))
 }
 
-pub fn convert_chunk_to_sf2info(chunk: &RiffChunk) -> Result<SF2Info, String> {
+pub fn convert_chunk_to_sf2info(chunk: &RiffChunk) -> Result<SF2Info, ToidError> {
     let mut ifil: Option<SFVersion> = None;
     let mut isng: Option<String> = None;
     let mut inam: Option<String> = None;
@@ -93,67 +94,60 @@ pub fn convert_chunk_to_sf2info(chunk: &RiffChunk) -> Result<SF2Info, String> {
                         match subchunk.id.as_str() {
                             "ifil" => {
                                 let i = data_in_subchunk;
-                                let (_i, ifil_) = parse_sfversion(i).expect("Invalid ifil");
+                                let (_i, ifil_) = parse_sfversion(i)
+                                    .map_err(|_| ToidError::SF2Parse("Invalid ifil".to_string()))?;
                                 ifil = Some(ifil_);
                             }
                             "isng" => {
-                                isng = Some(
-                                    String::from_utf8(data_in_subchunk.to_vec())
-                                        .expect("Invalid isng"),
-                                );
+                                isng = Some(String::from_utf8(data_in_subchunk.to_vec()).map_err(
+                                    |_| ToidError::SF2Parse("Invalid isng".to_string()),
+                                )?);
                             }
                             "INAM" => {
-                                inam = Some(
-                                    String::from_utf8(data_in_subchunk.to_vec())
-                                        .expect("Invalid INAM"),
-                                );
+                                inam = Some(String::from_utf8(data_in_subchunk.to_vec()).map_err(
+                                    |_| ToidError::SF2Parse("Invalid INAM".to_string()),
+                                )?);
                             }
                             "irom" => {
-                                irom = Some(
-                                    String::from_utf8(data_in_subchunk.to_vec())
-                                        .expect("Invalid irom"),
-                                );
+                                irom = Some(String::from_utf8(data_in_subchunk.to_vec()).map_err(
+                                    |_| ToidError::SF2Parse("Invalid irom".to_string()),
+                                )?);
                             }
                             "iver" => {
                                 let i = data_in_subchunk;
-                                let (_i, iver_) = parse_sfversion(i).expect("Invalid iver");
+                                let (_i, iver_) = parse_sfversion(i)
+                                    .map_err(|_| ToidError::SF2Parse("Invalid iver".to_string()))?;
                                 iver = Some(iver_);
                             }
                             "ICRD" => {
-                                icrd = Some(
-                                    String::from_utf8(data_in_subchunk.to_vec())
-                                        .expect("Invalid ICRD"),
-                                );
+                                icrd = Some(String::from_utf8(data_in_subchunk.to_vec()).map_err(
+                                    |_| ToidError::SF2Parse("Invalid ICRD".to_string()),
+                                )?);
                             }
                             "IENG" => {
-                                ieng = Some(
-                                    String::from_utf8(data_in_subchunk.to_vec())
-                                        .expect("Invalid IENG"),
-                                );
+                                ieng = Some(String::from_utf8(data_in_subchunk.to_vec()).map_err(
+                                    |_| ToidError::SF2Parse("Invalid IENG".to_string()),
+                                )?);
                             }
                             "IPRD" => {
-                                iprd = Some(
-                                    String::from_utf8(data_in_subchunk.to_vec())
-                                        .expect("Invalid IPRD"),
-                                );
+                                iprd = Some(String::from_utf8(data_in_subchunk.to_vec()).map_err(
+                                    |_| ToidError::SF2Parse("Invalid IPRD".to_string()),
+                                )?);
                             }
                             "ICOP" => {
-                                icop = Some(
-                                    String::from_utf8(data_in_subchunk.to_vec())
-                                        .expect("Invalid ICOP"),
-                                );
+                                icop = Some(String::from_utf8(data_in_subchunk.to_vec()).map_err(
+                                    |_| ToidError::SF2Parse("Invalid ICOP".to_string()),
+                                )?);
                             }
                             "ICMT" => {
-                                icmt = Some(
-                                    String::from_utf8(data_in_subchunk.to_vec())
-                                        .expect("Invalid ICMT"),
-                                );
+                                icmt = Some(String::from_utf8(data_in_subchunk.to_vec()).map_err(
+                                    |_| ToidError::SF2Parse("Invalid ICMT".to_string()),
+                                )?);
                             }
                             "ISFT" => {
-                                isft = Some(
-                                    String::from_utf8(data_in_subchunk.to_vec())
-                                        .expect("Invalid ISFT"),
-                                );
+                                isft = Some(String::from_utf8(data_in_subchunk.to_vec()).map_err(
+                                    |_| ToidError::SF2Parse("Invalid ISFT".to_string()),
+                                )?);
                             }
                             _ => {}
                         }
@@ -164,9 +158,9 @@ pub fn convert_chunk_to_sf2info(chunk: &RiffChunk) -> Result<SF2Info, String> {
     }
 
     Ok(SF2Info {
-        ifil: ifil.expect("Failed to parse ifil_major"),
-        isng: isng.expect("Failed to parse isng"),
-        inam: inam.expect("Failed to parse inam"),
+        ifil: ifil.ok_or_else(|| ToidError::SF2Parse("Failed to parse ifil_major".to_string()))?,
+        isng: isng.ok_or_else(|| ToidError::SF2Parse("Failed to parse isng".to_string()))?,
+        inam: inam.ok_or_else(|| ToidError::SF2Parse("Failed to parse inam".to_string()))?,
         irom,
         iver,
         icrd,
@@ -5,6 +5,7 @@ pub mod sdta;
 use std::fmt;
 use std::sync::Arc;
 
+use super::super::super::error::ToidError;
 use super::super::riff::{RiffChunk, RiffData};
 use info::{convert_chunk_to_sf2info, SF2Info};
 use pdta::{convert_chunk_to_sf2pdta, SF2pdta};
@@ -17,7 +18,7 @@ pub struct SF2 {
 }
 
 impl SF2 {
-    pub fn parse(i: &[u8]) -> Result<Self, String> {
+    pub fn parse(i: &[u8]) -> Result<Self, ToidError> {
         let chunk = RiffChunk::parse(i)?;
         let sf2 = convert_chunk_to_sf2(&chunk);
         sf2
@@ -35,7 +36,7 @@ impl fmt::Display for SF2 {
     }
 }
 
-fn convert_chunk_to_sf2(chunk: &RiffChunk) -> Result<SF2, String> {
+fn convert_chunk_to_sf2(chunk: &RiffChunk) -> Result<SF2, ToidError> {
     let mut info: Option<SF2Info> = None;
     let mut sdta: Option<SF2sdta> = None;
     let mut pdta: Option<SF2pdta> = None;
@@ -65,15 +66,15 @@ fn convert_chunk_to_sf2(chunk: &RiffChunk) -> Result<SF2, String> {
 
     let info = match info {
         Some(info) => info,
-        None => return Err("Failed to parse info".to_string()),
+        None => return Err(ToidError::SF2Parse("Failed to parse info".to_string())),
     };
     let sdta = match sdta {
         Some(sdta) => sdta,
-        None => return Err("Failed to parse sdta".to_string()),
+        None => return Err(ToidError::SF2Parse("Failed to parse sdta".to_string())),
     };
     let pdta = match pdta {
         Some(pdta) => pdta,
-        None => return Err("Failed to parse pdta".to_string()),
+        None => return Err(ToidError::SF2Parse("Failed to parse pdta".to_string())),
     };
 
     let info = Arc::new(info);
@@ -1,6 +1,7 @@
 use std::fmt;
 use std::sync::Arc;
 
+use super::super::super::super::super::error::ToidError;
 use super::super::super::super::riff::{RiffChunk, RiffData};
 use super::sf_bag::{parse_sf_bags, SFBag};
 use super::sf_gen::{parse_sf_gens, SFGen};
@@ -38,7 +39,7 @@ impl fmt::Display for SF2pdta {
     }
 }
 
-pub fn convert_chunk_to_sf2pdta(chunk: &RiffChunk) -> Result<SF2pdta, String> {
+pub fn convert_chunk_to_sf2pdta(chunk: &RiffChunk) -> Result<SF2pdta, ToidError> {
     let mut phdr: Option<Vec<Arc<SFPresetHeader>>> = None;
     let mut pbag: Option<Vec<Arc<SFBag>>> = None;
     let mut pmod: Option<Vec<Arc<SFMod>>> = None;
@@ -60,49 +61,63 @@ pub fn convert_chunk_to_sf2pdta(chunk: &RiffChunk) -> Result<SF2pdta, String> {
                                     data_in_subchunk,
                                     subchunk.size / 38 - 1,
                                 )
-                                .expect("Invalid phdr");
+                                .map_err(|_| ToidError::SF2Parse("Invalid phdr".to_string()))?;
                                 phdr = Some(phdr_);
                             }
                             "pbag" => {
                                 let (_, pbag_) =
                                     parse_sf_bags(data_in_subchunk, subchunk.size / 4 - 1)
-                                        .expect("Invalid pbag");
+                                        .map_err(|_| {
+                                            ToidError::SF2Parse("Invalid pbag".to_string())
+                                        })?;
                                 pbag = Some(pbag_);
                             }
                             "pmod" => {
                                 let (_, pmod_) =
                                     parse_sf_mods(data_in_subchunk, subchunk.size / 10 - 1)
-                                        .expect("Invalid pmod");
+                                        .map_err(|_| {
+                                            ToidError::SF2Parse("Invalid pmod".to_string())
+                                        })?;
                                 pmod = Some(pmod_);
                             }
                             "pgen" => {
                                 let (_, pgen_) =
                                     parse_sf_gens(data_in_subchunk, subchunk.size / 4 - 1)
-                                        .expect("Invalid pgen");
+                                        .map_err(|_| {
+                                            ToidError::SF2Parse("Invalid pgen".to_string())
+                                        })?;
                                 pgen = Some(pgen_);
                             }
                             "inst" => {
                                 let (_, inst_) =
                                     parse_sf_inst_headers(data_in_subchunk, subchunk.size / 22 - 1)
-                                        .expect("Invalid inst");
+                                        .map_err(|_| {
+                                            ToidError::SF2Parse("Invalid inst".to_string())
+                                        })?;
                                 inst = Some(inst_);
                             }
                             "ibag" => {
                                 let (_, ibag_) =
                                     parse_sf_bags(data_in_subchunk, subchunk.size / 4 - 1)
-                                        .expect("Invalid ibag");
+                                        .map_err(|_| {
+                                            ToidError::SF2Parse("Invalid ibag".to_string())
+                                        })?;
                                 ibag = Some(ibag_);
                             }
                             "imod" => {
                                 let (_, imod_) =
                                     parse_sf_mods(data_in_subchunk, subchunk.size / 10 - 1)
-                                        .expect("Invalid imod");
+                                        .map_err(|_| {
+                                            ToidError::SF2Parse("Invalid imod".to_string())
+                                        })?;
                                 imod = Some(imod_);
                             }
                             "igen" => {
                                 let (_, igen_) =
                                     parse_sf_gens(data_in_subchunk, subchunk.size / 4 - 1)
-                                        .expect("Invalid igen");
+                                        .map_err(|_| {
+                                            ToidError::SF2Parse("Invalid igen".to_string())
+                                        })?;
                                 igen = Some(igen_);
                             }
                             "shdr" => {
@@ -110,7 +125,7 @@ pub fn convert_chunk_to_sf2pdta(chunk: &RiffChunk) -> Result<SF2pdta, String> {
                                     data_in_subchunk,
                                     subchunk.size / 46 - 1,
                                 )
-                                .expect("Invalid shdr");
+                                .map_err(|_| ToidError::SF2Parse("Invalid shdr".to_string()))?;
                                 shdr = Some(shdr_);
                             }
                             _ => {}
@@ -122,14 +137,14 @@ pub fn convert_chunk_to_sf2pdta(chunk: &RiffChunk) -> Result<SF2pdta, String> {
     }
 
     Ok(SF2pdta {
-        phdr: phdr.expect("Failed to parse phdr"),
-        pbag: pbag.expect("Failed to parse pbag"),
-        pmod: pmod.expect("Failed to parse pmod"),
-        pgen: pgen.expect("Failed to parse pgen"),
-        inst: inst.expect("Failed to parse inst"),
-        ibag: ibag.expect("Failed to parse ibag"),
-        imod: imod.expect("Failed to parse imod"),
-        igen: igen.expect("Failed to parse igen"),
-        shdr: shdr.expect("Failed to parse shdr"),
+        phdr: phdr.ok_or_else(|| ToidError::SF2Parse("Failed to parse phdr".to_string()))?,
+        pbag: pbag.ok_or_else(|| ToidError::SF2Parse("Failed to parse pbag".to_string()))?,
+        pmod: pmod.ok_or_else(|| ToidError::SF2Parse("Failed to parse pmod".to_string()))?,
+        pgen: pgen.ok_or_else(|| ToidError::SF2Parse("Failed to parse pgen".to_string()))?,
+        inst: inst.ok_or_else(|| ToidError::SF2Parse("Failed to parse inst".to_string()))?,
+        ibag: ibag.ok_or_else(|| ToidError::SF2Parse("Failed to parse ibag".to_string()))?,
+        imod: imod.ok_or_else(|| ToidError::SF2Parse("Failed to parse imod".to_string()))?,
+        igen: igen.ok_or_else(|| ToidError::SF2Parse("Failed to parse igen".to_string()))?,
+        shdr: shdr.ok_or_else(|| ToidError::SF2Parse("Failed to parse shdr".to_string()))?,
     })
 }
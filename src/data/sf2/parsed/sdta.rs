@@ -5,6 +5,7 @@ use nom::multi::many_m_n;
 use nom::number::streaming::le_i16;
 use nom::IResult;
 
+use super::super::super::super::error::ToidError;
 use super::super::super::riff::{RiffChunk, RiffData};
 
 pub struct SF2sdta {
@@ -33,7 +34,7 @@ fn parse_smpl(i: &[u8], num: usize) -> IResult<&[u8], Vec<i16>> {
     Ok((i, (smpl)))
 }
 
-pub fn convert_chunk_to_sf2sdta(chunk: &RiffChunk) -> Result<SF2sdta, String> {
+pub fn convert_chunk_to_sf2sdta(chunk: &RiffChunk) -> Result<SF2sdta, ToidError> {
     let mut smpl: Option<Vec<i16>> = None;
 
     if let Some(chunk_type) = &chunk.chunk_type {
@@ -44,7 +45,7 @@ pub fn convert_chunk_to_sf2sdta(chunk: &RiffChunk) -> Result<SF2sdta, String> {
                         match subchunk.id.as_str() {
                             "smpl" => {
                                 let (_, smpl_) = parse_smpl(data_in_subchunk, subchunk.size / 2)
-                                    .expect("Invalid smpl");
+                                    .map_err(|_| ToidError::SF2Parse("Invalid smpl".to_string()))?;
                                 smpl = Some(smpl_);
                             }
                             _ => {}
@@ -56,6 +57,8 @@ pub fn convert_chunk_to_sf2sdta(chunk: &RiffChunk) -> Result<SF2sdta, String> {
     }
 
     Ok(SF2sdta {
-        smpl: Arc::new(smpl.expect("Failed to parse smpl")),
+        smpl: Arc::new(
+            smpl.ok_or_else(|| ToidError::SF2Parse("Failed to parse smpl".to_string()))?,
+        ),
     })
 }
@@ -4,6 +4,7 @@ use std::io::Write;
 use nom::number::streaming::{le_i16, le_i24};
 use nom::IResult;
 
+use super::super::super::error::ToidError;
 use super::parsed;
 
 #[derive(Clone, Debug)]
@@ -29,12 +30,12 @@ pub struct Wave {
 }
 
 impl Wave {
-    pub fn parse(i: &[u8]) -> Result<Self, String> {
+    pub fn parse(i: &[u8]) -> Result<Self, ToidError> {
         let parsed_wave = parsed::Wave::parse(i)?;
         Self::parsed_wave_to_own_wave(parsed_wave)
     }
 
-    pub fn get_samples(&self, start: usize, end: usize) -> Result<(Vec<f32>, Vec<f32>), String> {
+    pub fn get_samples(&self, start: usize, end: usize) -> Result<(Vec<f32>, Vec<f32>), ToidError> {
         let mut left_sample = Vec::new();
         let mut right_sample = Vec::new();
 
@@ -65,7 +66,39 @@ impl Wave {
         Ok((left_sample, right_sample))
     }
 
-    fn parsed_wave_to_own_wave(parsed_wave: parsed::Wave) -> Result<Wave, String> {
+    // sample_rateで再生したときのstartからendまでを線形補間で取り出す
+    pub fn get_samples_with_sample_rate(
+        &self,
+        start: usize,
+        end: usize,
+        sample_rate: f32,
+    ) -> Result<(Vec<f32>, Vec<f32>), ToidError> {
+        if sample_rate == self.sample_rate {
+            return self.get_samples(start, end);
+        }
+
+        let sample_width = self.sample_rate / sample_rate;
+        let mut left_sample = Vec::with_capacity(end - start);
+        let mut right_sample = Vec::with_capacity(end - start);
+        for idx in start..end {
+            let sample_idx = idx as f32 * sample_width;
+            match &self.data {
+                Data::Monoral(data) => {
+                    let value = self.vec_f32_access(data, sample_idx);
+                    left_sample.push(value);
+                    right_sample.push(value);
+                }
+                Data::Stereo((left_data, right_data)) => {
+                    left_sample.push(self.vec_f32_access(left_data, sample_idx));
+                    right_sample.push(self.vec_f32_access(right_data, sample_idx));
+                }
+            }
+        }
+
+        Ok((left_sample, right_sample))
+    }
+
+    fn parsed_wave_to_own_wave(parsed_wave: parsed::Wave) -> Result<Wave, ToidError> {
         match parsed_wave.format.channels {
             1 => {
                 let data = Self::parse_monoral_data(
@@ -73,7 +106,7 @@ impl Wave {
                     parsed_wave.data.data.len() / (parsed_wave.format.bitswidth as usize / 8),
                     parsed_wave.format.bitswidth as usize,
                 )
-                .unwrap()
+                .map_err(|e| ToidError::WaveParse(e.to_string()))?
                 .1;
                 Ok(Wave {
                     data,
@@ -88,7 +121,7 @@ impl Wave {
                     parsed_wave.data.data.len() / (parsed_wave.format.bitswidth as usize / 8) / 2,
                     parsed_wave.format.bitswidth as usize,
                 )
-                .unwrap()
+                .map_err(|e| ToidError::WaveParse(e.to_string()))?
                 .1;
                 Ok(Wave {
                     data,
@@ -98,7 +131,10 @@ impl Wave {
                     sample_rate: parsed_wave.format.samplerate as f32,
                 })
             }
-            _ => Err("invalid channel".to_string()),
+            channels => Err(ToidError::WaveParse(format!(
+                "invalid channel {}",
+                channels
+            ))),
         }
     }
 
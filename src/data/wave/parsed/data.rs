@@ -1,3 +1,4 @@
+use super::super::super::super::error::ToidError;
 use super::super::super::riff::{RiffChunk, RiffData};
 
 pub struct DataChunk {
@@ -12,7 +13,7 @@ impl std::fmt::Display for DataChunk {
     }
 }
 
-pub fn convert_chunk_to_data_chunk(chunk: &RiffChunk) -> Result<DataChunk, String> {
+pub fn convert_chunk_to_data_chunk(chunk: &RiffChunk) -> Result<DataChunk, ToidError> {
     if chunk.chunk_type == None {
         if let RiffData::Data(data) = &chunk.data {
             return Ok(DataChunk {
@@ -21,5 +22,5 @@ pub fn convert_chunk_to_data_chunk(chunk: &RiffChunk) -> Result<DataChunk, Strin
         }
     }
 
-    Err("error".to_string())
+    Err(ToidError::WaveParse("invalid data chunk".to_string()))
 }
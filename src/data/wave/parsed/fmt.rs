@@ -1,6 +1,7 @@
 use nom::number::streaming::{le_i16, le_u16, le_u32};
 use nom::IResult;
 
+use super::super::super::super::error::ToidError;
 use super::super::super::riff::{RiffChunk, RiffData};
 
 pub struct FormatChunk {
@@ -41,13 +42,16 @@ fn parse_format_chunk(i: &[u8]) -> IResult<&[u8], FormatChunk> {
     ))
 }
 
-pub fn convert_chunk_to_format_chunk(chunk: &RiffChunk) -> Result<FormatChunk, String> {
+pub fn convert_chunk_to_format_chunk(chunk: &RiffChunk) -> Result<FormatChunk, ToidError> {
     if chunk.chunk_type == None && chunk.size == 16 {
         if let RiffData::Data(data) = &chunk.data {
             let i: &[u8] = data.as_slice();
-            return Ok(parse_format_chunk(i).unwrap().1);
+            return match parse_format_chunk(i) {
+                Ok((_, format_chunk)) => Ok(format_chunk),
+                Err(e) => Err(ToidError::WaveParse(e.to_string())),
+            };
         }
     }
 
-    Err("error".to_string())
+    Err(ToidError::WaveParse("invalid fmt chunk".to_string()))
 }
@@ -3,6 +3,7 @@ mod fmt;
 
 use std::sync::Arc;
 
+use super::super::super::error::ToidError;
 use super::super::riff::{RiffChunk, RiffData};
 use data::{convert_chunk_to_data_chunk, DataChunk};
 use fmt::{convert_chunk_to_format_chunk, FormatChunk};
@@ -13,12 +14,12 @@ pub struct Wave {
 }
 
 impl Wave {
-    pub fn parse(i: &[u8]) -> Result<Self, String> {
+    pub fn parse(i: &[u8]) -> Result<Self, ToidError> {
         let chunk = RiffChunk::parse(i)?;
         Self::convert_from_chunk(&chunk)
     }
 
-    fn convert_from_chunk(chunk: &RiffChunk) -> Result<Wave, String> {
+    fn convert_from_chunk(chunk: &RiffChunk) -> Result<Wave, ToidError> {
         let mut format: Option<FormatChunk> = None;
         let mut data: Option<DataChunk> = None;
 
@@ -34,11 +35,10 @@ impl Wave {
                             } else {
                                 match subchunk.id.as_str() {
                                     "fmt " => {
-                                        format =
-                                            Some(convert_chunk_to_format_chunk(subchunk).unwrap());
+                                        format = Some(convert_chunk_to_format_chunk(subchunk)?);
                                     }
                                     "data" => {
-                                        data = Some(convert_chunk_to_data_chunk(subchunk).unwrap());
+                                        data = Some(convert_chunk_to_data_chunk(subchunk)?);
                                     }
                                     _ => {}
                                 }
@@ -52,11 +52,11 @@ impl Wave {
 
         let format = match format {
             Some(format) => format,
-            None => return Err("Failed to parse format".to_string()),
+            None => return Err(ToidError::WaveParse("Failed to parse format".to_string())),
         };
         let data = match data {
             Some(data) => data,
-            None => return Err("Failed to parse fodatarmat".to_string()),
+            None => return Err(ToidError::WaveParse("Failed to parse data".to_string())),
         };
 
         let format = Arc::new(format);
@@ -0,0 +1,77 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::sync::PoisonError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ToidError {
+    LockPoisoned,
+    Io(String),
+    RiffParse(String),
+    SF2Parse(String),
+    WaveParse(String),
+    ResourceConfig(String),
+    ResourceNotFound(String),
+    InvalidResourceType(String),
+    OutOfRange(String),
+    Serialization(String),
+    Network(String),
+    Audio(String),
+}
+
+impl fmt::Display for ToidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ToidError::LockPoisoned => write!(f, "RwLock Error"),
+            ToidError::Io(e) => write!(f, "io error : {}", e),
+            ToidError::RiffParse(e) => write!(f, "riff parse error : {}", e),
+            ToidError::SF2Parse(e) => write!(f, "sf2 parse error : {}", e),
+            ToidError::WaveParse(e) => write!(f, "wave parse error : {}", e),
+            ToidError::ResourceConfig(e) => write!(f, "resource config error : {}", e),
+            ToidError::ResourceNotFound(name) => write!(f, "resource not found : {}", name),
+            ToidError::InvalidResourceType(e) => write!(f, "invalid resource type : {}", e),
+            ToidError::OutOfRange(e) => write!(f, "out of range : {}", e),
+            ToidError::Serialization(e) => write!(f, "serialization error : {}", e),
+            ToidError::Network(e) => write!(f, "network error : {}", e),
+            ToidError::Audio(e) => write!(f, "audio error : {}", e),
+        }
+    }
+}
+
+impl error::Error for ToidError {}
+
+impl<T> From<PoisonError<T>> for ToidError {
+    fn from(_: PoisonError<T>) -> Self {
+        ToidError::LockPoisoned
+    }
+}
+
+impl From<io::Error> for ToidError {
+    fn from(e: io::Error) -> Self {
+        ToidError::Io(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ToidError {
+    fn from(e: serde_json::Error) -> Self {
+        ToidError::Serialization(e.to_string())
+    }
+}
+
+impl From<toml::de::Error> for ToidError {
+    fn from(e: toml::de::Error) -> Self {
+        ToidError::ResourceConfig(e.to_string())
+    }
+}
+
+impl From<ws::Error> for ToidError {
+    fn from(e: ws::Error) -> Self {
+        ToidError::Network(e.to_string())
+    }
+}
+
+impl From<portaudio::Error> for ToidError {
+    fn from(e: portaudio::Error) -> Self {
+        ToidError::Audio(e.to_string())
+    }
+}
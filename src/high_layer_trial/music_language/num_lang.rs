@@ -1,8 +1,10 @@
 use std::sync::Arc;
 
 use super::super::super::data::music_info::{
-    Beat, Instrument, Phrase, Pitch, PitchInterval, PitchNote,
+    Beat, Instrument, Phrase, Pitch, PitchInterval, PitchNote, Position, ACCENT_VELOCITY,
+    DEFAULT_VELOCITY,
 };
+use super::super::super::error::ToidError;
 use super::super::super::music_state::states::{MusicState, MusicStateEvent};
 use super::super::super::music_state::wave_reader::{WaveReader, WaveReaderEvent};
 use super::super::super::players::player::Player;
@@ -14,9 +16,17 @@ pub fn parse_num_lang(s: String, octave: f32, key: f32) -> Phrase<PitchNote> {
     let mut phrase = Phrase::new();
     let pitch_offset: f32 = octave * 12.0 + key;
 
-    phrase = phrase.set_length(Beat::from(s.len() as f32 / 2.0));
+    // '>'は次の音をアクセントにする。長さには数えない
+    let mut velocity = DEFAULT_VELOCITY;
+    phrase = phrase.set_length(Beat::from(
+        s.chars().filter(|&c| c != '>').count() as f32 / 2.0,
+    ));
 
     for c in s.chars() {
+        if c == '>' {
+            velocity = ACCENT_VELOCITY;
+            continue;
+        }
         let pitch = match c {
             '0' => Some(Pitch::from(47.0)),
             '1' => Some(Pitch::from(48.0)),
@@ -39,12 +49,14 @@ pub fn parse_num_lang(s: String, octave: f32, key: f32) -> Phrase<PitchNote> {
                     }),
                     duration: length_unit,
                     start: now,
+                    velocity,
                 };
                 phrase = phrase.add_note(note);
             }
             None => {}
         }
 
+        velocity = DEFAULT_VELOCITY;
         now = now + length_unit;
     }
     phrase
@@ -54,7 +66,7 @@ pub fn send_num_lang(
     phrase_string: String,
     octave: f32,
     key: f32,
-    section_beat: Beat,
+    section_position: impl Into<Position>,
     phrase_name: String,
     instrument: Instrument,
     vol: f32,
@@ -62,10 +74,10 @@ pub fn send_num_lang(
     player: Arc<
         dyn Player<MusicState, MusicStateEvent, WaveReader, (Vec<i16>, Vec<i16>), WaveReaderEvent>,
     >,
-) -> Result<(), String> {
+) -> Result<(), ToidError> {
     send_pitch_phrase(
         parse_num_lang(phrase_string, octave, key),
-        section_beat,
+        section_position,
         phrase_name,
         instrument,
         vol,
@@ -74,3 +86,34 @@ pub fn send_num_lang(
     )?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accent() {
+        let phrase = parse_num_lang(">1 >3".to_string(), 0.0, 0.0);
+        assert_eq!(phrase.length, Beat::from(1.5));
+
+        let velocities: Vec<(Beat, u8)> = phrase
+            .notes
+            .iter()
+            .flat_map(|(&start, notes)| notes.iter().map(move |note| (start, note.velocity)))
+            .collect();
+        assert_eq!(
+            velocities,
+            vec![
+                (Beat::from(0), ACCENT_VELOCITY),
+                (Beat::from(1), ACCENT_VELOCITY),
+            ]
+        );
+
+        let phrase = parse_num_lang("13".to_string(), 0.0, 0.0);
+        assert!(phrase
+            .notes
+            .values()
+            .flatten()
+            .all(|note| note.velocity == DEFAULT_VELOCITY));
+    }
+}
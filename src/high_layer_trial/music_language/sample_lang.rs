@@ -3,10 +3,13 @@ use std::sync::Arc;
 use nom::branch::alt;
 use nom::bytes::complete::take;
 use nom::character::complete::char;
-use nom::combinator::{iterator, not};
+use nom::combinator::{iterator, not, opt};
 use nom::IResult;
 
-use super::super::super::data::music_info::{Beat, Phrase, SampleNote};
+use super::super::super::data::music_info::{
+    Beat, Phrase, Position, SampleNote, ACCENT_VELOCITY, DEFAULT_VELOCITY,
+};
+use super::super::super::error::ToidError;
 use super::super::super::music_state::states::{MusicState, MusicStateEvent};
 use super::super::super::music_state::wave_reader::{WaveReader, WaveReaderEvent};
 use super::super::super::players::player::Player;
@@ -14,7 +17,7 @@ use super::send_phrase::send_sample_phrase;
 
 #[derive(Clone, Debug, PartialEq)]
 enum Element {
-    Sample(String),
+    Sample(String, u8),
     Tuplet(Tuplet),
 }
 
@@ -53,9 +56,15 @@ fn parse_tuplet(s: &str) -> IResult<&str, Element> {
     Ok((s, Element::Tuplet(Tuplet { elements })))
 }
 
+// '>'を前に付けるとアクセントになる
 fn parse_sample(s: &str) -> IResult<&str, Element> {
+    let (s, accent) = opt(char('>'))(s)?;
     let (s, sample_str) = take(1u8)(s)?;
-    Ok((s, Element::Sample(sample_str.to_string())))
+    let velocity = match accent {
+        Some(_) => ACCENT_VELOCITY,
+        None => DEFAULT_VELOCITY,
+    };
+    Ok((s, Element::Sample(sample_str.to_string(), velocity)))
 }
 
 fn tuplet_to_notes(tuplet: Tuplet, start: Beat, duration: Beat) -> Vec<SampleNote> {
@@ -64,11 +73,12 @@ fn tuplet_to_notes(tuplet: Tuplet, start: Beat, duration: Beat) -> Vec<SampleNot
     let mut ret_notes = vec![];
     for element in tuplet.elements.iter() {
         match element {
-            Element::Sample(sound) => {
+            Element::Sample(sound, velocity) => {
                 if sound != " " {
                     let note = SampleNote {
                         sound: sound.to_string(),
                         start: now,
+                        velocity: *velocity,
                     };
                     ret_notes.push(note)
                 }
@@ -95,11 +105,12 @@ pub fn parse_sample_lang(s: String) -> Phrase<SampleNote> {
 
     for element in elements.iter() {
         match element {
-            Element::Sample(sound) => {
+            Element::Sample(sound, velocity) => {
                 if sound != " " {
                     let note = SampleNote {
                         sound: sound.to_string(),
                         start: now,
+                        velocity: *velocity,
                     };
                     phrase = phrase.add_note(note);
                 }
@@ -119,7 +130,7 @@ pub fn parse_sample_lang(s: String) -> Phrase<SampleNote> {
 
 pub fn send_sample_lang(
     phrase_string: String,
-    section_beat: Beat,
+    section_position: impl Into<Position>,
     phrase_name: String,
     sample_name: String,
     vol: f32,
@@ -127,10 +138,10 @@ pub fn send_sample_lang(
     player: Arc<
         dyn Player<MusicState, MusicStateEvent, WaveReader, (Vec<i16>, Vec<i16>), WaveReaderEvent>,
     >,
-) -> Result<(), String> {
+) -> Result<(), ToidError> {
     send_sample_phrase(
         parse_sample_lang(phrase_string),
-        section_beat,
+        section_position,
         phrase_name,
         sample_name,
         vol,
@@ -148,12 +159,31 @@ mod tests {
     fn test_parse_elements() {
         let s = "xo[-x]";
         let true_elements = vec![
-            Element::Sample("x".to_string()),
-            Element::Sample("o".to_string()),
+            Element::Sample("x".to_string(), DEFAULT_VELOCITY),
+            Element::Sample("o".to_string(), DEFAULT_VELOCITY),
             Element::Tuplet(Tuplet {
                 elements: vec![
-                    Element::Sample("-".to_string()),
-                    Element::Sample("x".to_string()),
+                    Element::Sample("-".to_string(), DEFAULT_VELOCITY),
+                    Element::Sample("x".to_string(), DEFAULT_VELOCITY),
+                ],
+            }),
+        ];
+
+        let (_, elements) = parse_elements(s).unwrap();
+
+        assert_eq!(elements, true_elements);
+    }
+
+    #[test]
+    fn test_parse_accent() {
+        let s = ">xo[->x]";
+        let true_elements = vec![
+            Element::Sample("x".to_string(), ACCENT_VELOCITY),
+            Element::Sample("o".to_string(), DEFAULT_VELOCITY),
+            Element::Tuplet(Tuplet {
+                elements: vec![
+                    Element::Sample("-".to_string(), DEFAULT_VELOCITY),
+                    Element::Sample("x".to_string(), ACCENT_VELOCITY),
                 ],
             }),
         ];
@@ -1,30 +1,45 @@
 use std::sync::Arc;
 
 use super::super::super::data::music_info::{
-    Beat, Instrument, Phrase, PitchNote, SampleNote, Track,
+    Beat, Instrument, Phrase, PitchNote, Position, SampleNote, Track,
 };
+use super::super::super::error::ToidError;
 use super::super::super::music_state::states::{MusicState, MusicStateEvent, SectionStateEvent};
 use super::super::super::music_state::wave_reader::{WaveReader, WaveReaderEvent};
 use super::super::super::players::player::Player;
 
+type MusicPlayer =
+    Arc<dyn Player<MusicState, MusicStateEvent, WaveReader, (Vec<i16>, Vec<i16>), WaveReaderEvent>>;
+
+// BarPositionで指定された場合は、現在のstateの拍子でBeatに変換する
+fn get_section_beat(section_position: Position, player: &MusicPlayer) -> Result<Beat, ToidError> {
+    match section_position {
+        Position::Beat(beat) => Ok(beat),
+        Position::Bar(bar_position) => Ok(player
+            .get_store()
+            .get_state()?
+            .scheduling
+            .bar_position_to_beat(bar_position)),
+    }
+}
+
 pub fn send_pitch_phrase(
     phrase: Phrase<PitchNote>,
-    section_beat: Beat,
+    section_position: impl Into<Position>,
     track_name: String,
     instrument: Instrument,
     vol: f32,
     pan: f32,
-    player: Arc<
-        dyn Player<MusicState, MusicStateEvent, WaveReader, (Vec<i16>, Vec<i16>), WaveReaderEvent>,
-    >,
-) -> Result<(), String> {
+    player: MusicPlayer,
+) -> Result<(), ToidError> {
     let track = Track {
         phrase,
         instrument,
-        effects: vec![],
         vol,
         pan,
+        ..Track::new()
     };
+    let section_beat = get_section_beat(section_position.into(), &player)?;
     player.send_event(MusicStateEvent::SectionStateEvent(
         section_beat,
         SectionStateEvent::NewPitchTrack(track_name.clone(), track),
@@ -34,22 +49,21 @@ pub fn send_pitch_phrase(
 
 pub fn send_sample_phrase(
     phrase: Phrase<SampleNote>,
-    section_beat: Beat,
+    section_position: impl Into<Position>,
     track_name: String,
     sample_name: String,
     vol: f32,
     pan: f32,
-    player: Arc<
-        dyn Player<MusicState, MusicStateEvent, WaveReader, (Vec<i16>, Vec<i16>), WaveReaderEvent>,
-    >,
-) -> Result<(), String> {
+    player: MusicPlayer,
+) -> Result<(), ToidError> {
     let track = Track {
         phrase,
         instrument: Instrument::Sample(sample_name),
-        effects: vec![],
         vol,
         pan,
+        ..Track::new()
     };
+    let section_beat = get_section_beat(section_position.into(), &player)?;
     player.send_event(MusicStateEvent::SectionStateEvent(
         section_beat,
         SectionStateEvent::NewSampleTrack(track_name.clone(), track),
@@ -59,12 +73,11 @@ pub fn send_sample_phrase(
 
 pub fn send_pitch_track(
     track: Track<PitchNote>,
-    section_beat: Beat,
+    section_position: impl Into<Position>,
     track_name: String,
-    player: Arc<
-        dyn Player<MusicState, MusicStateEvent, WaveReader, (Vec<i16>, Vec<i16>), WaveReaderEvent>,
-    >,
-) -> Result<(), String> {
+    player: MusicPlayer,
+) -> Result<(), ToidError> {
+    let section_beat = get_section_beat(section_position.into(), &player)?;
     player.send_event(MusicStateEvent::SectionStateEvent(
         section_beat,
         SectionStateEvent::NewPitchTrack(track_name.clone(), track),
@@ -74,12 +87,11 @@ pub fn send_pitch_track(
 
 pub fn send_sample_track(
     track: Track<SampleNote>,
-    section_beat: Beat,
+    section_position: impl Into<Position>,
     track_name: String,
-    player: Arc<
-        dyn Player<MusicState, MusicStateEvent, WaveReader, (Vec<i16>, Vec<i16>), WaveReaderEvent>,
-    >,
-) -> Result<(), String> {
+    player: MusicPlayer,
+) -> Result<(), ToidError> {
+    let section_beat = get_section_beat(section_position.into(), &player)?;
     player.send_event(MusicStateEvent::SectionStateEvent(
         section_beat,
         SectionStateEvent::NewSampleTrack(track_name.clone(), track),
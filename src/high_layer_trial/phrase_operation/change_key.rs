@@ -11,6 +11,7 @@ pub fn change_key(phrase: Phrase<PitchNote>, interval: PitchInterval) -> Phrase<
                 pitch: note.pitch.add_interval(interval),
                 duration: note.duration,
                 start: note.start,
+                velocity: note.velocity,
             });
         }
         new_notes.insert(start, new_note_set);
@@ -42,6 +42,7 @@ pub fn change_pitch_in_key(
                 pitch: note.pitch.add_interval(PitchInterval { interval: offset }),
                 duration: note.duration,
                 start: note.start,
+                velocity: note.velocity,
             });
         }
     }
@@ -1,4 +1,6 @@
-use super::super::super::data::music_info::{Beat, ChordProgression, Phrase, Pitch, PitchNote};
+use super::super::super::data::music_info::{
+    Beat, ChordProgression, Phrase, Pitch, PitchNote, DEFAULT_VELOCITY,
+};
 
 pub fn four_bass(prog: ChordProgression) -> Phrase<PitchNote> {
     let mut ph = Phrase::new();
@@ -9,6 +11,7 @@ pub fn four_bass(prog: ChordProgression) -> Phrase<PitchNote> {
             pitch,
             duration: Beat::from(1),
             start: Beat::from(i as i32),
+            velocity: DEFAULT_VELOCITY,
         });
     }
     ph = ph.set_length(prog.length);
@@ -1,4 +1,6 @@
-use super::super::super::data::music_info::{Beat, ChordProgression, Phrase, Pitch, PitchNote};
+use super::super::super::data::music_info::{
+    Beat, ChordProgression, Phrase, Pitch, PitchNote, DEFAULT_VELOCITY,
+};
 
 pub fn four_comp(prog: ChordProgression, min_pitch: Pitch, max_pitch: Pitch) -> Phrase<PitchNote> {
     let mut ph = Phrase::new();
@@ -11,6 +13,7 @@ pub fn four_comp(prog: ChordProgression, min_pitch: Pitch, max_pitch: Pitch) ->
                 pitch,
                 duration: Beat::from(1),
                 start: Beat::from(i as i32),
+                velocity: DEFAULT_VELOCITY,
             });
         }
     }
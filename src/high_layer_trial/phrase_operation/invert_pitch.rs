@@ -10,6 +10,7 @@ pub fn invert_pitch(phrase: Phrase<PitchNote>, center: Pitch) -> Phrase<PitchNot
                 pitch: center.sub_interval(note.pitch - center),
                 duration: note.duration,
                 start: note.start,
+                velocity: note.velocity,
             });
         }
     }
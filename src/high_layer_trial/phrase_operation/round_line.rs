@@ -3,7 +3,9 @@ use std::ops::Bound::{Excluded, Included, Unbounded};
 
 use itertools::izip;
 
-use super::super::super::data::music_info::{Beat, Phrase, Pitch, PitchNote, Scale};
+use super::super::super::data::music_info::{
+    Beat, Phrase, Pitch, PitchNote, Scale, DEFAULT_VELOCITY,
+};
 
 pub fn round_line(
     line_beat: Vec<Beat>,
@@ -69,12 +71,14 @@ pub fn round_line(
                         pitch: down_pitch,
                         start: start_beat,
                         duration: duration_beat,
+                        velocity: DEFAULT_VELOCITY,
                     });
                 } else {
                     phrase = phrase.add_note(PitchNote {
                         pitch: up_pitch,
                         start: start_beat,
                         duration: duration_beat,
+                        velocity: DEFAULT_VELOCITY,
                     });
                 }
             }
@@ -83,6 +87,7 @@ pub fn round_line(
                     pitch: up_pitch,
                     start: start_beat,
                     duration: duration_beat,
+                    velocity: DEFAULT_VELOCITY,
                 });
             }
             (None, Some(&down_pitch)) => {
@@ -90,6 +95,7 @@ pub fn round_line(
                     pitch: down_pitch,
                     start: start_beat,
                     duration: duration_beat,
+                    velocity: DEFAULT_VELOCITY,
                 });
             }
             (None, None) => {
@@ -5,6 +5,7 @@
 extern crate lazy_static;
 
 pub mod data;
+pub mod error;
 pub mod high_layer_trial;
 pub mod music_state;
 pub mod outputters;
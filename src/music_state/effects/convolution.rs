@@ -4,8 +4,6 @@ use super::fft::{fft_64, ifft_64};
 use super::ring_buffer::RingBuffer;
 use super::Effect;
 
-const FRAMES_PER_BUFFER: usize = 512;
-
 pub struct ConvolutionEffect {
     fft_filter: Vec<Vec<Complex<f64>>>,
     fft_left_sample: RingBuffer<Vec<Complex<f64>>>,
@@ -14,20 +12,20 @@ pub struct ConvolutionEffect {
     residual_right_responce: Vec<f32>,
     dry: f32,
     wet: f32,
-    // block_size: usize,
+    frames_per_buffer: usize,
 }
 
 impl ConvolutionEffect {
-    pub fn new(filter: &Vec<f32>, dry: f32, wet: f32) -> Self {
+    pub fn new(filter: &[f32], dry: f32, wet: f32, frames_per_buffer: usize) -> Self {
         let mut fft_filter = vec![];
-        let block_size = (filter.len() - 1) / FRAMES_PER_BUFFER + 1;
+        let block_size = (filter.len() - 1) / frames_per_buffer + 1;
         for block_idx in 0..block_size {
-            let mut fft_filter_ = vec![Complex::new(0.0, 0.0); FRAMES_PER_BUFFER * 2];
+            let mut fft_filter_ = vec![Complex::new(0.0, 0.0); frames_per_buffer * 2];
 
-            for sample_idx in 0..FRAMES_PER_BUFFER {
-                if sample_idx + block_idx * FRAMES_PER_BUFFER < filter.len() {
+            for sample_idx in 0..frames_per_buffer {
+                if sample_idx + block_idx * frames_per_buffer < filter.len() {
                     fft_filter_[sample_idx] = Complex::new(
-                        filter[sample_idx + block_idx * FRAMES_PER_BUFFER] as f64,
+                        filter[sample_idx + block_idx * frames_per_buffer] as f64,
                         0.0,
                     );
                 }
@@ -37,53 +35,55 @@ impl ConvolutionEffect {
 
         let fft_left_sample = RingBuffer::new(
             block_size,
-            vec![Complex::new(0.0, 0.0); FRAMES_PER_BUFFER * 2],
+            vec![Complex::new(0.0, 0.0); frames_per_buffer * 2],
         );
         let fft_right_sample = RingBuffer::new(
             block_size,
-            vec![Complex::new(0.0, 0.0); FRAMES_PER_BUFFER * 2],
+            vec![Complex::new(0.0, 0.0); frames_per_buffer * 2],
         );
 
         Self {
             fft_filter,
             fft_left_sample,
             fft_right_sample,
-            residual_left_responce: vec![0.0; FRAMES_PER_BUFFER],
-            residual_right_responce: vec![0.0; FRAMES_PER_BUFFER],
+            residual_left_responce: vec![0.0; frames_per_buffer],
+            residual_right_responce: vec![0.0; frames_per_buffer],
             dry,
             wet,
-            // block_size,
+            frames_per_buffer,
         }
     }
 }
 
 impl Effect for ConvolutionEffect {
     fn effect(&mut self, left_wave: &Vec<f32>, right_wave: &Vec<f32>) -> (Vec<f32>, Vec<f32>) {
-        let mut fft_left_sample_ = vec![Complex::new(0.0, 0.0); FRAMES_PER_BUFFER * 2];
-        let mut fft_right_sample_ = vec![Complex::new(0.0, 0.0); FRAMES_PER_BUFFER * 2];
-        for sample_idx in 0..FRAMES_PER_BUFFER {
+        let mut fft_left_sample_ = vec![Complex::new(0.0, 0.0); self.frames_per_buffer * 2];
+        let mut fft_right_sample_ = vec![Complex::new(0.0, 0.0); self.frames_per_buffer * 2];
+        for sample_idx in 0..self.frames_per_buffer {
             fft_left_sample_[sample_idx] = Complex::new(left_wave[sample_idx] as f64, 0.0);
             fft_right_sample_[sample_idx] = Complex::new(right_wave[sample_idx] as f64, 0.0);
         }
         self.fft_left_sample.push(fft_64(&fft_left_sample_));
         self.fft_right_sample.push(fft_64(&fft_right_sample_));
 
-        let mut convolued_fft_left_sample = vec![Complex::new(0.0, 0.0); FRAMES_PER_BUFFER * 2];
+        let mut convolued_fft_left_sample =
+            vec![Complex::new(0.0, 0.0); self.frames_per_buffer * 2];
         for (fft_left_sample_, fft_filter_) in
             self.fft_left_sample.iter().zip(self.fft_filter.iter())
         {
-            for sample_idx in 0..FRAMES_PER_BUFFER * 2 {
+            for sample_idx in 0..self.frames_per_buffer * 2 {
                 convolued_fft_left_sample[sample_idx] +=
                     fft_left_sample_[sample_idx] * fft_filter_[sample_idx];
             }
         }
         let convolved_left_sample = ifft_64(&convolued_fft_left_sample);
 
-        let mut convolued_fft_right_sample = vec![Complex::new(0.0, 0.0); FRAMES_PER_BUFFER * 2];
+        let mut convolued_fft_right_sample =
+            vec![Complex::new(0.0, 0.0); self.frames_per_buffer * 2];
         for (fft_right_sample_, fft_filter_) in
             self.fft_right_sample.iter().zip(self.fft_filter.iter())
         {
-            for sample_idx in 0..FRAMES_PER_BUFFER * 2 {
+            for sample_idx in 0..self.frames_per_buffer * 2 {
                 convolued_fft_right_sample[sample_idx] +=
                     fft_right_sample_[sample_idx] * fft_filter_[sample_idx];
             }
@@ -94,7 +94,7 @@ impl Effect for ConvolutionEffect {
         let mut new_right_wave = vec![];
         let mut residual_left_responce = vec![];
         let mut residual_right_responce = vec![];
-        for sample_idx in 0..FRAMES_PER_BUFFER {
+        for sample_idx in 0..self.frames_per_buffer {
             new_left_wave.push(
                 convolved_left_sample[sample_idx].re as f32
                     + self.residual_left_responce[sample_idx],
@@ -105,9 +105,9 @@ impl Effect for ConvolutionEffect {
             );
 
             residual_left_responce
-                .push(convolved_left_sample[FRAMES_PER_BUFFER + sample_idx].re as f32);
+                .push(convolved_left_sample[self.frames_per_buffer + sample_idx].re as f32);
             residual_right_responce
-                .push(convolved_right_sample[FRAMES_PER_BUFFER + sample_idx].re as f32);
+                .push(convolved_right_sample[self.frames_per_buffer + sample_idx].re as f32);
         }
 
         self.residual_left_responce = residual_left_responce;
@@ -146,7 +146,7 @@ mod tests {
         for i in 0..700 {
             filter.push((i + 1) as f32);
         }
-        let mut conv_effect = ConvolutionEffect::new(&filter, 0.0, 1.0);
+        let mut conv_effect = ConvolutionEffect::new(&filter, 0.0, 1.0, 512);
 
         let mut input1 = vec![];
         let mut input2 = vec![];
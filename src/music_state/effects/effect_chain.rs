@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use super::super::super::resource_management::resource_manager::ResourceManager;
+use super::super::render_config::RenderConfig;
+use super::{Effect, EffectInfo};
+
+// EffectInfoが変わらなかったEffectは作り直さずに使い回し、reverbの残響などを途切れさせない
+pub struct EffectChain {
+    effect_infos: Vec<EffectInfo>,
+    effects: Vec<Box<dyn Effect + Sync + Send>>,
+    render_config: RenderConfig,
+}
+
+impl EffectChain {
+    pub fn new(render_config: RenderConfig) -> Self {
+        Self {
+            effect_infos: vec![],
+            effects: vec![],
+            render_config,
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        new_effect_infos: &[EffectInfo],
+        resource_manager: Arc<ResourceManager>,
+    ) {
+        if self.effect_infos == new_effect_infos {
+            return;
+        }
+
+        let mut old_effects: Vec<Option<(EffectInfo, Box<dyn Effect + Sync + Send>)>> = self
+            .effect_infos
+            .drain(..)
+            .zip(self.effects.drain(..))
+            .map(Some)
+            .collect();
+
+        let mut effects = vec![];
+        for new_effect_info in new_effect_infos.iter() {
+            // 同じEffectInfoを持つ未使用のEffectがあれば、順番が変わっていても使い回す
+            let reusable = old_effects.iter_mut().find(|old| match old {
+                Some((old_effect_info, _)) => old_effect_info == new_effect_info,
+                None => false,
+            });
+            match reusable.and_then(|old| old.take()) {
+                Some((_, effect)) => effects.push(effect),
+                None => effects.push(
+                    new_effect_info.get_effect(Arc::clone(&resource_manager), &self.render_config),
+                ),
+            }
+        }
+
+        self.effect_infos = new_effect_infos.to_vec();
+        self.effects = effects;
+    }
+
+    pub fn effect(&mut self, left_wave: Vec<f32>, right_wave: Vec<f32>) -> (Vec<f32>, Vec<f32>) {
+        let mut left_wave = left_wave;
+        let mut right_wave = right_wave;
+        for effect in self.effects.iter_mut() {
+            let (l, r) = effect.effect(&left_wave, &right_wave);
+            left_wave = l;
+            right_wave = r;
+        }
+        (left_wave, right_wave)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reuse_unchanged_effects() {
+        let resource_manager = Arc::new(ResourceManager::new());
+        let mut chain = EffectChain::new(RenderConfig::default());
+        chain.update(
+            &[
+                EffectInfo::SchroederReverb(1.0, 0.5),
+                EffectInfo::ToLeftEffect,
+            ],
+            Arc::clone(&resource_manager),
+        );
+
+        // 残響を溜めておく
+        let mut impulse = vec![0.0; 512];
+        impulse[0] = 1.0;
+        chain.effect(impulse.clone(), impulse);
+
+        // reverbの前にToLeftEffectを移動し、reverbの残響が残っていることを確認する
+        chain.update(
+            &[
+                EffectInfo::ToLeftEffect,
+                EffectInfo::SchroederReverb(1.0, 0.5),
+            ],
+            Arc::clone(&resource_manager),
+        );
+        let (left_wave, _) = chain.effect(vec![0.0; 512], vec![0.0; 512]);
+        assert!(left_wave.iter().any(|&x| x != 0.0));
+
+        chain.update(&[], Arc::clone(&resource_manager));
+        let (left_wave, _) = chain.effect(vec![0.0; 512], vec![0.0; 512]);
+        assert!(left_wave.iter().all(|&x| x == 0.0));
+    }
+}
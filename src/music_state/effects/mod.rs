@@ -1,4 +1,5 @@
 mod convolution;
+mod effect_chain;
 pub mod fft;
 pub mod ring_buffer;
 mod schroeder_reverb;
@@ -9,11 +10,15 @@ use std::sync::Arc;
 use log::error;
 use serde::{Deserialize, Serialize};
 
+use super::super::error::ToidError;
 use super::super::resource_management::resource_manager::ResourceManager;
+use super::render_config::RenderConfig;
 use convolution::ConvolutionEffect;
 use schroeder_reverb::SchroederReverbEffect;
 use to_left::ToLeftEffect;
 
+pub use effect_chain::EffectChain;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum EffectInfo {
     ToLeftEffect,
@@ -25,6 +30,7 @@ impl EffectInfo {
     pub fn get_effect(
         &self,
         resource_manager: Arc<ResourceManager>,
+        render_config: &RenderConfig,
     ) -> Box<dyn Effect + Sync + Send> {
         match self {
             EffectInfo::ToLeftEffect => Box::new(ToLeftEffect {}) as Box<dyn Effect + Sync + Send>,
@@ -32,38 +38,127 @@ impl EffectInfo {
                 let wave = resource_manager.get_sample_wave(sample_name.to_string(), sound.clone());
                 match wave {
                     Ok(wave) => {
+                        let wave = if wave.sample_rate != render_config.sample_rate {
+                            wave.change_sample_rate(render_config.sample_rate)
+                        } else {
+                            (*wave).clone()
+                        };
                         let sample_data = wave.get_samples(0, wave.sample_num);
                         match sample_data {
                             Ok((left_sample, _right_sample)) => {
                                 // TODO: fix
-                                let left_sample = left_sample.split_at(44100).0;
-                                let left_sample = Vec::from(left_sample);
-                                Box::new(ConvolutionEffect::new(&left_sample, *dry, *wet))
-                                    as Box<dyn Effect + Sync + Send>
+                                let left_sample = left_sample
+                                    .split_at(std::cmp::min(
+                                        left_sample.len(),
+                                        render_config.sample_rate as usize,
+                                    ))
+                                    .0;
+                                Box::new(ConvolutionEffect::new(
+                                    left_sample,
+                                    *dry,
+                                    *wet,
+                                    render_config.block_size,
+                                )) as Box<dyn Effect + Sync + Send>
                             }
                             Err(e) => {
                                 // TODO:
                                 error!("error {}", e);
-                                Box::new(ConvolutionEffect::new(&vec![0.0; 512], 1.0, 0.0))
-                                    as Box<dyn Effect + Sync + Send>
+                                Box::new(ConvolutionEffect::new(
+                                    &vec![0.0; render_config.block_size],
+                                    1.0,
+                                    0.0,
+                                    render_config.block_size,
+                                )) as Box<dyn Effect + Sync + Send>
                             }
                         }
                     }
                     Err(e) => {
                         // TODO:
                         error!("error {}", e);
-                        Box::new(ConvolutionEffect::new(&vec![0.0; 512], 1.0, 0.0))
-                            as Box<dyn Effect + Sync + Send>
+                        Box::new(ConvolutionEffect::new(
+                            &vec![0.0; render_config.block_size],
+                            1.0,
+                            0.0,
+                            render_config.block_size,
+                        )) as Box<dyn Effect + Sync + Send>
                     }
                 }
             }
             EffectInfo::SchroederReverb(dry, wet) => {
-                Box::new(SchroederReverbEffect::new(*dry, *wet)) as Box<dyn Effect + Sync + Send>
+                Box::new(SchroederReverbEffect::new(*dry, *wet, render_config))
+                    as Box<dyn Effect + Sync + Send>
             }
         }
     }
 }
 
+// trackとsectionで共通のeffect列の編集
+// indexが範囲外の場合はerror!を出して何もしない
+pub fn remove_effect(effects: &mut Vec<EffectInfo>, idx: usize) {
+    if idx < effects.len() {
+        effects.remove(idx);
+    } else {
+        error!("remove_effect Error {}", out_of_range(effects, idx));
+    }
+}
+
+pub fn move_effect(effects: &mut Vec<EffectInfo>, from: usize, to: usize) {
+    match (from < effects.len(), to < effects.len()) {
+        (true, true) => {
+            let effect = effects.remove(from);
+            effects.insert(to, effect);
+        }
+        (false, _) => error!("move_effect Error {}", out_of_range(effects, from)),
+        (_, false) => error!("move_effect Error {}", out_of_range(effects, to)),
+    }
+}
+
+pub fn replace_effect(effects: &mut [EffectInfo], idx: usize, effect: EffectInfo) {
+    if idx < effects.len() {
+        effects[idx] = effect;
+    } else {
+        error!("replace_effect Error {}", out_of_range(effects, idx));
+    }
+}
+
+fn out_of_range(effects: &[EffectInfo], idx: usize) -> ToidError {
+    ToidError::OutOfRange(format!("effect index {} (len {})", idx, effects.len()))
+}
+
 pub trait Effect {
     fn effect(&mut self, left_wave: &Vec<f32>, right_wave: &Vec<f32>) -> (Vec<f32>, Vec<f32>);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_effects() {
+        let mut effects = vec![
+            EffectInfo::ToLeftEffect,
+            EffectInfo::SchroederReverb(0.5, 0.5),
+        ];
+
+        move_effect(&mut effects, 0, 1);
+        assert_eq!(
+            effects,
+            vec![
+                EffectInfo::SchroederReverb(0.5, 0.5),
+                EffectInfo::ToLeftEffect
+            ]
+        );
+
+        replace_effect(&mut effects, 0, EffectInfo::SchroederReverb(0.2, 0.8));
+        assert_eq!(effects[0], EffectInfo::SchroederReverb(0.2, 0.8));
+
+        remove_effect(&mut effects, 1);
+        assert_eq!(effects, vec![EffectInfo::SchroederReverb(0.2, 0.8)]);
+
+        // 範囲外のindexは無視される
+        remove_effect(&mut effects, 3);
+        move_effect(&mut effects, 0, 3);
+        replace_effect(&mut effects, 3, EffectInfo::ToLeftEffect);
+        assert_eq!(effects, vec![EffectInfo::SchroederReverb(0.2, 0.8)]);
+    }
+}
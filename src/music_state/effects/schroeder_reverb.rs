@@ -3,11 +3,10 @@
 
 use rand::prelude::*;
 
+use super::super::render_config::RenderConfig;
 use super::ring_buffer::RingBuffer;
 use super::Effect;
 
-const FRAMES_PER_BUFFER: usize = 512;
-
 pub struct SchroederReverbEffect {
     multitap_delay: MultitapDelay,
     comb_filters: Vec<CombFilter>,
@@ -17,7 +16,8 @@ pub struct SchroederReverbEffect {
 }
 
 impl SchroederReverbEffect {
-    pub fn new(dry: f32, wet: f32) -> SchroederReverbEffect {
+    pub fn new(dry: f32, wet: f32, render_config: &RenderConfig) -> SchroederReverbEffect {
+        let sample_rate = render_config.sample_rate;
         let mut rng = rand::thread_rng();
 
         let mut multitap_delay: Vec<usize> = vec![];
@@ -26,7 +26,7 @@ impl SchroederReverbEffect {
             let fluctdelay: f32 = 0.002 * rng.gen::<f32>();
             let fluctamp: f32 = 0.1 * rng.gen::<f32>();
             let delay_sec = 0.020 + (0.008 + fluctdelay) * (i as f32);
-            let delay = (delay_sec * 44100.0 + 0.5) as usize;
+            let delay = (delay_sec * sample_rate + 0.5) as usize;
             let amp = 0.6 + fluctamp + (-0.3) * (i as f32) / 10.0;
             multitap_delay.push(delay);
             multitap_amp.push(amp);
@@ -34,15 +34,15 @@ impl SchroederReverbEffect {
         let multitap_delay = MultitapDelay::new(multitap_delay, multitap_amp);
 
         let comb_filters = vec![
-            CombFilter::new((0.03985 * 44100.0 + 0.5) as usize, 0.871402),
-            CombFilter::new((0.03610 * 44100.0 + 0.5) as usize, 0.882762),
-            CombFilter::new((0.03327 * 44100.0 + 0.5) as usize, 0.891443),
-            CombFilter::new((0.03015 * 44100.0 + 0.5) as usize, 0.901117),
+            CombFilter::new((0.03985 * sample_rate + 0.5) as usize, 0.871402),
+            CombFilter::new((0.03610 * sample_rate + 0.5) as usize, 0.882762),
+            CombFilter::new((0.03327 * sample_rate + 0.5) as usize, 0.891443),
+            CombFilter::new((0.03015 * sample_rate + 0.5) as usize, 0.901117),
         ];
 
         let allpass_filters = vec![
-            AllpassFilter::new((0.005 * 44100.0 + 0.5) as usize, 0.7),
-            AllpassFilter::new((0.0017 * 44100.0 + 0.5) as usize, 0.7),
+            AllpassFilter::new((0.005 * sample_rate + 0.5) as usize, 0.7),
+            AllpassFilter::new((0.0017 * sample_rate + 0.5) as usize, 0.7),
         ];
 
         SchroederReverbEffect {
@@ -59,7 +59,7 @@ impl Effect for SchroederReverbEffect {
     fn effect(&mut self, left_wave: &Vec<f32>, right_wave: &Vec<f32>) -> (Vec<f32>, Vec<f32>) {
         let mut new_left_wave = vec![];
         let mut new_right_wave = vec![];
-        for i in 0..FRAMES_PER_BUFFER {
+        for i in 0..left_wave.len() {
             let mut new_left = 0.0;
             let mut new_right = 0.0;
 
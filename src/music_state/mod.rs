@@ -1,5 +1,9 @@
 pub mod effects;
 pub mod pitch_track_player;
+pub mod quantize;
+pub mod render_config;
 pub mod sample_track_player;
 pub mod states;
+pub mod synth;
+pub mod timeline;
 pub mod wave_reader;
@@ -1,44 +1,246 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::f32::consts::PI;
 use std::iter::Iterator;
-use std::ops::Bound::{Excluded, Included};
+use std::ops::Bound::{Excluded, Included, Unbounded};
 use std::sync::Arc;
 
 use log::{error, warn};
 
-use super::super::data::music_info::{Beat, Instrument, PitchNote, Track};
-use super::super::music_state::effects::{Effect, EffectInfo};
+use super::super::data::music_info::{Beat, Instrument, Note, PitchNote, Track};
+use super::super::data::sf2::{ModulatorInput, SF2Voice};
+use super::super::music_state::effects::EffectChain;
 use super::super::resource_management::resource_manager::ResourceManager;
+use super::render_config::RenderConfig;
+use super::synth::oscillator;
+use super::synth::{FmVoice, SynthVoice};
+use super::timeline::Timeline;
 
-fn tri(x: f32) -> f32 {
-    let x = (x - 0.5 * PI) % (2.0 * PI);
-    (x - PI).abs() / PI * 2.0 - 1.0
+// cum_start_samplesからhertzをfrom_hertzからto_hertzへ動かす
+struct PitchGlide {
+    cum_start_samples: u64,
+    from_hertz: f32,
+    to_hertz: f32,
 }
 
-fn saw(x: f32) -> f32 {
-    let x = x % (2.0 * PI);
-    x / PI - 1.0
+impl PitchGlide {
+    // pitchが等間隔に動くように、周波数の比で補間する
+    fn get_hertz(&self, cum_samples: u64, glide_samples: u64) -> f32 {
+        if glide_samples == 0 || cum_samples >= self.cum_start_samples + glide_samples {
+            return self.to_hertz;
+        }
+        let progress =
+            cum_samples.saturating_sub(self.cum_start_samples) as f32 / glide_samples as f32;
+        self.from_hertz * (self.to_hertz / self.from_hertz).powf(progress)
+    }
+}
+
+struct PlayedNote {
+    // envelopeはここから数える。legatoで続いたnoteは最初のnoteのまま
+    cum_start_samples: u64,
+    cum_note_off_samples: u64,
+    note: PitchNote,
+    // oscillatorのphase(0.0 ~ 1.0)。sampleごとに足していく
+    phase: f64,
+    // 始まった順。legatoで続いたnoteの分だけ増える
+    glides: Vec<PitchGlide>,
+    // Synth, FMのときだけ、最初に鳴らすときに作る
+    synth_voice: Option<SynthVoice>,
+    fm_voice: Option<FmVoice>,
+    // SF2のときだけ、note onのときにmodulatorを反映して作る
+    sf2_voices: Vec<SF2Voice>,
+}
+
+impl PlayedNote {
+    // 最後にnoteが始まった位置。legatoで続いたnoteがあればその位置
+    fn get_last_onset_samples(&self) -> u64 {
+        self.glides
+            .last()
+            .map_or(self.cum_start_samples, |glide| glide.cum_start_samples)
+    }
+
+    // 楽器ごとの1sample分の値。gain, envelope, volume, panはplayでまとめてかける
+    fn next_sample(
+        &mut self,
+        instrument: &Instrument,
+        hertz: f32,
+        sample_rate: f32,
+        sec: f32,
+        note_off_sec: f32,
+    ) -> f32 {
+        match instrument {
+            Instrument::Synth(synth) => {
+                let cum_start_samples = self.cum_start_samples;
+                self.synth_voice
+                    .get_or_insert_with(|| SynthVoice::new(synth, cum_start_samples))
+                    .next_sample(synth, hertz, sample_rate, sec, note_off_sec)
+            }
+            Instrument::FM(fm) => self
+                .fm_voice
+                .get_or_insert_with(|| FmVoice::new(fm))
+                .next_sample(fm, hertz, sample_rate, sec, note_off_sec),
+            _ => {
+                let herts_par_sample = hertz / sample_rate;
+                let phase = self.phase as f32;
+                let x = phase * 2.0 * PI;
+                let value = match instrument {
+                    Instrument::Tri => oscillator::tri(phase, herts_par_sample),
+                    Instrument::Saw => oscillator::saw(phase, herts_par_sample),
+                    Instrument::Square => oscillator::pulse(phase, herts_par_sample, 0.5),
+                    Instrument::LofiTri => oscillator::lofi_tri(x),
+                    Instrument::LofiSaw => oscillator::lofi_saw(x),
+                    _ => x.sin(),
+                };
+                self.phase = (self.phase + herts_par_sample as f64).fract();
+                value
+            }
+        }
+    }
+}
+
+// cum_samplesの時点の周波数。glidesは始まった順
+fn get_glided_hertz(glides: &[PitchGlide], cum_samples: u64, glide_samples: u64) -> f32 {
+    let glide = glides
+        .iter()
+        .rev()
+        .find(|glide| glide.cum_start_samples <= cum_samples)
+        .unwrap_or(&glides[0]);
+    glide.get_hertz(cum_samples, glide_samples)
+}
+
+// cum_current_samplesから始まるwaveのうち、noteが鳴る範囲のindex
+fn get_play_range(
+    cum_start_samples: u64,
+    cum_end_samples: u64,
+    cum_current_samples: u64,
+    wave_length: u64,
+) -> (usize, usize) {
+    let start_idx = cum_start_samples.saturating_sub(cum_current_samples) as usize;
+    let end_idx = if cum_end_samples >= cum_current_samples + wave_length {
+        wave_length as usize
+    } else {
+        (cum_end_samples - cum_current_samples) as usize
+    };
+    (start_idx, end_idx)
+}
+
+// trackのvolumeとpanをかけて、waveのidxに足す
+fn mix_sample(
+    left_wave: &mut [f32],
+    right_wave: &mut [f32],
+    idx: usize,
+    left_value: f32,
+    right_value: f32,
+    track: &Track<PitchNote>,
+) {
+    left_wave[idx] += (1.0 - track.pan) * left_value * track.vol;
+    right_wave[idx] += (1.0 + track.pan) * right_value * track.vol;
 }
 
 pub struct PitchTrackPlayer {
     wave_length: u64,
-    played_notes: BTreeMap<u64, Vec<(u64, PitchNote)>>,
-    effect_infos: Vec<EffectInfo>,
-    effects: Vec<Box<dyn Effect + Sync + Send>>,
+    sample_rate: f32,
+    // 鳴り終わり(releaseを含む) -> 鳴っているnote
+    played_notes: BTreeMap<u64, Vec<PlayedNote>>,
+    // 直前に始まったnoteの周波数。glideの始まり
+    last_hertz: Option<f32>,
+    effect_chain: EffectChain,
 }
 
 impl PitchTrackPlayer {
-    pub fn new() -> Self {
+    pub fn new(render_config: RenderConfig) -> Self {
         Self {
-            wave_length: 512,
+            wave_length: render_config.block_size as u64,
+            sample_rate: render_config.sample_rate,
             played_notes: BTreeMap::new(),
-            effect_infos: vec![],
-            effects: vec![],
+            last_hertz: None,
+            effect_chain: EffectChain::new(render_config),
         }
     }
 
     pub fn clean(&mut self) {
         self.played_notes = BTreeMap::new();
+        self.last_hertz = None;
+    }
+
+    // cum_start_beatsからcum_end_beatsの間に始まるnotesをself.played_notesに加える
+    pub fn schedule_notes(
+        &mut self,
+        track: &Track<PitchNote>,
+        timeline: &Timeline,
+        cum_start_beats: &Beat,
+        cum_end_beats: &Beat,
+        resource_manager: Arc<ResourceManager>,
+    ) {
+        if track.phrase.length <= Beat::from(0) || *cum_start_beats >= *cum_end_beats {
+            return;
+        }
+        let rep_current_beats = *cum_start_beats % track.phrase.length;
+        let rep_next_beats = *cum_end_beats % track.phrase.length;
+        let cum_phrase_start_beats = *cum_start_beats - rep_current_beats;
+
+        if rep_current_beats < rep_next_beats {
+            for (&start, new_notes) in track
+                .phrase
+                .notes
+                .range((Included(rep_current_beats), Excluded(rep_next_beats)))
+            {
+                self.register_notes(
+                    new_notes,
+                    track,
+                    timeline,
+                    cum_phrase_start_beats + start,
+                    &resource_manager,
+                );
+            }
+        } else {
+            for (&start, new_notes) in track
+                .phrase
+                .notes
+                .range((Included(rep_current_beats), Excluded(track.phrase.length)))
+            {
+                self.register_notes(
+                    new_notes,
+                    track,
+                    timeline,
+                    cum_phrase_start_beats + start,
+                    &resource_manager,
+                );
+            }
+            for (&start, new_notes) in track
+                .phrase
+                .notes
+                .range((Included(Beat::from(0)), Excluded(rep_next_beats)))
+            {
+                self.register_notes(
+                    new_notes,
+                    track,
+                    timeline,
+                    cum_phrase_start_beats + track.phrase.length + start,
+                    &resource_manager,
+                );
+            }
+        }
+    }
+
+    // cum_release_samplesより後まで押されているnoteを、cum_release_samplesで離す。
+    // releaseの長さは変えない
+    pub fn release_notes(&mut self, cum_release_samples: u64) {
+        let later_notes = self.played_notes.split_off(&(cum_release_samples + 1));
+        for (cum_end_samples, notes) in later_notes.into_iter() {
+            for mut played_note in notes.into_iter() {
+                let cum_end_samples = if played_note.cum_note_off_samples > cum_release_samples {
+                    let release_samples = cum_end_samples - played_note.cum_note_off_samples;
+                    played_note.cum_note_off_samples = cum_release_samples;
+                    cum_release_samples + release_samples
+                } else {
+                    cum_end_samples
+                };
+                self.played_notes
+                    .entry(cum_end_samples)
+                    .or_default()
+                    .push(played_note);
+            }
+        }
     }
 
     pub fn play(
@@ -46,8 +248,6 @@ impl PitchTrackPlayer {
         track: &Track<PitchNote>,
         resource_manager: Arc<ResourceManager>,
         cum_current_samples: &u64,
-        cum_current_beats: &Beat,
-        current_bpm: &f32,
     ) -> (Vec<f32>, Vec<f32>) {
         let mut left_wave: Vec<f32> = Vec::new();
         let mut right_wave: Vec<f32> = Vec::new();
@@ -55,206 +255,120 @@ impl PitchTrackPlayer {
         right_wave.resize(self.wave_length as usize, 0.0);
 
         let cum_next_samples = cum_current_samples + self.wave_length;
-        let cum_next_beats =
-            *cum_current_beats + Beat::from(self.wave_length as f32 * current_bpm / 44100.0 / 60.0);
-
-        // 付け加えるnotesをリストアップする。
-        // self.played_notesに加える。
-        if track.phrase.length > Beat::from(0) {
-            let rep_current_beats = *cum_current_beats % track.phrase.length;
-            let rep_next_beats = cum_next_beats % track.phrase.length;
-
-            if rep_current_beats < rep_next_beats {
-                for (&start, new_notes) in track
-                    .phrase
-                    .notes
-                    .range((Included(rep_current_beats), Excluded(rep_next_beats)))
-                {
-                    let cum_start_samples = ((start - rep_current_beats).to_f32() * 44100.0 * 60.0
-                        / current_bpm) as u64
-                        + cum_current_samples;
-                    self.register_notes(new_notes, &current_bpm, &cum_start_samples);
-                }
-            } else {
-                for (&start, new_notes) in track
-                    .phrase
-                    .notes
-                    .range((Included(rep_current_beats), Excluded(track.phrase.length)))
-                {
-                    let cum_start_samples = ((start - rep_current_beats).to_f32() * 44100.0 * 60.0
-                        / current_bpm) as u64
-                        + cum_current_samples;
-                    self.register_notes(new_notes, &current_bpm, &cum_start_samples);
-                }
-                for (&start, new_notes) in track
-                    .phrase
-                    .notes
-                    .range((Included(Beat::from(0)), Excluded(rep_next_beats)))
-                {
-                    let cum_start_samples = ((track.phrase.length + start - rep_current_beats)
-                        .to_f32()
-                        * 44100.0
-                        * 60.0
-                        / current_bpm) as u64
-                        + cum_current_samples;
-
-                    self.register_notes(new_notes, &current_bpm, &cum_start_samples);
-                }
-            }
-        }
 
-        // self.played_notesのを鳴らす
+        let glide_samples = track.glide.get_samples(self.sample_rate);
+
+        // self.played_notesのを鳴らす。SF2はglideしない
         match &track.instrument {
-            Instrument::Sin => {
-                for (&cum_end_samples, notes) in self.played_notes.iter() {
-                    for (cum_start_samples, note) in notes.iter() {
-                        let herts_par_sample = note.pitch.get_hertz() / 44100.0;
-                        let start_idx = if *cum_start_samples <= *cum_current_samples {
-                            0
-                        } else {
-                            (cum_start_samples - cum_current_samples) as usize
-                        };
-                        let end_idx = if cum_end_samples >= cum_next_samples {
-                            self.wave_length as usize
-                        } else {
-                            (cum_end_samples - cum_current_samples) as usize
-                        };
+            Instrument::Sin
+            | Instrument::Tri
+            | Instrument::Saw
+            | Instrument::Square
+            | Instrument::LofiTri
+            | Instrument::LofiSaw
+            | Instrument::Synth(_)
+            | Instrument::FM(_) => {
+                // FMはoperatorごとのenvelopeを使う
+                let use_track_envelope = !matches!(track.instrument, Instrument::FM(_));
+                for (&cum_end_samples, notes) in self.played_notes.iter_mut() {
+                    for played_note in notes.iter_mut() {
+                        let cum_start_samples = played_note.cum_start_samples;
+                        let note_off_sec = (played_note.cum_note_off_samples - cum_start_samples)
+                            as f32
+                            / self.sample_rate;
+                        let gain = played_note.note.get_gain();
+                        let (start_idx, end_idx) = get_play_range(
+                            cum_start_samples,
+                            cum_end_samples,
+                            *cum_current_samples,
+                            self.wave_length,
+                        );
 
                         for i in start_idx..end_idx {
-                            let x = (cum_current_samples + i as u64 - cum_start_samples) as f32
-                                * herts_par_sample;
-                            let x = x * 2.0 * PI;
-                            let addition = x.sin() * 0.3 * track.vol;
-                            left_wave[i] = left_wave[i] + (1.0 - track.pan) * addition;
-                            right_wave[i] = right_wave[i] + (1.0 + track.pan) * addition;
+                            let cum_samples = cum_current_samples + i as u64;
+                            let sec = (cum_samples - cum_start_samples) as f32 / self.sample_rate;
+                            let hertz =
+                                get_glided_hertz(&played_note.glides, cum_samples, glide_samples);
+                            let value = played_note.next_sample(
+                                &track.instrument,
+                                hertz,
+                                self.sample_rate,
+                                sec,
+                                note_off_sec,
+                            );
+                            let envelope = if use_track_envelope {
+                                track.envelope.get_value(sec, note_off_sec)
+                            } else {
+                                1.0
+                            };
+                            let addition = value * 0.3 * envelope * gain;
+                            mix_sample(
+                                &mut left_wave,
+                                &mut right_wave,
+                                i,
+                                addition,
+                                addition,
+                                track,
+                            );
                         }
                     }
                 }
             }
-            Instrument::Tri => {
-                for (&cum_end_samples, notes) in self.played_notes.iter() {
-                    for (cum_start_samples, note) in notes.iter() {
-                        let herts_par_sample = note.pitch.get_hertz() / 44100.0;
-                        let start_idx = if *cum_start_samples <= *cum_current_samples {
-                            0
-                        } else {
-                            (cum_start_samples - cum_current_samples) as usize
-                        };
-                        let end_idx = if cum_end_samples >= cum_next_samples {
-                            self.wave_length as usize
-                        } else {
-                            (cum_end_samples - cum_current_samples) as usize
-                        };
+            Instrument::SF2(_, _) => {
+                for (&cum_end_samples, notes) in self.played_notes.iter_mut() {
+                    for played_note in notes.iter_mut() {
+                        let cum_start_samples = played_note.cum_start_samples;
+                        let note_off =
+                            (played_note.cum_note_off_samples - cum_start_samples) as usize;
+                        let (start_idx, end_idx) = get_play_range(
+                            cum_start_samples,
+                            cum_end_samples,
+                            *cum_current_samples,
+                            self.wave_length,
+                        );
 
-                        for i in start_idx..end_idx {
-                            let x = (cum_current_samples + i as u64 - cum_start_samples) as f32
-                                * herts_par_sample;
-                            let x = x * 2.0 * (PI as f32);
-                            let addition = tri(x) * 0.3 * track.vol;
-                            left_wave[i] = left_wave[i] + (1.0 - track.pan) * addition;
-                            right_wave[i] = right_wave[i] + (1.0 + track.pan) * addition;
-                        }
-                    }
-                }
-            }
-            Instrument::Saw => {
-                for (&cum_end_samples, notes) in self.played_notes.iter() {
-                    for (cum_start_samples, note) in notes.iter() {
-                        let herts_par_sample = note.pitch.get_hertz() / 44100.0;
-                        let start_idx = if *cum_start_samples <= *cum_current_samples {
-                            0
-                        } else {
-                            (cum_start_samples - cum_current_samples) as usize
-                        };
-                        let end_idx = if cum_end_samples >= cum_next_samples {
-                            self.wave_length as usize
-                        } else {
-                            (cum_end_samples - cum_current_samples) as usize
-                        };
+                        let start_idx_for_sample =
+                            (cum_current_samples + start_idx as u64 - cum_start_samples) as usize;
+                        let end_idx_for_sample =
+                            (cum_current_samples + end_idx as u64 - cum_start_samples) as usize;
 
-                        for i in start_idx..end_idx {
-                            let x = (cum_current_samples + i as u64 - cum_start_samples) as f32
-                                * herts_par_sample;
-                            let x = x * 2.0 * (PI as f32);
-                            let addition = saw(x) * 0.3 * track.vol;
-                            left_wave[i] = left_wave[i] + (1.0 - track.pan) * addition;
-                            right_wave[i] = right_wave[i] + (1.0 + track.pan) * addition;
-                        }
-                    }
-                }
-            }
-            Instrument::SF2(sf2_name, preset_idx) => {
-                let sf2 = resource_manager.get_sf2(sf2_name.to_string());
-                match sf2 {
-                    Ok(sf2) => {
-                        for (&cum_end_samples, notes) in self.played_notes.iter() {
-                            for (cum_start_samples, note) in notes.iter() {
-                                let start_idx = if *cum_start_samples <= *cum_current_samples {
-                                    0
-                                } else {
-                                    (cum_start_samples - cum_current_samples) as usize
-                                };
-                                let end_idx = if cum_end_samples >= cum_next_samples {
-                                    self.wave_length as usize
-                                } else {
-                                    (cum_end_samples - cum_current_samples) as usize
-                                };
-
-                                let start_idx_for_sample = (cum_current_samples + start_idx as u64
-                                    - cum_start_samples)
-                                    as usize;
-                                let end_idx_for_sample = (cum_current_samples + end_idx as u64
-                                    - cum_start_samples)
-                                    as usize;
-
-                                let sample_data = sf2.get_samples(
-                                    *preset_idx,
-                                    note.pitch.get_u8_pitch(),
-                                    start_idx_for_sample,
-                                    end_idx_for_sample,
-                                );
-                                match sample_data {
-                                    Ok(sample_data) => {
-                                        for (i, j) in (start_idx..end_idx).enumerate() {
-                                            let addition = sample_data[i] * 0.5 * track.vol;
-                                            left_wave[j] =
-                                                left_wave[j] + (1.0 - track.pan) * addition;
-                                            right_wave[j] =
-                                                right_wave[j] + (1.0 + track.pan) * addition;
-                                        }
-                                    }
-                                    Err(e) => {
-                                        // TODO:
-                                        error!("error {}", e);
+                        // velocityはmodulatorでInitialAttenuationに入っている
+                        for sf2_voice in played_note.sf2_voices.iter_mut() {
+                            let sample_data = sf2_voice.get_samples(
+                                start_idx_for_sample,
+                                end_idx_for_sample,
+                                note_off,
+                                self.sample_rate,
+                            );
+                            match sample_data {
+                                Ok((left_sample_data, right_sample_data)) => {
+                                    for (i, j) in (start_idx..end_idx).enumerate() {
+                                        mix_sample(
+                                            &mut left_wave,
+                                            &mut right_wave,
+                                            j,
+                                            left_sample_data[i] * 0.5,
+                                            right_sample_data[i] * 0.5,
+                                            track,
+                                        );
                                     }
                                 }
+                                Err(e) => {
+                                    // TODO:
+                                    error!("error {}", e);
+                                }
                             }
                         }
                     }
-                    Err(e) => {
-                        error!("sf2 error {}", e);
-                    }
                 }
             }
             _ => warn!("instrument is not for pitch track"),
         };
 
-        // Effect更新
-        if self.effect_infos != track.effects {
-            self.effect_infos = track.effects.clone();
-            let mut effects = vec![];
-            for efi in self.effect_infos.iter() {
-                effects.push(efi.get_effect(Arc::clone(&resource_manager)));
-            }
-            self.effects = effects;
-        }
-
         // Effect
-        for effect in self.effects.iter_mut() {
-            let (l, r) = effect.effect(&left_wave, &right_wave);
-            left_wave = l;
-            right_wave = r;
-        }
+        self.effect_chain
+            .update(&track.effects, Arc::clone(&resource_manager));
+        let (left_wave, right_wave) = self.effect_chain.effect(left_wave, right_wave);
 
         // 使ったself.played_notesのノートを消す
         for cum_note_samples in *cum_current_samples..cum_next_samples {
@@ -264,29 +378,280 @@ impl PitchTrackPlayer {
         (left_wave, right_wave)
     }
 
+    // cum_beatsの時点ですでに鳴り始めていて、まだ終わっていないnoteを途中から鳴らす。
+    // cum_beatsちょうどに始まるnoteはschedule_notesで登録される
+    pub fn chase_notes(
+        &mut self,
+        track: &Track<PitchNote>,
+        timeline: &Timeline,
+        cum_beats: &Beat,
+        resource_manager: Arc<ResourceManager>,
+    ) {
+        if track.phrase.length <= Beat::from(0) {
+            return;
+        }
+        let rep_beats = *cum_beats % track.phrase.length;
+        let cum_phrase_start_beats = *cum_beats - rep_beats;
+
+        // 1つ前の繰り返しで始まったnoteも、長ければまだ鳴っている
+        for &cum_phrase_start_beats in [
+            cum_phrase_start_beats - track.phrase.length,
+            cum_phrase_start_beats,
+        ]
+        .iter()
+        {
+            if cum_phrase_start_beats < Beat::from(0) {
+                continue;
+            }
+            for (&start, notes) in track.phrase.notes.iter() {
+                let cum_start_beats = cum_phrase_start_beats + start;
+                if cum_start_beats >= *cum_beats {
+                    break;
+                }
+                let sounding_notes: BTreeSet<PitchNote> = notes
+                    .iter()
+                    .filter(|note| cum_start_beats + note.duration > *cum_beats)
+                    .cloned()
+                    .collect();
+                self.register_notes(
+                    &sounding_notes,
+                    track,
+                    timeline,
+                    cum_start_beats,
+                    &resource_manager,
+                );
+            }
+        }
+    }
+
+    // note offの後もenvelopeのreleaseの間鳴らす。FMはoperatorの、SF2はsampleごとのenvelopeを使う
+    fn get_release_samples(&self, track: &Track<PitchNote>, sf2_voices: &[SF2Voice]) -> u64 {
+        match &track.instrument {
+            Instrument::Sin
+            | Instrument::Tri
+            | Instrument::Saw
+            | Instrument::Square
+            | Instrument::LofiTri
+            | Instrument::LofiSaw
+            | Instrument::Synth(_) => track.envelope.get_release_samples(self.sample_rate),
+            Instrument::FM(fm) => (fm.get_release().max(0.0) * self.sample_rate).ceil() as u64,
+            Instrument::SF2(_, _) => {
+                let release = sf2_voices
+                    .iter()
+                    .map(|sf2_voice| sf2_voice.get_release())
+                    .fold(0.0, f32::max);
+                (release * self.sample_rate).ceil() as u64
+            }
+            _ => 0,
+        }
+    }
+
+    fn get_sf2_voices(
+        track: &Track<PitchNote>,
+        note: &PitchNote,
+        resource_manager: &ResourceManager,
+    ) -> Vec<SF2Voice> {
+        if let Instrument::SF2(sf2_name, preset_idx) = &track.instrument {
+            let input = ModulatorInput {
+                mod_wheel: track.mod_wheel,
+                ..ModulatorInput::new(note.pitch.get_u8_pitch(), note.velocity)
+            };
+            let sf2_voices = resource_manager
+                .get_sf2(sf2_name.to_string())
+                .and_then(|sf2| sf2.get_voices(*preset_idx, &input));
+            match sf2_voices {
+                Ok(sf2_voices) => return sf2_voices,
+                Err(e) => error!("sf2 error {}", e),
+            }
+        }
+        vec![]
+    }
+
+    // 周波数を少しずつ変えられるのは、自前でoscillatorを回す楽器だけ
+    fn can_glide(track: &Track<PitchNote>) -> bool {
+        !matches!(
+            track.instrument,
+            Instrument::SF2(_, _) | Instrument::Sample(_)
+        )
+    }
+
+    // cum_samplesに押されている中で、一番最後に始まったnoteを取り出す。
+    // 和音の他の音を取らないように、cum_samplesより前に始まったnoteだけにする
+    fn take_held_note(&mut self, cum_samples: u64) -> Option<PlayedNote> {
+        let mut held: Option<(u64, usize, u64)> = None;
+        for (&cum_end_samples, notes) in self.played_notes.range((Excluded(cum_samples), Unbounded))
+        {
+            for (idx, played_note) in notes.iter().enumerate() {
+                let cum_onset_samples = played_note.get_last_onset_samples();
+                if cum_onset_samples < cum_samples
+                    && played_note.cum_note_off_samples > cum_samples
+                    && match held {
+                        Some((_, _, cum_held_onset_samples)) => {
+                            cum_onset_samples >= cum_held_onset_samples
+                        }
+                        None => true,
+                    }
+                {
+                    held = Some((cum_end_samples, idx, cum_onset_samples));
+                }
+            }
+        }
+        let (cum_end_samples, idx, _) = held?;
+        let notes = self.played_notes.get_mut(&cum_end_samples)?;
+        let played_note = notes.remove(idx);
+        if notes.is_empty() {
+            self.played_notes.remove(&cum_end_samples);
+        }
+        Some(played_note)
+    }
+
     fn register_notes(
         &mut self,
         notes: &BTreeSet<PitchNote>,
-        current_bpm: &f32,
-        cum_start_samples: &u64,
+        track: &Track<PitchNote>,
+        timeline: &Timeline,
+        cum_start_beats: Beat,
+        resource_manager: &ResourceManager,
     ) {
+        let cum_start_samples = timeline.beat_to_samples(cum_start_beats);
+        let can_glide = Self::can_glide(track);
+        let glide_samples = track.glide.get_samples(self.sample_rate);
+        // 和音の音はどれも、1つ前に始まったnoteからglideする
+        let last_hertz = self.last_hertz;
         for &note in notes.iter() {
-            let cum_end_samples =
-                cum_start_samples + (note.duration.to_f32() * 44100.0 * 60.0 / current_bpm) as u64;
+            let cum_note_off_samples = timeline.beat_to_samples(cum_start_beats + note.duration);
+            let sf2_voices = Self::get_sf2_voices(track, &note, resource_manager);
+            let release_samples = self.get_release_samples(track, &sf2_voices);
+            let hertz = note.pitch.get_hertz();
 
-            if self.played_notes.contains_key(&cum_end_samples) {
-                match self.played_notes.get_mut(&cum_end_samples) {
-                    Some(notes_in_cum_end_samples) => {
-                        notes_in_cum_end_samples.push((*cum_start_samples, note));
-                    }
-                    None => {
-                        error!("get_mut failed");
-                    }
-                };
-            } else {
-                self.played_notes
-                    .insert(cum_end_samples, vec![(*cum_start_samples, note)]);
+            // legatoなら、鳴っているnoteのpitchを変えて、note offを延ばす
+            if can_glide && track.glide.legato {
+                if let Some(mut played_note) = self.take_held_note(cum_start_samples) {
+                    let from_hertz =
+                        get_glided_hertz(&played_note.glides, cum_start_samples, glide_samples);
+                    played_note.glides.push(PitchGlide {
+                        cum_start_samples,
+                        from_hertz,
+                        to_hertz: hertz,
+                    });
+                    played_note.cum_note_off_samples = cum_note_off_samples;
+                    self.played_notes
+                        .entry(cum_note_off_samples + release_samples)
+                        .or_default()
+                        .push(played_note);
+                    continue;
+                }
             }
+
+            let from_hertz = match last_hertz {
+                Some(last_hertz) if can_glide && !track.glide.legato => last_hertz,
+                _ => hertz,
+            };
+            self.played_notes
+                .entry(cum_note_off_samples + release_samples)
+                .or_default()
+                .push(PlayedNote {
+                    cum_start_samples,
+                    cum_note_off_samples,
+                    note,
+                    phase: 0.0,
+                    glides: vec![PitchGlide {
+                        cum_start_samples,
+                        from_hertz,
+                        to_hertz: hertz,
+                    }],
+                    synth_voice: None,
+                    fm_voice: None,
+                    sf2_voices,
+                });
         }
+        if let Some(note) = notes.iter().next_back() {
+            self.last_hertz = Some(note.pitch.get_hertz());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::data::music_info::{Glide, Pitch, DEFAULT_VELOCITY};
+    use super::super::super::state_management::state::State;
+    use super::super::states::SchedulingState;
+    use super::*;
+
+    fn make_chord(pitches: &[i32], start: f32, duration: f32) -> BTreeSet<PitchNote> {
+        pitches
+            .iter()
+            .map(|&pitch| PitchNote {
+                pitch: Pitch::from(pitch),
+                duration: Beat::from(duration),
+                start: Beat::from(start),
+                velocity: DEFAULT_VELOCITY,
+            })
+            .collect()
+    }
+
+    fn get_played_notes(player: &PitchTrackPlayer) -> Vec<&PlayedNote> {
+        player.played_notes.values().flatten().collect()
+    }
+
+    fn register(
+        player: &mut PitchTrackPlayer,
+        track: &Track<PitchNote>,
+        chord: &[i32],
+        start: f32,
+    ) {
+        let timeline = Timeline::new(Arc::new(SchedulingState::new()), 0, Beat::from(0), 44100.0);
+        player.register_notes(
+            &make_chord(chord, start, 2.0),
+            track,
+            &timeline,
+            Beat::from(start),
+            &ResourceManager::new(),
+        );
+    }
+
+    #[test]
+    fn test_legato_chord() {
+        let track = Track::new()
+            .set_inst(Instrument::Sin)
+            .set_glide(Glide::new(0.05, true));
+        let mut player = PitchTrackPlayer::new(RenderConfig::default());
+
+        // 最初の和音は、音の数だけ鳴る
+        register(&mut player, &track, &[69, 73, 76], 0.0);
+        let played_notes = get_played_notes(&player);
+        assert_eq!(played_notes.len(), 3);
+        assert!(played_notes.iter().all(|note| note.glides.len() == 1));
+
+        // 次の和音は、鳴っているnoteを1つずつ引き継ぐ
+        register(&mut player, &track, &[71, 74], 1.0);
+        let played_notes = get_played_notes(&player);
+        assert_eq!(played_notes.len(), 3);
+        assert_eq!(
+            played_notes
+                .iter()
+                .filter(|note| note.glides.len() == 2)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_glide_chord() {
+        let track = Track::new()
+            .set_inst(Instrument::Sin)
+            .set_glide(Glide::new(0.05, false));
+        let mut player = PitchTrackPlayer::new(RenderConfig::default());
+
+        // 和音の音はどれも、前のnoteからglideする
+        register(&mut player, &track, &[57], 0.0);
+        register(&mut player, &track, &[69, 73, 76], 1.0);
+        let played_notes = get_played_notes(&player);
+        assert_eq!(played_notes.len(), 4);
+        let from_hertz = Pitch::from(57).get_hertz();
+        assert!(played_notes
+            .iter()
+            .filter(|note| note.cum_start_samples > 0)
+            .all(|note| note.glides[0].from_hertz == from_hertz));
     }
 }
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::data::music_info::{BarPosition, Beat};
+use super::states::SchedulingState;
+
+// eventをいつ反映するか。Phraseは指定した長さの倍数の位置で反映する
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Quantize {
+    Beat,
+    Bar,
+    Phrase(Beat),
+}
+
+impl Quantize {
+    // beat以降で最初の区切り。beatがちょうど区切りならbeatを返す
+    pub fn next_boundary(&self, scheduling: &SchedulingState, beat: Beat) -> Beat {
+        match self {
+            Quantize::Beat => {
+                let bar_position = scheduling.beat_to_bar_position(beat);
+                if bar_position.tick == 0 {
+                    beat
+                } else {
+                    scheduling.bar_position_to_beat(BarPosition::new(
+                        bar_position.bar,
+                        bar_position.beat + 1,
+                        0,
+                    ))
+                }
+            }
+            Quantize::Bar => {
+                let bar_position = scheduling.beat_to_bar_position(beat);
+                if bar_position.beat == 0 && bar_position.tick == 0 {
+                    beat
+                } else {
+                    scheduling.bar_position_to_beat(BarPosition::from_bar(bar_position.bar + 1))
+                }
+            }
+            Quantize::Phrase(length) => {
+                let length = length.get_num();
+                if length <= 0 {
+                    return beat;
+                }
+                let num = beat.get_num();
+                Beat::from_num((num + length - 1).div_euclid(length) * length)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::data::music_info::TimeSignature;
+    use super::super::super::state_management::state::State;
+    use super::super::states::SchedulingStateEvent;
+    use super::*;
+
+    #[test]
+    fn test_next_boundary() {
+        let scheduling = SchedulingState::new().reduce(SchedulingStateEvent::ChangeTimeSignature(
+            Beat::from(8),
+            TimeSignature::new(3, 4).unwrap(),
+        ));
+
+        assert_eq!(
+            Quantize::Beat.next_boundary(&scheduling, Beat::from(1.5)),
+            Beat::from(2)
+        );
+        assert_eq!(
+            Quantize::Beat.next_boundary(&scheduling, Beat::from(2)),
+            Beat::from(2)
+        );
+        assert_eq!(
+            Quantize::Bar.next_boundary(&scheduling, Beat::from(1)),
+            Beat::from(4)
+        );
+        // 8拍目から3/4拍子
+        assert_eq!(
+            Quantize::Bar.next_boundary(&scheduling, Beat::from(9)),
+            Beat::from(11)
+        );
+        assert_eq!(
+            Quantize::Phrase(Beat::from(16)).next_boundary(&scheduling, Beat::from(17)),
+            Beat::from(32)
+        );
+        assert_eq!(
+            Quantize::Phrase(Beat::from(16)).next_boundary(&scheduling, Beat::from(16)),
+            Beat::from(16)
+        );
+    }
+}
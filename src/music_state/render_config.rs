@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::error::ToidError;
+
+// 出力側が決める、1回のreadで返すsample数とsample rate。
+// ConvolutionEffectがFFTを使うので、block_sizeは2の累乗にする
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RenderConfig {
+    pub sample_rate: f32,
+    pub block_size: usize,
+}
+
+impl RenderConfig {
+    pub fn new(sample_rate: f32, block_size: usize) -> Result<Self, ToidError> {
+        let render_config = Self {
+            sample_rate,
+            block_size,
+        };
+        render_config.validate()?;
+        Ok(render_config)
+    }
+
+    // deserializeしたものはnewを通らないので、使う側でも確認する
+    pub fn validate(&self) -> Result<(), ToidError> {
+        if !(self.sample_rate.is_finite() && self.sample_rate > 0.0) {
+            return Err(ToidError::OutOfRange(format!(
+                "sample rate must be positive : {}",
+                self.sample_rate
+            )));
+        }
+        if !self.block_size.is_power_of_two() {
+            return Err(ToidError::OutOfRange(format!(
+                "block size must be a power of two : {}",
+                self.block_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44_100.0,
+            block_size: 512,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert!(RenderConfig::new(48_000.0, 256).is_ok());
+        assert!(RenderConfig::new(48_000.0, 480).is_err());
+        assert!(RenderConfig::new(48_000.0, 0).is_err());
+        assert!(RenderConfig::new(0.0, 256).is_err());
+    }
+}
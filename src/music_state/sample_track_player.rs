@@ -5,24 +5,26 @@ use std::sync::Arc;
 
 use log::error;
 
-use super::super::data::music_info::{Beat, Instrument, SampleNote, Track};
-use super::super::music_state::effects::{Effect, EffectInfo};
+use super::super::data::music_info::{Beat, Instrument, Note, SampleNote, Track};
+use super::super::music_state::effects::EffectChain;
 use super::super::resource_management::resource_manager::ResourceManager;
+use super::render_config::RenderConfig;
+use super::timeline::Timeline;
 
 pub struct SampleTrackPlayer {
     wave_length: u64,
+    sample_rate: f32,
     played_notes: BTreeMap<u64, Vec<(u64, SampleNote)>>,
-    effect_infos: Vec<EffectInfo>,
-    effects: Vec<Box<dyn Effect + Sync + Send>>,
+    effect_chain: EffectChain,
 }
 
 impl SampleTrackPlayer {
-    pub fn new() -> Self {
+    pub fn new(render_config: RenderConfig) -> Self {
         Self {
-            wave_length: 512,
+            wave_length: render_config.block_size as u64,
+            sample_rate: render_config.sample_rate,
             played_notes: BTreeMap::new(),
-            effect_infos: vec![],
-            effects: vec![],
+            effect_chain: EffectChain::new(render_config),
         }
     }
 
@@ -30,13 +32,67 @@ impl SampleTrackPlayer {
         self.played_notes = BTreeMap::new();
     }
 
+    // cum_start_beatsからcum_end_beatsの間に始まるnotesをself.played_notesに加える
+    pub fn schedule_notes(
+        &mut self,
+        track: &Track<SampleNote>,
+        timeline: &Timeline,
+        cum_start_beats: &Beat,
+        cum_end_beats: &Beat,
+    ) {
+        if track.phrase.length <= Beat::from(0) || *cum_start_beats >= *cum_end_beats {
+            return;
+        }
+        let rep_current_beats = *cum_start_beats % track.phrase.length;
+        let rep_next_beats = *cum_end_beats % track.phrase.length;
+        let cum_phrase_start_beats = *cum_start_beats - rep_current_beats;
+
+        if rep_current_beats < rep_next_beats {
+            for (&start, new_notes) in track
+                .phrase
+                .notes
+                .range((Included(rep_current_beats), Excluded(rep_next_beats)))
+            {
+                self.register_notes(new_notes, timeline, cum_phrase_start_beats + start);
+            }
+        } else {
+            for (&start, new_notes) in track
+                .phrase
+                .notes
+                .range((Included(rep_current_beats), Excluded(track.phrase.length)))
+            {
+                self.register_notes(new_notes, timeline, cum_phrase_start_beats + start);
+            }
+            for (&start, new_notes) in track
+                .phrase
+                .notes
+                .range((Included(Beat::from(0)), Excluded(rep_next_beats)))
+            {
+                self.register_notes(
+                    new_notes,
+                    timeline,
+                    cum_phrase_start_beats + track.phrase.length + start,
+                );
+            }
+        }
+    }
+
+    // cum_release_samplesより後まで鳴るnoteを、cum_release_samplesで止める
+    pub fn release_notes(&mut self, cum_release_samples: u64) {
+        let released_notes = self.played_notes.split_off(&(cum_release_samples + 1));
+        for (_, notes) in released_notes.into_iter() {
+            self.played_notes
+                .entry(cum_release_samples)
+                .or_default()
+                .extend(notes);
+        }
+    }
+
     pub fn play(
         &mut self,
         track: &Track<SampleNote>,
         resource_manager: Arc<ResourceManager>,
         cum_current_samples: &u64,
-        cum_current_beats: &Beat,
-        current_bpm: &f32,
     ) -> (Vec<f32>, Vec<f32>) {
         let mut left_wave: Vec<f32> = Vec::new();
         let mut right_wave: Vec<f32> = Vec::new();
@@ -44,53 +100,6 @@ impl SampleTrackPlayer {
         right_wave.resize(self.wave_length as usize, 0.0);
 
         let cum_next_samples = cum_current_samples + self.wave_length;
-        let cum_next_beats =
-            *cum_current_beats + Beat::from(self.wave_length as f32 * current_bpm / 44100.0 / 60.0);
-
-        // 付け加えるnotesをリストアップする。
-        // self.played_notesに加える。
-        if track.phrase.length > Beat::from(0) {
-            let rep_current_beats = *cum_current_beats % track.phrase.length;
-            let rep_next_beats = cum_next_beats % track.phrase.length;
-
-            if rep_current_beats < rep_next_beats {
-                for (&start, new_notes) in track
-                    .phrase
-                    .notes
-                    .range((Included(rep_current_beats), Excluded(rep_next_beats)))
-                {
-                    let cum_start_samples = ((start - rep_current_beats).to_f32() * 44100.0 * 60.0
-                        / current_bpm) as u64
-                        + cum_current_samples;
-                    self.register_notes(new_notes, &current_bpm, &cum_start_samples);
-                }
-            } else {
-                for (&start, new_notes) in track
-                    .phrase
-                    .notes
-                    .range((Included(rep_current_beats), Excluded(track.phrase.length)))
-                {
-                    let cum_start_samples = ((start - rep_current_beats).to_f32() * 44100.0 * 60.0
-                        / current_bpm) as u64
-                        + cum_current_samples;
-                    self.register_notes(new_notes, &current_bpm, &cum_start_samples);
-                }
-                for (&start, new_notes) in track
-                    .phrase
-                    .notes
-                    .range((Included(Beat::from(0)), Excluded(rep_next_beats)))
-                {
-                    let cum_start_samples = ((track.phrase.length + start - rep_current_beats)
-                        .to_f32()
-                        * 44100.0
-                        * 60.0
-                        / current_bpm) as u64
-                        + cum_current_samples;
-
-                    self.register_notes(new_notes, &current_bpm, &cum_start_samples);
-                }
-            }
-        }
 
         // self.played_notesのを鳴らす
         for (&cum_end_samples, notes) in self.played_notes.iter() {
@@ -117,13 +126,18 @@ impl SampleTrackPlayer {
                             let end_idx_for_sample =
                                 (cum_current_samples + end_idx as u64 - cum_start_samples) as usize;
 
-                            let sample_data =
-                                wave.get_samples(start_idx_for_sample, end_idx_for_sample);
+                            let sample_data = wave.get_samples_with_sample_rate(
+                                start_idx_for_sample,
+                                end_idx_for_sample,
+                                self.sample_rate,
+                            );
                             match sample_data {
                                 Ok((left_sample, right_sample)) => {
                                     for (i, j) in (start_idx..end_idx).enumerate() {
-                                        let left_addition = left_sample[i] * 0.5 * track.vol;
-                                        let right_addition = right_sample[i] * 0.5 * track.vol;
+                                        let left_addition =
+                                            left_sample[i] * 0.5 * note.get_gain() * track.vol;
+                                        let right_addition =
+                                            right_sample[i] * 0.5 * note.get_gain() * track.vol;
                                         if track.pan > 0.0 {
                                             left_wave[j] =
                                                 left_wave[j] + (1.0 - track.pan) * left_addition;
@@ -151,22 +165,10 @@ impl SampleTrackPlayer {
             }
         }
 
-        // Effect更新
-        if self.effect_infos != track.effects {
-            self.effect_infos = track.effects.clone();
-            let mut effects = vec![];
-            for efi in self.effect_infos.iter() {
-                effects.push(efi.get_effect(Arc::clone(&resource_manager)));
-            }
-            self.effects = effects;
-        }
-
         // Effect
-        for effect in self.effects.iter_mut() {
-            let (l, r) = effect.effect(&left_wave, &right_wave);
-            left_wave = l;
-            right_wave = r;
-        }
+        self.effect_chain
+            .update(&track.effects, Arc::clone(&resource_manager));
+        let (left_wave, right_wave) = self.effect_chain.effect(left_wave, right_wave);
 
         // 使ったself.played_notesのノートを消す
         for cum_note_samples in *cum_current_samples..cum_next_samples {
@@ -176,15 +178,55 @@ impl SampleTrackPlayer {
         (left_wave, right_wave)
     }
 
+    // cum_beatsの時点ですでに鳴り始めていて、まだ終わっていないnoteを途中から鳴らす。
+    // cum_beatsちょうどに始まるnoteはschedule_notesで登録される
+    pub fn chase_notes(
+        &mut self,
+        track: &Track<SampleNote>,
+        timeline: &Timeline,
+        cum_beats: &Beat,
+    ) {
+        if track.phrase.length <= Beat::from(0) {
+            return;
+        }
+        let rep_beats = *cum_beats % track.phrase.length;
+        let cum_phrase_start_beats = *cum_beats - rep_beats;
+
+        // 1つ前の繰り返しで始まったnoteも、長ければまだ鳴っている
+        for &cum_phrase_start_beats in [
+            cum_phrase_start_beats - track.phrase.length,
+            cum_phrase_start_beats,
+        ]
+        .iter()
+        {
+            if cum_phrase_start_beats < Beat::from(0) {
+                continue;
+            }
+            for (&start, notes) in track.phrase.notes.iter() {
+                let cum_start_beats = cum_phrase_start_beats + start;
+                if cum_start_beats >= *cum_beats {
+                    break;
+                }
+                let sounding_notes: BTreeSet<SampleNote> = notes
+                    .iter()
+                    .filter(|_| cum_start_beats + Beat::from(1) > *cum_beats)
+                    .cloned()
+                    .collect();
+                self.register_notes(&sounding_notes, timeline, cum_start_beats);
+            }
+        }
+    }
+
     fn register_notes(
         &mut self,
         notes: &BTreeSet<SampleNote>,
-        current_bpm: &f32,
-        cum_start_samples: &u64,
+        timeline: &Timeline,
+        cum_start_beats: Beat,
     ) {
+        let cum_start_samples = &timeline.beat_to_samples(cum_start_beats);
         for note in notes.iter() {
             let note = note.clone();
-            let cum_end_samples = cum_start_samples + (1.0 * 44100.0 * 60.0 / current_bpm) as u64; // TODO: accurate wave length
+            let cum_end_samples = timeline.beat_to_samples(cum_start_beats + Beat::from(1)); // TODO: accurate wave length
 
             if self.played_notes.contains_key(&cum_end_samples) {
                 match self.played_notes.get_mut(&cum_end_samples) {
@@ -1,7 +1,9 @@
 mod music;
 mod scheduling;
 mod section;
+mod track;
 
 pub use music::{MusicState, MusicStateEvent};
-pub use scheduling::{SchedulingState, SchedulingStateEvent};
+pub use scheduling::{SchedulingState, SchedulingStateEvent, TempoRamp};
 pub use section::{SectionState, SectionStateEvent};
+pub use track::TrackEvent;
@@ -1,11 +1,12 @@
 use std::collections::BTreeMap;
 use std::iter::FromIterator;
-use std::ops::Bound::Included;
+use std::ops::Bound::{Excluded, Included, Unbounded};
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
-use super::super::super::data::music_info::Beat;
+use super::super::super::data::music_info::{BarPosition, Beat};
+use super::super::super::error::ToidError;
 use super::super::super::state_management::serialize;
 use super::super::super::state_management::state::State;
 use super::scheduling::{SchedulingState, SchedulingStateEvent};
@@ -74,6 +75,44 @@ impl MusicState {
         }
     }
 
+    // beat 0のsectionは常に存在する必要があるので、削除や移動の後は空のsectionで埋める
+    fn remove_section(&self, beat: Beat) -> Self {
+        let mut new_section_map = self.section_map.clone();
+        new_section_map.remove(&beat);
+        new_section_map
+            .entry(Beat::from(0))
+            .or_insert_with(|| Arc::new(SectionState::new()));
+        Self {
+            scheduling: Arc::clone(&self.scheduling),
+            section_map: new_section_map,
+        }
+    }
+
+    fn move_section(&self, from: Beat, to: Beat) -> Self {
+        let mut new_section_map = self.section_map.clone();
+        if let Some(section) = new_section_map.remove(&from) {
+            new_section_map.insert(to, section);
+        }
+        new_section_map
+            .entry(Beat::from(0))
+            .or_insert_with(|| Arc::new(SectionState::new()));
+        Self {
+            scheduling: Arc::clone(&self.scheduling),
+            section_map: new_section_map,
+        }
+    }
+
+    fn duplicate_section(&self, from: Beat, to: Beat) -> Self {
+        let mut new_section_map = self.section_map.clone();
+        if let Some(section) = self.section_map.get(&from) {
+            new_section_map.insert(to, Arc::clone(section));
+        }
+        Self {
+            scheduling: Arc::clone(&self.scheduling),
+            section_map: new_section_map,
+        }
+    }
+
     pub fn get_section_state_by_beat(&self, beat: Beat) -> Arc<SectionState> {
         Arc::clone(
             self.section_map
@@ -85,6 +124,22 @@ impl MusicState {
         )
     }
 
+    // beatを含むsectionの開始位置と、次のsectionの開始位置
+    pub fn get_section_range(&self, beat: Beat) -> (Beat, Option<Beat>) {
+        let start = *self
+            .section_map
+            .range((Included(&Beat::from(0)), Included(&beat)))
+            .next_back()
+            .unwrap()
+            .0;
+        let end = self
+            .section_map
+            .range((Excluded(&start), Unbounded))
+            .next()
+            .map(|(&end, _)| end);
+        (start, end)
+    }
+
     pub fn get_section_beats(&self) -> Vec<Beat> {
         Vec::from_iter(self.section_map.keys().cloned())
     }
@@ -105,6 +160,12 @@ impl State<MusicStateEvent> for MusicState {
             MusicStateEvent::SectionStateEvent(beat, e) => self.section_state_event(beat, e),
             MusicStateEvent::SchedulingStateEvent(e) => self.scheduling_state_event(e),
             MusicStateEvent::NewSection(beat) => self.new_section(beat),
+            MusicStateEvent::NewSectionAtBar(bar_position) => {
+                self.new_section(self.scheduling.bar_position_to_beat(bar_position))
+            }
+            MusicStateEvent::RemoveSection(beat) => self.remove_section(beat),
+            MusicStateEvent::MoveSection(from, to) => self.move_section(from, to),
+            MusicStateEvent::DuplicateSection(from, to) => self.duplicate_section(from, to),
             MusicStateEvent::Clear => self.clear(),
             MusicStateEvent::ClearSections => self.clear_sections(),
         }
@@ -112,16 +173,16 @@ impl State<MusicStateEvent> for MusicState {
 }
 
 impl serialize::Serialize<MusicState> for MusicState {
-    fn serialize(&self) -> Result<String, String> {
+    fn serialize(&self) -> Result<String, ToidError> {
         match serde_json::to_string(&self) {
             Ok(serialized) => Ok(serialized),
-            Err(err) => Err(format!("error in serizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
-    fn deserialize(serialized: String) -> Result<Self, String> {
+    fn deserialize(serialized: String) -> Result<Self, ToidError> {
         match serde_json::from_str(serialized.as_str()) {
             Ok(deserialized) => Ok(deserialized),
-            Err(err) => Err(format!("error in deserizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
 }
@@ -131,21 +192,25 @@ pub enum MusicStateEvent {
     SectionStateEvent(Beat, SectionStateEvent),
     SchedulingStateEvent(SchedulingStateEvent),
     NewSection(Beat),
+    NewSectionAtBar(BarPosition),
+    RemoveSection(Beat),
+    MoveSection(Beat, Beat),
+    DuplicateSection(Beat, Beat),
     Clear,
     ClearSections,
 }
 
 impl serialize::Serialize<MusicStateEvent> for MusicStateEvent {
-    fn serialize(&self) -> Result<String, String> {
+    fn serialize(&self) -> Result<String, ToidError> {
         match serde_json::to_string(&self) {
             Ok(serialized) => Ok(serialized),
-            Err(err) => Err(format!("error in serizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
-    fn deserialize(serialized: String) -> Result<Self, String> {
+    fn deserialize(serialized: String) -> Result<Self, ToidError> {
         match serde_json::from_str(serialized.as_str()) {
             Ok(deserialized) => Ok(deserialized),
-            Err(err) => Err(format!("error in deserizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
 }
@@ -1,55 +1,418 @@
 use std::collections::BTreeMap;
 
+use log::error;
 use serde::{Deserialize, Serialize};
 
-use super::super::super::data::music_info::Beat;
+use super::super::super::data::music_info::{BarPosition, Beat, Position, TimeSignature};
+use super::super::super::error::ToidError;
 use super::super::super::state_management::serialize;
 use super::super::super::state_management::state::State;
 
+// bpm_scheduleの直前の点から、その点に向かってbpmをどう変化させるか
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TempoRamp {
+    Step,
+    Linear,
+    Exponential,
+}
+
+// beatと秒、小節の変換はreadのたびに呼ばれるので、区間はscheduleが変わったときに作っておく
 #[derive(Serialize, Deserialize)]
+#[serde(from = "RawSchedulingState")]
 pub struct SchedulingState {
-    pub bpm_schedule: BTreeMap<Beat, f32>,
+    bpm_schedule: BTreeMap<Beat, f32>,
+    // 登録されていない点はStep
+    ramp_schedule: BTreeMap<Beat, TempoRamp>,
+    // 最初の点より前は4/4とみなす
+    time_signature_schedule: BTreeMap<Beat, TimeSignature>,
+    #[serde(skip)]
+    segments: Vec<TempoSegment>,
+    #[serde(skip)]
+    meter_segments: Vec<(Beat, i64, TimeSignature)>,
+}
+
+#[derive(Deserialize)]
+struct RawSchedulingState {
+    bpm_schedule: BTreeMap<Beat, f32>,
+    #[serde(default)]
+    ramp_schedule: BTreeMap<Beat, TempoRamp>,
+    #[serde(default)]
+    time_signature_schedule: BTreeMap<Beat, TimeSignature>,
+}
+
+impl From<RawSchedulingState> for SchedulingState {
+    fn from(raw: RawSchedulingState) -> Self {
+        Self::from_schedules(
+            raw.bpm_schedule,
+            raw.ramp_schedule,
+            raw.time_signature_schedule,
+        )
+    }
+}
+
+// bpm_scheduleの隣り合う2点の間の区間。beatは区間の始点からの相対値
+struct TempoSegment {
+    start: f64,
+    end: f64,
+    start_bpm: f64,
+    end_bpm: f64,
+    ramp: TempoRamp,
+}
+
+impl TempoSegment {
+    fn get_bpm(&self, beat: f64) -> f64 {
+        let length = self.end - self.start;
+        match self.ramp {
+            TempoRamp::Step => self.start_bpm,
+            TempoRamp::Linear => self.start_bpm + (self.end_bpm - self.start_bpm) * beat / length,
+            TempoRamp::Exponential => {
+                self.start_bpm * (self.end_bpm / self.start_bpm).powf(beat / length)
+            }
+        }
+    }
+
+    // 60 / bpm(beat) を区間の始点からbeatまで積分する
+    fn beat_to_sec(&self, beat: f64) -> f64 {
+        let length = self.end - self.start;
+        match self.ramp {
+            TempoRamp::Step => 60.0 * beat / self.start_bpm,
+            TempoRamp::Linear => {
+                let slope = (self.end_bpm - self.start_bpm) / length;
+                if slope == 0.0 {
+                    60.0 * beat / self.start_bpm
+                } else {
+                    60.0 / slope * ((self.start_bpm + slope * beat) / self.start_bpm).ln()
+                }
+            }
+            TempoRamp::Exponential => {
+                let rate = (self.end_bpm / self.start_bpm).ln() / length;
+                if rate == 0.0 {
+                    60.0 * beat / self.start_bpm
+                } else {
+                    60.0 / (self.start_bpm * rate) * (1.0 - (-rate * beat).exp())
+                }
+            }
+        }
+    }
+
+    fn sec_to_beat(&self, sec: f64) -> f64 {
+        let length = self.end - self.start;
+        match self.ramp {
+            TempoRamp::Step => sec * self.start_bpm / 60.0,
+            TempoRamp::Linear => {
+                let slope = (self.end_bpm - self.start_bpm) / length;
+                if slope == 0.0 {
+                    sec * self.start_bpm / 60.0
+                } else {
+                    self.start_bpm / slope * ((slope * sec / 60.0).exp() - 1.0)
+                }
+            }
+            TempoRamp::Exponential => {
+                let rate = (self.end_bpm / self.start_bpm).ln() / length;
+                if rate == 0.0 {
+                    sec * self.start_bpm / 60.0
+                } else {
+                    -(1.0 - self.start_bpm * rate * sec / 60.0).ln() / rate
+                }
+            }
+        }
+    }
+
+    fn get_sec_length(&self) -> f64 {
+        self.beat_to_sec(self.end - self.start)
+    }
 }
 
 impl SchedulingState {
+    fn from_schedules(
+        bpm_schedule: BTreeMap<Beat, f32>,
+        ramp_schedule: BTreeMap<Beat, TempoRamp>,
+        time_signature_schedule: BTreeMap<Beat, TimeSignature>,
+    ) -> Self {
+        let segments = Self::build_segments(&bpm_schedule, &ramp_schedule);
+        let meter_segments = Self::build_meter_segments(&time_signature_schedule);
+        SchedulingState {
+            bpm_schedule,
+            ramp_schedule,
+            time_signature_schedule,
+            segments,
+            meter_segments,
+        }
+    }
+
+    // 0以下や有限でないbpmは、秒とbeatの変換が壊れるので入れない
     fn change_bpm(&self, change: Beat, bpm: f32) -> Self {
         let mut new_bpm_schedule = self.bpm_schedule.clone();
-        new_bpm_schedule.insert(change, bpm);
-        SchedulingState {
-            bpm_schedule: new_bpm_schedule,
+        let mut new_ramp_schedule = self.ramp_schedule.clone();
+        match validate_bpm(bpm) {
+            Ok(()) => {
+                new_bpm_schedule.insert(change, bpm);
+                new_ramp_schedule.remove(&change);
+            }
+            Err(e) => error!("change_bpm Error {}", e),
+        }
+        Self::from_schedules(
+            new_bpm_schedule,
+            new_ramp_schedule,
+            self.time_signature_schedule.clone(),
+        )
+    }
+
+    fn ramp_bpm(&self, change: Beat, bpm: f32, ramp: TempoRamp) -> Self {
+        let mut new_bpm_schedule = self.bpm_schedule.clone();
+        let mut new_ramp_schedule = self.ramp_schedule.clone();
+        match validate_bpm(bpm) {
+            Ok(()) => {
+                new_bpm_schedule.insert(change, bpm);
+                new_ramp_schedule.insert(change, ramp);
+            }
+            Err(e) => error!("ramp_bpm Error {}", e),
+        }
+        Self::from_schedules(
+            new_bpm_schedule,
+            new_ramp_schedule,
+            self.time_signature_schedule.clone(),
+        )
+    }
+
+    // newを通らずに作られた拍子は、小節の長さが0になりうるので入れない
+    fn change_time_signature(&self, change: Beat, time_signature: TimeSignature) -> Self {
+        let mut new_time_signature_schedule = self.time_signature_schedule.clone();
+        match time_signature.validate() {
+            Ok(()) => {
+                new_time_signature_schedule.insert(change, time_signature);
+            }
+            Err(e) => error!("change_time_signature Error {}", e),
         }
+        Self::from_schedules(
+            self.bpm_schedule.clone(),
+            self.ramp_schedule.clone(),
+            new_time_signature_schedule,
+        )
     }
 
     pub fn get_bpm_schedule(&self) -> BTreeMap<Beat, f32> {
         self.bpm_schedule.clone()
     }
+
+    // beat 0より前に点が無い場合は、最初の点のbpmが0から続いているとみなす
+    fn build_segments(
+        bpm_schedule: &BTreeMap<Beat, f32>,
+        ramp_schedule: &BTreeMap<Beat, TempoRamp>,
+    ) -> Vec<TempoSegment> {
+        let points: Vec<(Beat, f64)> = bpm_schedule
+            .iter()
+            .map(|(&beat, &bpm)| (beat, bpm as f64))
+            .collect();
+
+        let mut segments = vec![];
+        let first_bpm = points.first().map_or(120.0, |&(_, bpm)| bpm);
+        let first_beat = points.first().map_or(0.0, |&(beat, _)| beat.to_f64());
+        if first_beat > 0.0 || points.is_empty() {
+            segments.push(TempoSegment {
+                start: 0.0,
+                end: if points.is_empty() {
+                    f64::INFINITY
+                } else {
+                    first_beat
+                },
+                start_bpm: first_bpm,
+                end_bpm: first_bpm,
+                ramp: TempoRamp::Step,
+            });
+        }
+        for (i, &(start, start_bpm)) in points.iter().enumerate() {
+            match points.get(i + 1) {
+                Some(&(end, end_bpm)) => {
+                    let ramp = *ramp_schedule.get(&end).unwrap_or(&TempoRamp::Step);
+                    segments.push(TempoSegment {
+                        start: start.to_f64(),
+                        end: end.to_f64(),
+                        start_bpm,
+                        end_bpm,
+                        ramp,
+                    });
+                }
+                None => {
+                    segments.push(TempoSegment {
+                        start: start.to_f64(),
+                        end: f64::INFINITY,
+                        start_bpm,
+                        end_bpm: start_bpm,
+                        ramp: TempoRamp::Step,
+                    });
+                }
+            }
+        }
+        segments
+    }
+
+    pub fn get_bpm(&self, beat: Beat) -> f32 {
+        let beat = beat.to_f64();
+        for segment in self.segments.iter() {
+            if beat < segment.end {
+                return segment.get_bpm(beat - segment.start) as f32;
+            }
+        }
+        120.0
+    }
+
+    // beat 0からbeatまでの秒数
+    pub fn beat_to_sec(&self, beat: Beat) -> f64 {
+        let beat = beat.to_f64();
+        let mut sec = 0.0;
+        for segment in self.segments.iter() {
+            if beat < segment.end {
+                return sec + segment.beat_to_sec(beat - segment.start);
+            }
+            sec += segment.get_sec_length();
+        }
+        sec
+    }
+
+    // beat_to_secの逆変換。Beatの精度に丸めないようにf64で返す
+    pub fn sec_to_beat(&self, sec: f64) -> f64 {
+        let mut segment_start_sec = 0.0;
+        for segment in self.segments.iter() {
+            let segment_sec_length = segment.get_sec_length();
+            if sec < segment_start_sec + segment_sec_length {
+                return segment.start + segment.sec_to_beat(sec - segment_start_sec);
+            }
+            segment_start_sec += segment_sec_length;
+        }
+        0.0
+    }
+
+    // 拍子が変わる位置から新しい小節を始める。途中で切れた小節も1小節と数える
+    fn build_meter_segments(
+        time_signature_schedule: &BTreeMap<Beat, TimeSignature>,
+    ) -> Vec<(Beat, i64, TimeSignature)> {
+        let mut segments = vec![];
+        let mut start_bar = 0;
+        let mut prev: Option<(Beat, TimeSignature)> = match time_signature_schedule.keys().next() {
+            Some(&first) if first > Beat::from(0) => {
+                Some((Beat::from(0), TimeSignature::default()))
+            }
+            None => Some((Beat::from(0), TimeSignature::default())),
+            _ => None,
+        };
+        for (&start, &time_signature) in time_signature_schedule.iter() {
+            if let Some((prev_start, prev_time_signature)) = prev {
+                segments.push((prev_start, start_bar, prev_time_signature));
+                let length = (start - prev_start).get_num();
+                let bar_length = prev_time_signature.get_bar_length().get_num();
+                start_bar += (length + bar_length - 1) / bar_length;
+            }
+            prev = Some((start, time_signature));
+        }
+        if let Some((prev_start, prev_time_signature)) = prev {
+            segments.push((prev_start, start_bar, prev_time_signature));
+        }
+        segments
+    }
+
+    pub fn get_time_signature(&self, beat: Beat) -> TimeSignature {
+        match self
+            .meter_segments
+            .iter()
+            .rev()
+            .find(|&&(start, _, _)| start <= beat)
+        {
+            Some(&(_, _, time_signature)) => time_signature,
+            None => TimeSignature::default(),
+        }
+    }
+
+    pub fn beat_to_bar_position(&self, beat: Beat) -> BarPosition {
+        let segments = &self.meter_segments;
+        let (start, start_bar, time_signature) = segments
+            .iter()
+            .rev()
+            .find(|&&(start, _, _)| start <= beat)
+            .cloned()
+            .unwrap_or(segments[0]);
+
+        let offset = (beat - start).get_num();
+        let bar_length = time_signature.get_bar_length().get_num();
+        let beat_length = time_signature.get_beat_length().get_num();
+        let bar = offset.div_euclid(bar_length);
+        let in_bar = offset.rem_euclid(bar_length);
+        BarPosition {
+            bar: start_bar + bar,
+            beat: in_bar / beat_length,
+            tick: in_bar % beat_length,
+        }
+    }
+
+    pub fn bar_position_to_beat(&self, bar_position: BarPosition) -> Beat {
+        let segments = &self.meter_segments;
+        let (start, start_bar, time_signature) = segments
+            .iter()
+            .rev()
+            .find(|&&(_, start_bar, _)| start_bar <= bar_position.bar)
+            .cloned()
+            .unwrap_or(segments[0]);
+
+        let bar_length = time_signature.get_bar_length().get_num();
+        let beat_length = time_signature.get_beat_length().get_num();
+        start
+            + Beat::from_num(
+                (bar_position.bar - start_bar) * bar_length
+                    + bar_position.beat * beat_length
+                    + bar_position.tick,
+            )
+    }
+
+    pub fn get_beat(&self, position: Position) -> Beat {
+        match position {
+            Position::Beat(beat) => beat,
+            Position::Bar(bar_position) => self.bar_position_to_beat(bar_position),
+        }
+    }
+}
+
+fn validate_bpm(bpm: f32) -> Result<(), ToidError> {
+    if bpm.is_finite() && bpm > 0.0 {
+        Ok(())
+    } else {
+        Err(ToidError::OutOfRange(format!(
+            "bpm must be finite and positive : {}",
+            bpm
+        )))
+    }
 }
 
 impl State<SchedulingStateEvent> for SchedulingState {
     fn new() -> Self {
         let mut bpm_schedule = BTreeMap::new();
         bpm_schedule.insert(Beat::from(0), 120.0);
-        SchedulingState { bpm_schedule }
+        let mut time_signature_schedule = BTreeMap::new();
+        time_signature_schedule.insert(Beat::from(0), TimeSignature::default());
+        Self::from_schedules(bpm_schedule, BTreeMap::new(), time_signature_schedule)
     }
 
     fn reduce(&self, event: SchedulingStateEvent) -> Self {
         match event {
             SchedulingStateEvent::ChangeBPM(beat, bpm) => self.change_bpm(beat, bpm),
+            SchedulingStateEvent::RampBPM(beat, bpm, ramp) => self.ramp_bpm(beat, bpm, ramp),
+            SchedulingStateEvent::ChangeTimeSignature(beat, time_signature) => {
+                self.change_time_signature(beat, time_signature)
+            }
         }
     }
 }
 
 impl serialize::Serialize<SchedulingState> for SchedulingState {
-    fn serialize(&self) -> Result<String, String> {
+    fn serialize(&self) -> Result<String, ToidError> {
         match serde_json::to_string(&self) {
             Ok(serialized) => Ok(serialized),
-            Err(err) => Err(format!("error in serizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
-    fn deserialize(serialized: String) -> Result<Self, String> {
+    fn deserialize(serialized: String) -> Result<Self, ToidError> {
         match serde_json::from_str(serialized.as_str()) {
             Ok(deserialized) => Ok(deserialized),
-            Err(err) => Err(format!("error in deserizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
 }
@@ -57,19 +420,166 @@ impl serialize::Serialize<SchedulingState> for SchedulingState {
 #[derive(Serialize, Deserialize)]
 pub enum SchedulingStateEvent {
     ChangeBPM(Beat, f32),
+    RampBPM(Beat, f32, TempoRamp),
+    ChangeTimeSignature(Beat, TimeSignature),
 }
 
 impl serialize::Serialize<SchedulingStateEvent> for SchedulingStateEvent {
-    fn serialize(&self) -> Result<String, String> {
+    fn serialize(&self) -> Result<String, ToidError> {
         match serde_json::to_string(&self) {
             Ok(serialized) => Ok(serialized),
-            Err(err) => Err(format!("error in serizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
-    fn deserialize(serialized: String) -> Result<Self, String> {
+    fn deserialize(serialized: String) -> Result<Self, ToidError> {
         match serde_json::from_str(serialized.as_str()) {
             Ok(deserialized) => Ok(deserialized),
-            Err(err) => Err(format!("error in deserizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step() {
+        let state =
+            SchedulingState::new().reduce(SchedulingStateEvent::ChangeBPM(Beat::from(4), 60.0));
+        assert!((state.beat_to_sec(Beat::from(4)) - 2.0).abs() < 1e-9);
+        assert!((state.beat_to_sec(Beat::from(6)) - 4.0).abs() < 1e-9);
+        assert!((state.sec_to_beat(3.0) - 5.0).abs() < 1e-9);
+        assert_eq!(state.get_bpm(Beat::from(3)), 120.0);
+        assert_eq!(state.get_bpm(Beat::from(4)), 60.0);
+    }
+
+    #[test]
+    fn test_bar_position() {
+        let state = SchedulingState::new()
+            .reduce(SchedulingStateEvent::ChangeTimeSignature(
+                Beat::from(8),
+                TimeSignature::new(7, 8).unwrap(),
+            ))
+            .reduce(SchedulingStateEvent::ChangeTimeSignature(
+                Beat::from(13),
+                TimeSignature::new(4, 4).unwrap(),
+            ));
+        assert_eq!(
+            state.beat_to_bar_position(Beat::from(5.5)),
+            BarPosition::new(1, 1, 480)
+        );
+        assert_eq!(
+            state.beat_to_bar_position(Beat::from(12)),
+            BarPosition::new(3, 1, 0)
+        );
+        assert_eq!(state.get_time_signature(Beat::from(12)).numerator, 7);
+        // 7/8の2小節目は途中で4/4に変わるので、4/4は5小節目から始まる
+        assert_eq!(
+            state.beat_to_bar_position(Beat::from(13)),
+            BarPosition::new(4, 0, 0)
+        );
+        for &beat in [0.0, 3.25, 8.0, 11.5, 12.75, 20.0].iter() {
+            let bar_position = state.beat_to_bar_position(Beat::from(beat));
+            assert_eq!(state.bar_position_to_beat(bar_position), Beat::from(beat));
+        }
+        assert_eq!(
+            state.get_beat(Position::Bar(BarPosition::from_bar(3))),
+            Beat::from(11.5)
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let state = SchedulingState::new()
+            .reduce(SchedulingStateEvent::RampBPM(
+                Beat::from(4),
+                60.0,
+                TempoRamp::Linear,
+            ))
+            .reduce(SchedulingStateEvent::ChangeTimeSignature(
+                Beat::from(8),
+                TimeSignature::new(3, 4).unwrap(),
+            ));
+        // deserializeしたときも区間を作り直す
+        let serialized = serde_json::to_string(&state).unwrap();
+        let deserialized: SchedulingState = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(
+            deserialized.beat_to_sec(Beat::from(6)),
+            state.beat_to_sec(Beat::from(6))
+        );
+        assert_eq!(
+            deserialized.beat_to_bar_position(Beat::from(11)),
+            BarPosition::from_bar(3)
+        );
+    }
+
+    #[test]
+    fn test_invalid_time_signature() {
+        let state = SchedulingState::new().reduce(SchedulingStateEvent::ChangeTimeSignature(
+            Beat::from(4),
+            TimeSignature {
+                numerator: 0,
+                denominator: 4,
+            },
+        ));
+        assert_eq!(
+            state.get_time_signature(Beat::from(4)),
+            TimeSignature::default()
+        );
+        assert_eq!(
+            state.beat_to_bar_position(Beat::from(4)),
+            BarPosition::from_bar(1)
+        );
+    }
+
+    #[test]
+    fn test_invalid_bpm() {
+        let state = SchedulingState::new().reduce(SchedulingStateEvent::RampBPM(
+            Beat::from(4),
+            60.0,
+            TempoRamp::Linear,
+        ));
+        let bpm_schedule = state.get_bpm_schedule();
+        for &bpm in [0.0, -120.0, f32::NAN, f32::INFINITY].iter() {
+            let changed = state
+                .reduce(SchedulingStateEvent::ChangeBPM(Beat::from(4), bpm))
+                .reduce(SchedulingStateEvent::RampBPM(
+                    Beat::from(8),
+                    bpm,
+                    TempoRamp::Exponential,
+                ));
+            assert_eq!(changed.get_bpm_schedule(), bpm_schedule);
+            assert_eq!(changed.get_bpm(Beat::from(2)), state.get_bpm(Beat::from(2)));
+            assert_eq!(
+                changed.beat_to_sec(Beat::from(8)),
+                state.beat_to_sec(Beat::from(8))
+            );
+        }
+    }
+
+    #[test]
+    fn test_ramp() {
+        for &ramp in [TempoRamp::Linear, TempoRamp::Exponential].iter() {
+            let state = SchedulingState::new()
+                .reduce(SchedulingStateEvent::ChangeBPM(Beat::from(4), 60.0))
+                .reduce(SchedulingStateEvent::RampBPM(Beat::from(8), 120.0, ramp));
+            assert!((state.get_bpm(Beat::from(6)) - 60.0).abs() > 1.0);
+            assert!((state.get_bpm(Beat::from(6)) - 120.0).abs() > 1.0);
+
+            // 細かく区切った数値積分と一致する
+            let mut numerical_sec = 0.0;
+            let step = 1.0 / 960.0;
+            for i in 0..(8 * 960) {
+                let beat = Beat::from(i as f64 * step + step / 2.0);
+                numerical_sec += 60.0 / state.get_bpm(beat) as f64 * step;
+            }
+            assert!((state.beat_to_sec(Beat::from(8)) - numerical_sec).abs() < 1e-3);
+
+            for &beat in [1.0, 5.0, 6.5, 7.9, 10.0].iter() {
+                let sec = state.beat_to_sec(Beat::from(beat));
+                assert!((state.sec_to_beat(sec) - beat).abs() < 1e-6);
+            }
         }
     }
 }
@@ -1,48 +1,196 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 
+use log::error;
 use serde::{Deserialize, Serialize};
 
 use super::super::super::data::music_info::{PitchNote, SampleNote, Track};
+use super::super::super::error::ToidError;
 use super::super::super::state_management::serialize;
 use super::super::super::state_management::state::State;
-use super::super::effects::EffectInfo;
+use super::super::effects::{self, EffectInfo};
+use super::track::{reduce_track, TrackEvent};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct SectionState {
     pub pitch_track_map: HashMap<String, Track<PitchNote>>,
     pub sample_track_map: HashMap<String, Track<SampleNote>>,
     pub effects: Vec<EffectInfo>,
+    #[serde(default)]
+    pub muted_pitch_tracks: HashSet<String>,
+    #[serde(default)]
+    pub muted_sample_tracks: HashSet<String>,
+    #[serde(default)]
+    pub soloed_pitch_tracks: HashSet<String>,
+    #[serde(default)]
+    pub soloed_sample_tracks: HashSet<String>,
 }
 
 impl SectionState {
+    // 書き換えるfieldだけをその場で編集するので、cloneは1回で済む
     fn new_pitch_track(&self, key: String, track: Track<PitchNote>) -> Self {
-        let mut new_pitch_track_map = self.pitch_track_map.clone();
-        new_pitch_track_map.insert(key, track);
-        Self {
-            pitch_track_map: new_pitch_track_map,
-            sample_track_map: self.sample_track_map.clone(),
-            effects: self.effects.clone(),
-        }
+        let mut new_state = self.clone();
+        new_state.pitch_track_map.insert(key, track);
+        new_state
     }
 
     fn new_sample_track(&self, key: String, track: Track<SampleNote>) -> Self {
-        let mut new_sample_track_map = self.sample_track_map.clone();
-        new_sample_track_map.insert(key, track);
-        Self {
-            pitch_track_map: self.pitch_track_map.clone(),
-            sample_track_map: new_sample_track_map,
-            effects: self.effects.clone(),
-        }
+        let mut new_state = self.clone();
+        new_state.sample_track_map.insert(key, track);
+        new_state
     }
 
     fn add_effect(&self, effect: EffectInfo) -> Self {
-        let mut new_effects = self.effects.clone();
-        new_effects.push(effect);
+        let mut new_state = self.clone();
+        new_state.effects.push(effect);
+        new_state
+    }
+
+    fn clear_effects(&self) -> Self {
         Self {
             pitch_track_map: self.pitch_track_map.clone(),
             sample_track_map: self.sample_track_map.clone(),
-            effects: new_effects,
+            effects: vec![],
+            muted_pitch_tracks: self.muted_pitch_tracks.clone(),
+            muted_sample_tracks: self.muted_sample_tracks.clone(),
+            soloed_pitch_tracks: self.soloed_pitch_tracks.clone(),
+            soloed_sample_tracks: self.soloed_sample_tracks.clone(),
+        }
+    }
+
+    fn remove_effect(&self, idx: usize) -> Self {
+        let mut new_state = self.clone();
+        effects::remove_effect(&mut new_state.effects, idx);
+        new_state
+    }
+
+    fn move_effect(&self, from: usize, to: usize) -> Self {
+        let mut new_state = self.clone();
+        effects::move_effect(&mut new_state.effects, from, to);
+        new_state
+    }
+
+    fn replace_effect(&self, idx: usize, effect: EffectInfo) -> Self {
+        let mut new_state = self.clone();
+        effects::replace_effect(&mut new_state.effects, idx, effect);
+        new_state
+    }
+
+    // trackが無い場合は何もしない
+    fn pitch_track_event(&self, key: String, event: TrackEvent) -> Self {
+        match self.pitch_track_map.get(&key) {
+            Some(track) => {
+                let track = reduce_track(track, event);
+                self.new_pitch_track(key, track)
+            }
+            None => self.clone(),
+        }
+    }
+
+    fn sample_track_event(&self, key: String, event: TrackEvent) -> Self {
+        match self.sample_track_map.get(&key) {
+            Some(track) => {
+                let track = reduce_track(track, event);
+                self.new_sample_track(key, track)
+            }
+            None => self.clone(),
+        }
+    }
+
+    fn remove_pitch_track(&self, key: String) -> Self {
+        let mut new_state = self.clone();
+        new_state.pitch_track_map.remove(&key);
+        new_state.muted_pitch_tracks.remove(&key);
+        new_state.soloed_pitch_tracks.remove(&key);
+        new_state
+    }
+
+    fn remove_sample_track(&self, key: String) -> Self {
+        let mut new_state = self.clone();
+        new_state.sample_track_map.remove(&key);
+        new_state.muted_sample_tracks.remove(&key);
+        new_state.soloed_sample_tracks.remove(&key);
+        new_state
+    }
+
+    // mute, soloの状態も新しい名前に引き継ぐ
+    // 新しい名前のtrackが既にある場合は、上書きせずにerror!を出して何もしない
+    fn rename_pitch_track(&self, key: String, new_key: String) -> Self {
+        if !self.pitch_track_map.contains_key(&key) || key == new_key {
+            return self.clone();
+        }
+        if self.pitch_track_map.contains_key(&new_key) {
+            error!("rename_pitch_track Error track {} already exists", new_key);
+            return self.clone();
+        }
+        let mut new_state = self.clone();
+        if let Some(track) = new_state.pitch_track_map.remove(&key) {
+            new_state.pitch_track_map.insert(new_key.clone(), track);
+        }
+        rename_in_set(&mut new_state.muted_pitch_tracks, &key, &new_key);
+        rename_in_set(&mut new_state.soloed_pitch_tracks, &key, &new_key);
+        new_state
+    }
+
+    fn rename_sample_track(&self, key: String, new_key: String) -> Self {
+        if !self.sample_track_map.contains_key(&key) || key == new_key {
+            return self.clone();
+        }
+        if self.sample_track_map.contains_key(&new_key) {
+            error!("rename_sample_track Error track {} already exists", new_key);
+            return self.clone();
+        }
+        let mut new_state = self.clone();
+        if let Some(track) = new_state.sample_track_map.remove(&key) {
+            new_state.sample_track_map.insert(new_key.clone(), track);
+        }
+        rename_in_set(&mut new_state.muted_sample_tracks, &key, &new_key);
+        rename_in_set(&mut new_state.soloed_sample_tracks, &key, &new_key);
+        new_state
+    }
+
+    fn set_pitch_track_mute(&self, key: String, mute: bool) -> Self {
+        let mut new_state = self.clone();
+        set_in_set(&mut new_state.muted_pitch_tracks, key, mute);
+        new_state
+    }
+
+    fn set_sample_track_mute(&self, key: String, mute: bool) -> Self {
+        let mut new_state = self.clone();
+        set_in_set(&mut new_state.muted_sample_tracks, key, mute);
+        new_state
+    }
+
+    fn set_pitch_track_solo(&self, key: String, solo: bool) -> Self {
+        let mut new_state = self.clone();
+        set_in_set(&mut new_state.soloed_pitch_tracks, key, solo);
+        new_state
+    }
+
+    fn set_sample_track_solo(&self, key: String, solo: bool) -> Self {
+        let mut new_state = self.clone();
+        set_in_set(&mut new_state.soloed_sample_tracks, key, solo);
+        new_state
+    }
+
+    // soloのtrackが1つでもあれば、soloのtrackだけを鳴らす
+    fn has_solo(&self) -> bool {
+        !self.soloed_pitch_tracks.is_empty() || !self.soloed_sample_tracks.is_empty()
+    }
+
+    pub fn is_pitch_track_audible(&self, key: &str) -> bool {
+        if self.has_solo() {
+            self.soloed_pitch_tracks.contains(key)
+        } else {
+            !self.muted_pitch_tracks.contains(key)
+        }
+    }
+
+    pub fn is_sample_track_audible(&self, key: &str) -> bool {
+        if self.has_solo() {
+            self.soloed_sample_tracks.contains(key)
+        } else {
+            !self.muted_sample_tracks.contains(key)
         }
     }
 
@@ -63,12 +211,30 @@ impl SectionState {
     }
 }
 
+fn rename_in_set(set: &mut HashSet<String>, key: &str, new_key: &str) {
+    if set.remove(key) {
+        set.insert(new_key.to_string());
+    }
+}
+
+fn set_in_set(set: &mut HashSet<String>, key: String, flag: bool) {
+    if flag {
+        set.insert(key);
+    } else {
+        set.remove(&key);
+    }
+}
+
 impl State<SectionStateEvent> for SectionState {
     fn new() -> Self {
         Self {
             pitch_track_map: HashMap::new(),
             sample_track_map: HashMap::new(),
             effects: vec![],
+            muted_pitch_tracks: HashSet::new(),
+            muted_sample_tracks: HashSet::new(),
+            soloed_pitch_tracks: HashSet::new(),
+            soloed_sample_tracks: HashSet::new(),
         }
     }
 
@@ -77,21 +243,43 @@ impl State<SectionStateEvent> for SectionState {
             SectionStateEvent::NewPitchTrack(key, track) => self.new_pitch_track(key, track),
             SectionStateEvent::NewSampleTrack(key, track) => self.new_sample_track(key, track),
             SectionStateEvent::AddEffect(effect) => self.add_effect(effect),
+            SectionStateEvent::RemoveEffect(idx) => self.remove_effect(idx),
+            SectionStateEvent::MoveEffect(from, to) => self.move_effect(from, to),
+            SectionStateEvent::ReplaceEffect(idx, effect) => self.replace_effect(idx, effect),
+            SectionStateEvent::ClearEffects => self.clear_effects(),
+            SectionStateEvent::PitchTrackEvent(key, e) => self.pitch_track_event(key, e),
+            SectionStateEvent::SampleTrackEvent(key, e) => self.sample_track_event(key, e),
+            SectionStateEvent::RemovePitchTrack(key) => self.remove_pitch_track(key),
+            SectionStateEvent::RemoveSampleTrack(key) => self.remove_sample_track(key),
+            SectionStateEvent::RenamePitchTrack(key, new_key) => {
+                self.rename_pitch_track(key, new_key)
+            }
+            SectionStateEvent::RenameSampleTrack(key, new_key) => {
+                self.rename_sample_track(key, new_key)
+            }
+            SectionStateEvent::SetPitchTrackMute(key, mute) => self.set_pitch_track_mute(key, mute),
+            SectionStateEvent::SetSampleTrackMute(key, mute) => {
+                self.set_sample_track_mute(key, mute)
+            }
+            SectionStateEvent::SetPitchTrackSolo(key, solo) => self.set_pitch_track_solo(key, solo),
+            SectionStateEvent::SetSampleTrackSolo(key, solo) => {
+                self.set_sample_track_solo(key, solo)
+            }
         }
     }
 }
 
 impl serialize::Serialize<SectionState> for SectionState {
-    fn serialize(&self) -> Result<String, String> {
+    fn serialize(&self) -> Result<String, ToidError> {
         match serde_json::to_string(&self) {
             Ok(serialized) => Ok(serialized),
-            Err(err) => Err(format!("error in serizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
-    fn deserialize(serialized: String) -> Result<Self, String> {
+    fn deserialize(serialized: String) -> Result<Self, ToidError> {
         match serde_json::from_str(serialized.as_str()) {
             Ok(deserialized) => Ok(deserialized),
-            Err(err) => Err(format!("error in deserizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
 }
@@ -101,19 +289,33 @@ pub enum SectionStateEvent {
     NewPitchTrack(String, Track<PitchNote>),
     NewSampleTrack(String, Track<SampleNote>),
     AddEffect(EffectInfo),
+    RemoveEffect(usize),
+    MoveEffect(usize, usize),
+    ReplaceEffect(usize, EffectInfo),
+    ClearEffects,
+    PitchTrackEvent(String, TrackEvent),
+    SampleTrackEvent(String, TrackEvent),
+    RemovePitchTrack(String),
+    RemoveSampleTrack(String),
+    RenamePitchTrack(String, String),
+    RenameSampleTrack(String, String),
+    SetPitchTrackMute(String, bool),
+    SetSampleTrackMute(String, bool),
+    SetPitchTrackSolo(String, bool),
+    SetSampleTrackSolo(String, bool),
 }
 
 impl serialize::Serialize<SectionStateEvent> for SectionStateEvent {
-    fn serialize(&self) -> Result<String, String> {
+    fn serialize(&self) -> Result<String, ToidError> {
         match serde_json::to_string(&self) {
             Ok(serialized) => Ok(serialized),
-            Err(err) => Err(format!("error in serizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
-    fn deserialize(serialized: String) -> Result<Self, String> {
+    fn deserialize(serialized: String) -> Result<Self, ToidError> {
         match serde_json::from_str(serialized.as_str()) {
             Ok(deserialized) => Ok(deserialized),
-            Err(err) => Err(format!("error in deserizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
 }
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::super::data::music_info::{Envelope, Glide, Note, Track};
+use super::super::effects::EffectInfo;
+
+// trackを丸ごと置き換えずに、一部だけ変える
+#[derive(Serialize, Deserialize)]
+pub enum TrackEvent {
+    AddEffect(EffectInfo),
+    RemoveEffect(usize),
+    MoveEffect(usize, usize),
+    ReplaceEffect(usize, EffectInfo),
+    ClearEffects,
+    SetEnvelope(Envelope),
+    SetGlide(Glide),
+    SetModWheel(u8),
+}
+
+pub fn reduce_track<N: Note + Ord + Eq + Clone>(track: &Track<N>, event: TrackEvent) -> Track<N> {
+    match event {
+        TrackEvent::AddEffect(effect) => track.add_effect(effect),
+        TrackEvent::RemoveEffect(idx) => track.remove_effect(idx),
+        TrackEvent::MoveEffect(from, to) => track.move_effect(from, to),
+        TrackEvent::ReplaceEffect(idx, effect) => track.replace_effect(idx, effect),
+        TrackEvent::ClearEffects => track.clear_effects(),
+        TrackEvent::SetEnvelope(envelope) => track.set_envelope(envelope),
+        TrackEvent::SetGlide(glide) => track.set_glide(glide),
+        TrackEvent::SetModWheel(mod_wheel) => track.set_mod_wheel(mod_wheel),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::super::data::music_info::PitchNote;
+    use super::*;
+
+    #[test]
+    fn test_effect_events() {
+        let track: Track<PitchNote> = Track::new();
+        let track = reduce_track(&track, TrackEvent::AddEffect(EffectInfo::ToLeftEffect));
+        let track = reduce_track(
+            &track,
+            TrackEvent::AddEffect(EffectInfo::SchroederReverb(1.0, 0.5)),
+        );
+        let track = reduce_track(&track, TrackEvent::MoveEffect(1, 0));
+        assert_eq!(
+            track.effects,
+            vec![
+                EffectInfo::SchroederReverb(1.0, 0.5),
+                EffectInfo::ToLeftEffect
+            ]
+        );
+
+        let track = reduce_track(
+            &track,
+            TrackEvent::ReplaceEffect(0, EffectInfo::SchroederReverb(0.5, 1.0)),
+        );
+        let track = reduce_track(&track, TrackEvent::RemoveEffect(1));
+        // 範囲外のindexは無視する
+        let track = reduce_track(&track, TrackEvent::RemoveEffect(5));
+        assert_eq!(track.effects, vec![EffectInfo::SchroederReverb(0.5, 1.0)]);
+
+        let track = reduce_track(&track, TrackEvent::ClearEffects);
+        assert!(track.effects.is_empty());
+    }
+
+    #[test]
+    fn test_set_envelope() {
+        let track: Track<PitchNote> = Track::new();
+        let track = reduce_track(&track, TrackEvent::AddEffect(EffectInfo::ToLeftEffect));
+        let envelope = Envelope::new(0.1, 0.2, 0.5, 0.3);
+        let track = reduce_track(&track, TrackEvent::SetEnvelope(envelope));
+        assert_eq!(track.envelope, envelope);
+        // 他のfieldはそのまま
+        assert_eq!(track.effects, vec![EffectInfo::ToLeftEffect]);
+    }
+
+    #[test]
+    fn test_set_glide() {
+        let track: Track<PitchNote> = Track::new();
+        let envelope = Envelope::new(0.1, 0.2, 0.5, 0.3);
+        let track = reduce_track(&track, TrackEvent::SetEnvelope(envelope));
+        let glide = Glide::new(0.05, true);
+        let track = reduce_track(&track, TrackEvent::SetGlide(glide));
+        assert_eq!(track.glide, glide);
+        assert_eq!(track.envelope, envelope);
+    }
+
+    #[test]
+    fn test_set_mod_wheel() {
+        let track: Track<PitchNote> = Track::new();
+        let glide = Glide::new(0.05, false);
+        let track = reduce_track(&track, TrackEvent::SetGlide(glide));
+        let track = reduce_track(&track, TrackEvent::SetModWheel(64));
+        assert_eq!(track.mod_wheel, 64);
+        assert_eq!(track.glide, glide);
+    }
+}
@@ -0,0 +1,73 @@
+use std::f32::consts::PI;
+
+use super::super::super::data::music_info::FilterType;
+
+// cutoffを毎sample変えても安定するstate variable filter
+pub struct StateVariableFilter {
+    ic1eq: f32,
+    ic2eq: f32,
+}
+
+impl StateVariableFilter {
+    pub fn new() -> Self {
+        Self {
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+        }
+    }
+
+    pub fn process(
+        &mut self,
+        input: f32,
+        filter_type: FilterType,
+        cutoff: f32,
+        resonance: f32,
+        sample_rate: f32,
+    ) -> f32 {
+        let cutoff = cutoff.max(20.0).min(sample_rate * 0.49);
+        let g = (PI * cutoff / sample_rate).tan();
+        let k = 2.0 - 2.0 * resonance.clamp(0.0, 0.99);
+
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        let v3 = input - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        match filter_type {
+            FilterType::LowPass => v2,
+            FilterType::HighPass => input - k * v1 - v2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_gain(filter_type: FilterType, hertz: f32) -> f32 {
+        let sample_rate = 44100.0;
+        let mut filter = StateVariableFilter::new();
+        let mut peak: f32 = 0.0;
+        for i in 0..44100 {
+            let input = (2.0 * PI * hertz * i as f32 / sample_rate).sin();
+            let output = filter.process(input, filter_type, 1000.0, 0.0, sample_rate);
+            if i > 22050 {
+                peak = peak.max(output.abs());
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn test_low_pass_high_pass() {
+        assert!(get_gain(FilterType::LowPass, 100.0) > 0.9);
+        assert!(get_gain(FilterType::LowPass, 10000.0) < 0.05);
+        assert!(get_gain(FilterType::HighPass, 100.0) < 0.05);
+        assert!(get_gain(FilterType::HighPass, 10000.0) > 0.9);
+    }
+}
@@ -0,0 +1,147 @@
+use std::f64::consts::PI;
+
+use super::super::super::data::music_info::{Fm, FmAlgorithm};
+
+// operatorごとのphaseと、feedback用の1つ前の出力
+pub struct FmVoice {
+    phases: Vec<f64>,
+    outputs: Vec<f32>,
+    algorithm: FmAlgorithm,
+    modulators: Vec<Vec<usize>>,
+    carriers: Vec<usize>,
+}
+
+impl FmVoice {
+    pub fn new(fm: &Fm) -> Self {
+        let operator_num = fm.operators.len();
+        let (modulators, carriers) = fm.algorithm.get_connections(operator_num);
+        Self {
+            phases: vec![0.0; operator_num],
+            outputs: vec![0.0; operator_num],
+            algorithm: fm.algorithm,
+            modulators,
+            carriers,
+        }
+    }
+
+    // 鳴っている間にoperatorの数やalgorithmが変わったら、残っているoperatorのphaseは引き継いで繋ぎ直す
+    fn sync_operators(&mut self, fm: &Fm) {
+        let operator_num = fm.operators.len();
+        if self.phases.len() == operator_num && self.algorithm == fm.algorithm {
+            return;
+        }
+        self.phases.resize(operator_num, 0.0);
+        self.outputs.resize(operator_num, 0.0);
+        self.algorithm = fm.algorithm;
+        let (modulators, carriers) = fm.algorithm.get_connections(operator_num);
+        self.modulators = modulators;
+        self.carriers = carriers;
+    }
+
+    // secはnote onからの秒数
+    pub fn next_sample(
+        &mut self,
+        fm: &Fm,
+        hertz: f32,
+        sample_rate: f32,
+        sec: f32,
+        note_off_sec: f32,
+    ) -> f32 {
+        self.sync_operators(fm);
+        let last = fm.operators.len().saturating_sub(1);
+        // modulatorは番号が大きいので、後ろから計算する
+        for (i, operator) in fm.operators.iter().enumerate().rev() {
+            let mut modulation: f32 = self.modulators[i]
+                .iter()
+                .map(|&modulator| self.outputs[modulator])
+                .sum();
+            if i == last {
+                modulation += fm.feedback * self.outputs[i];
+            }
+            let envelope = operator.envelope.get_value(sec, note_off_sec);
+            self.outputs[i] = operator.index
+                * envelope
+                * (2.0 * PI * self.phases[i] + modulation as f64).sin() as f32;
+
+            self.phases[i] =
+                (self.phases[i] + (hertz * operator.ratio / sample_rate) as f64).fract();
+        }
+
+        if self.carriers.is_empty() {
+            return 0.0;
+        }
+        let sample: f32 = self.carriers.iter().map(|&i| self.outputs[i]).sum();
+        sample / self.carriers.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::super::data::music_info::{Envelope, FmAlgorithm, FmOperator};
+    use super::*;
+
+    fn render(fm: &Fm) -> Vec<f32> {
+        let mut voice = FmVoice::new(fm);
+        (0..4410)
+            .map(|i| voice.next_sample(fm, 440.0, 44100.0, i as f32 / 44100.0, 1.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_modulation_index() {
+        let envelope = Envelope::new(0.0, 0.0, 1.0, 0.0);
+        let mut fm = Fm {
+            operators: vec![
+                FmOperator::new(1.0, 1.0, envelope),
+                FmOperator::new(2.0, 0.0, envelope),
+            ],
+            algorithm: FmAlgorithm::Stack,
+            feedback: 0.0,
+        };
+
+        // indexが0ならただのsin
+        for (i, sample) in render(&fm).iter().enumerate() {
+            let expected = (2.0 * PI * 440.0 * i as f64 / 44100.0).sin() as f32;
+            assert!((sample - expected).abs() < 1e-3);
+        }
+
+        // 変調すると倍音が増えるので、隣り合うsampleの差が大きくなる
+        let roughness =
+            |wave: Vec<f32>| -> f32 { wave.windows(2).map(|w| (w[1] - w[0]).abs()).sum() };
+        let pure = roughness(render(&fm));
+        fm.operators[1].index = 5.0;
+        let modulated = roughness(render(&fm));
+        assert!(modulated > pure * 1.5);
+    }
+
+    #[test]
+    fn test_change_operators_while_playing() {
+        let envelope = Envelope::new(0.0, 0.0, 1.0, 0.0);
+        let two_operators = Fm {
+            operators: vec![
+                FmOperator::new(1.0, 1.0, envelope),
+                FmOperator::new(2.0, 1.0, envelope),
+            ],
+            algorithm: FmAlgorithm::Stack,
+            feedback: 0.5,
+        };
+        let mut four_operators = Fm::electric_piano();
+        four_operators.feedback = 0.5;
+
+        // 鳴っている間にoperatorを増やしたり減らしたりしても、音は途切れない
+        let mut voice = FmVoice::new(&two_operators);
+        let mut wave = vec![];
+        for (i, fm) in [&two_operators, &four_operators, &two_operators]
+            .iter()
+            .enumerate()
+        {
+            for j in 0..441 {
+                let sec = (i * 441 + j) as f32 / 44100.0;
+                wave.push(voice.next_sample(fm, 440.0, 44100.0, sec, 1.0));
+            }
+        }
+        assert!(wave.iter().all(|sample| sample.is_finite()));
+        assert!(wave[441..882].iter().any(|&sample| sample != 0.0));
+        assert!(wave[882..].iter().any(|&sample| sample != 0.0));
+    }
+}
@@ -0,0 +1,142 @@
+pub(crate) mod filter;
+mod fm;
+pub mod oscillator;
+
+use std::f32::consts::PI;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use super::super::data::music_info::{Synth, Waveform};
+use filter::StateVariableFilter;
+
+pub use fm::FmVoice;
+
+// 1つのnoteを鳴らす間の、oscillatorのphaseやfilterの状態
+pub struct SynthVoice {
+    phases: Vec<Vec<f32>>,
+    filter: StateVariableFilter,
+    rng: StdRng,
+}
+
+impl SynthVoice {
+    pub fn new(synth: &Synth, seed: u64) -> Self {
+        // unisonが同じphaseから始まるとうなりが揃うので、ずらしておく
+        let phases = synth
+            .oscillators
+            .iter()
+            .map(|oscillator| {
+                let unison = oscillator.unison.max(1);
+                (0..unison).map(|i| i as f32 / unison as f32).collect()
+            })
+            .collect();
+        Self {
+            phases,
+            filter: StateVariableFilter::new(),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // secはnote onからの秒数。音量のenvelopeは呼ぶ側でかける
+    pub fn next_sample(
+        &mut self,
+        synth: &Synth,
+        hertz: f32,
+        sample_rate: f32,
+        sec: f32,
+        note_off_sec: f32,
+    ) -> f32 {
+        let lfo = (2.0 * PI * synth.lfo.rate * sec).sin();
+
+        let mut sample = 0.0;
+        for (oscillator, phases) in synth.oscillators.iter().zip(self.phases.iter_mut()) {
+            let unison = phases.len();
+            let mut oscillator_sample = 0.0;
+            for (i, phase) in phases.iter_mut().enumerate() {
+                let spread = if unison > 1 {
+                    oscillator.unison_detune * (i as f32 / (unison - 1) as f32 - 0.5)
+                } else {
+                    0.0
+                };
+                let cent = oscillator.detune + spread + lfo * synth.lfo.pitch_depth;
+                let dt = (hertz * 2.0_f32.powf(cent / 1200.0) / sample_rate).min(0.5);
+
+                oscillator_sample += match oscillator.waveform {
+                    Waveform::Saw => oscillator::saw(*phase, dt),
+                    Waveform::Square => oscillator::pulse(*phase, dt, 0.5),
+                    Waveform::Pulse(width) => oscillator::pulse(*phase, dt, width),
+                    Waveform::Noise => self.rng.gen_range(-1.0, 1.0),
+                };
+                *phase = (*phase + dt) % 1.0;
+            }
+            sample += oscillator.level * oscillator_sample / unison as f32;
+        }
+
+        let filter_envelope = synth.filter.envelope.get_value(sec, note_off_sec);
+        let cutoff = synth.filter.cutoff
+            * 2.0_f32.powf(
+                synth.filter.envelope_amount * filter_envelope + lfo * synth.lfo.cutoff_depth,
+            );
+        let sample = self.filter.process(
+            sample,
+            synth.filter.filter_type,
+            cutoff,
+            synth.filter.resonance,
+            sample_rate,
+        );
+
+        let amp = 1.0 - synth.lfo.amp_depth * (1.0 - lfo) / 2.0;
+        sample * amp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::data::music_info::{Envelope, FilterType, SynthOscillator};
+    use super::*;
+
+    // 隣り合うsampleの差の大きさで、高い周波数成分の量を比べる
+    fn get_roughness(synth: &Synth) -> f32 {
+        let mut voice = SynthVoice::new(synth, 0);
+        let samples: Vec<f32> = (0..4410)
+            .map(|i| voice.next_sample(synth, 220.0, 44100.0, i as f32 / 44100.0, 1.0))
+            .collect();
+        samples.windows(2).map(|w| (w[1] - w[0]).abs()).sum()
+    }
+
+    #[test]
+    fn test_filter_cutoff() {
+        let mut synth = Synth {
+            oscillators: vec![SynthOscillator {
+                unison: 3,
+                unison_detune: 20.0,
+                ..SynthOscillator::new(Waveform::Square)
+            }],
+            ..Synth::default()
+        };
+        synth.filter.envelope = Envelope::new(0.0, 0.0, 0.0, 0.0);
+        synth.filter.filter_type = FilterType::LowPass;
+
+        synth.filter.cutoff = 300.0;
+        let dark = get_roughness(&synth);
+        synth.filter.cutoff = 8000.0;
+        let bright = get_roughness(&synth);
+        assert!(dark * 2.0 < bright);
+    }
+
+    #[test]
+    fn test_noise_is_reproducible() {
+        let synth = Synth {
+            oscillators: vec![SynthOscillator::new(Waveform::Noise)],
+            ..Synth::default()
+        };
+        let mut voice1 = SynthVoice::new(&synth, 1);
+        let mut voice2 = SynthVoice::new(&synth, 1);
+        for i in 0..100 {
+            let sec = i as f32 / 44100.0;
+            let sample = voice1.next_sample(&synth, 440.0, 44100.0, sec, 1.0);
+            assert_eq!(sample, voice2.next_sample(&synth, 440.0, 44100.0, sec, 1.0));
+            assert!(sample.abs() <= 2.0);
+        }
+    }
+}
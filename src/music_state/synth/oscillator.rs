@@ -0,0 +1,128 @@
+use std::f32::consts::PI;
+
+// phaseは0.0 ~ 1.0、dtは1sampleで進むphase
+
+// 不連続点の前後1sampleを滑らかにして、折り返しを減らす
+pub fn poly_blep(phase: f32, dt: f32) -> f32 {
+    if phase < dt {
+        let t = phase / dt;
+        t + t - t * t - 1.0
+    } else if phase > 1.0 - dt {
+        let t = (phase - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+// 傾きが変わる点の前後を滑らかにする。poly_blepを積分したもの
+pub fn poly_blamp(phase: f32, dt: f32) -> f32 {
+    if phase < dt {
+        let t = phase / dt - 1.0;
+        -t * t * t / 3.0
+    } else if phase > 1.0 - dt {
+        let t = (phase - 1.0) / dt + 1.0;
+        t * t * t / 3.0
+    } else {
+        0.0
+    }
+}
+
+pub fn saw(phase: f32, dt: f32) -> f32 {
+    2.0 * phase - 1.0 - poly_blep(phase, dt)
+}
+
+pub fn pulse(phase: f32, dt: f32, width: f32) -> f32 {
+    let width = width.max(dt).min(1.0 - dt);
+    let naive = if phase < width { 1.0 } else { -1.0 };
+    naive + poly_blep(phase, dt) - poly_blep((phase - width + 1.0) % 1.0, dt)
+}
+
+// sinと同じく0から上がり始める
+pub fn tri(phase: f32, dt: f32) -> f32 {
+    let peak_phase = (phase + 0.75) % 1.0;
+    let trough_phase = (phase + 0.25) % 1.0;
+    let naive = 4.0 * (peak_phase - 0.5).abs() - 1.0;
+    naive + 8.0 * dt * (poly_blamp(trough_phase, dt) - poly_blamp(peak_phase, dt))
+}
+
+// 以前の、帯域制限をしない波形。xはradian
+pub fn lofi_tri(x: f32) -> f32 {
+    let x = (x - 0.5 * PI).rem_euclid(2.0 * PI);
+    (x - PI).abs() / PI * 2.0 - 1.0
+}
+
+pub fn lofi_saw(x: f32) -> f32 {
+    let x = x.rem_euclid(2.0 * PI);
+    x / PI - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waveform_range() {
+        let dt = 440.0 / 44100.0;
+        let mut phase = 0.0;
+        for _ in 0..1000 {
+            assert!(saw(phase, dt).abs() <= 1.0 + 1e-5);
+            assert!(pulse(phase, dt, 0.3).abs() <= 1.0 + 1e-5);
+            phase = (phase + dt) % 1.0;
+        }
+        assert_eq!(pulse(0.2, dt, 0.5), 1.0);
+        assert_eq!(pulse(0.7, dt, 0.5), -1.0);
+        assert!((tri(0.25, dt) - 1.0).abs() < 0.05);
+        assert!((tri(0.75, dt) + 1.0).abs() < 0.05);
+        assert!(tri(0.1, dt) > 0.0 && tri(0.6, dt) < 0.0);
+    }
+
+    // 基本周波数の倍数でない周波数の成分は、Nyquistで折り返した倍音
+    fn get_aliasing_ratio(wave_func: impl Fn(f32, f32) -> f32) -> f64 {
+        let sample_rate = 44100.0;
+        let hertz = 3520.0;
+        let sample_num = 2205; // 1binは20Hz
+        let harmonic_bin = (hertz / 20.0) as usize;
+        let dt = hertz / sample_rate;
+        let wave: Vec<f64> = (0..sample_num)
+            .map(|i| {
+                let phase = (i as f64 * dt as f64).fract() as f32;
+                wave_func(phase, dt) as f64
+            })
+            .collect();
+
+        let mut harmonic_energy = 0.0;
+        let mut aliasing_energy = 0.0;
+        for bin in 1..sample_num / 2 {
+            let (mut re, mut im) = (0.0, 0.0);
+            for (i, x) in wave.iter().enumerate() {
+                let theta =
+                    2.0 * std::f64::consts::PI * (bin * i % sample_num) as f64 / sample_num as f64;
+                re += x * theta.cos();
+                im -= x * theta.sin();
+            }
+            let energy = re * re + im * im;
+            if bin % harmonic_bin == 0 {
+                harmonic_energy += energy;
+            } else {
+                aliasing_energy += energy;
+            }
+        }
+        aliasing_energy / harmonic_energy
+    }
+
+    #[test]
+    fn test_aliasing() {
+        let lofi_saw_ratio = get_aliasing_ratio(|phase, _| lofi_saw(phase * 2.0 * PI));
+        let saw_ratio = get_aliasing_ratio(saw);
+        let square_ratio = get_aliasing_ratio(|phase, dt| pulse(phase, dt, 0.5));
+        let lofi_tri_ratio = get_aliasing_ratio(|phase, _| lofi_tri(phase * 2.0 * PI));
+        let tri_ratio = get_aliasing_ratio(tri);
+        assert!(saw_ratio * 10.0 < lofi_saw_ratio);
+        assert!(saw_ratio < 0.01);
+        assert!(square_ratio < 0.01);
+        // 三角波は倍音が早く減るので、もともと折り返しが少ない
+        assert!(tri_ratio * 2.0 < lofi_tri_ratio);
+        assert!(tri_ratio < 0.001);
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use super::super::data::music_info::Beat;
+use super::states::SchedulingState;
+
+// WaveReaderの累積sample数とbeatを対応づける。
+// 再生中にbpm_scheduleが変わっても位置が飛ばないように、anchorの位置を基準にして変換する
+pub struct Timeline {
+    scheduling: Arc<SchedulingState>,
+    anchor_samples: u64,
+    anchor_sec: f64,
+    sample_rate: f64,
+}
+
+impl Timeline {
+    pub fn new(
+        scheduling: Arc<SchedulingState>,
+        anchor_samples: u64,
+        anchor_beats: Beat,
+        sample_rate: f32,
+    ) -> Self {
+        let anchor_sec = scheduling.beat_to_sec(anchor_beats);
+        Self {
+            scheduling,
+            anchor_samples,
+            anchor_sec,
+            sample_rate: sample_rate as f64,
+        }
+    }
+
+    pub fn get_scheduling(&self) -> Arc<SchedulingState> {
+        Arc::clone(&self.scheduling)
+    }
+
+    pub fn beat_to_samples(&self, beat: Beat) -> u64 {
+        let samples = self.anchor_samples as f64
+            + (self.scheduling.beat_to_sec(beat) - self.anchor_sec) * self.sample_rate;
+        samples.round().max(0.0) as u64
+    }
+
+    // そのsample以降で最初のtickに切り上げる。
+    // こうすると[samples_to_beat(a), samples_to_beat(b))に入るnoteは[a, b)のsampleで鳴り始める
+    pub fn samples_to_beat(&self, samples: u64) -> Beat {
+        let sec =
+            self.anchor_sec + (samples as f64 - self.anchor_samples as f64) / self.sample_rate;
+        let beat = self.scheduling.sec_to_beat(sec);
+        let beat_length = Beat::from(1).get_num() as f64;
+        Beat::from_num((beat * beat_length - 1e-6).ceil() as i64)
+    }
+
+    pub fn get_bpm(&self, beat: Beat) -> f32 {
+        self.scheduling.get_bpm(beat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::state_management::state::State;
+    use super::super::states::SchedulingStateEvent;
+    use super::*;
+
+    #[test]
+    fn test_beat_to_samples() {
+        let scheduling =
+            SchedulingState::new().reduce(SchedulingStateEvent::ChangeBPM(Beat::from(1), 60.0));
+        let timeline = Timeline::new(Arc::new(scheduling), 0, Beat::from(0), 44100.0);
+
+        // バッファの境界ではない位置でのbpm変化もsample単位で反映される
+        assert_eq!(timeline.beat_to_samples(Beat::from(1)), 22050);
+        assert_eq!(timeline.beat_to_samples(Beat::from(2)), 66150);
+        assert_eq!(timeline.samples_to_beat(66150), Beat::from(2));
+        assert_eq!(timeline.samples_to_beat(66149), Beat::from(2));
+        assert!(timeline.samples_to_beat(66100) < Beat::from(2));
+
+        // 途中の位置を基準にしても同じ対応になる
+        let timeline = Timeline::new(timeline.get_scheduling(), 22050, Beat::from(1), 44100.0);
+        assert_eq!(timeline.beat_to_samples(Beat::from(2)), 66150);
+    }
+}
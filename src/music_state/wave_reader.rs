@@ -1,55 +1,345 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, VecDeque};
 use std::iter::FromIterator;
-use std::ops::Bound::{Included, Unbounded};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
 use std::sync::Arc;
+use std::thread;
 
 use log::error;
 use serde::{Deserialize, Serialize};
 
 use super::super::data::music_info::Beat;
+use super::super::error::ToidError;
 use super::super::resource_management::resource_manager::ResourceManager;
 use super::super::state_management::serialize;
+use super::super::state_management::state::State;
 use super::super::state_management::store::Store;
 use super::super::state_management::store_reader::StoreReader;
-use super::effects::{Effect, EffectInfo};
+use super::effects::EffectChain;
 use super::pitch_track_player::PitchTrackPlayer;
+use super::quantize::Quantize;
+use super::render_config::RenderConfig;
 use super::sample_track_player::SampleTrackPlayer;
-use super::states::{MusicState, MusicStateEvent};
+use super::states::{MusicState, MusicStateEvent, SchedulingState, SectionState};
+use super::timeline::Timeline;
+
+// Sectionはbeatを含むsection全体をloopする。最後のsectionは終わりがないのでloopしない
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum LoopRegion {
+    Beats(Beat, Beat),
+    Section(Beat),
+}
+
+// 区切りはread時のschedulingで、queueした位置から求める。
+// storeに送る分は、audio threadで作らないようにqueueするときにserializeしておく
+struct QueuedEvent {
+    cum_queued_beats: Beat,
+    quantize: Quantize,
+    serialized_event: String,
+}
+
+impl QueuedEvent {
+    fn get_apply_beats(&self, scheduling: &SchedulingState) -> Beat {
+        self.quantize
+            .next_boundary(scheduling, self.cum_queued_beats)
+    }
+}
+
+const COMMIT_CHANNEL_CAPACITY: usize = 64;
+
+type CommitMessage = (Arc<Store<MusicState, MusicStateEvent>>, String);
+
+// 区切りで反映したeventを、audio threadの外のthreadからstoreに送る。
+// channelがいっぱいのときは、次のreadで送り直す
+struct EventCommitter {
+    sender: SyncSender<CommitMessage>,
+    unsent_messages: VecDeque<CommitMessage>,
+    sent_num: usize,
+    committed_num: Arc<AtomicUsize>,
+}
+
+impl EventCommitter {
+    fn new() -> Self {
+        let (sender, receiver) = sync_channel::<CommitMessage>(COMMIT_CHANNEL_CAPACITY);
+        let committed_num = Arc::new(AtomicUsize::new(0));
+        {
+            let committed_num = Arc::clone(&committed_num);
+            thread::spawn(move || {
+                for (store, serialized_event) in receiver.iter() {
+                    if let Err(e) =
+                        <MusicStateEvent as serialize::Serialize<MusicStateEvent>>::deserialize(
+                            serialized_event,
+                        )
+                        .and_then(|event| store.update_state(event))
+                    {
+                        error!("update_state Error {}", e);
+                    }
+                    committed_num.fetch_add(1, Ordering::Release);
+                }
+            });
+        }
+        Self {
+            sender,
+            unsent_messages: VecDeque::new(),
+            sent_num: 0,
+            committed_num,
+        }
+    }
+
+    fn commit(
+        &mut self,
+        store: &Arc<Store<MusicState, MusicStateEvent>>,
+        serialized_event: String,
+    ) {
+        self.unsent_messages
+            .push_back((Arc::clone(store), serialized_event));
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        while let Some(message) = self.unsent_messages.pop_front() {
+            match self.sender.try_send(message) {
+                Ok(()) => self.sent_num += 1,
+                Err(TrySendError::Full(message)) => {
+                    self.unsent_messages.push_front(message);
+                    break;
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    error!("event committer is disconnected");
+                    self.sent_num += 1;
+                    self.committed_num.fetch_add(1, Ordering::Release);
+                }
+            }
+        }
+    }
+
+    // trueなら、送ったeventはすべてstoreのstateに入っている
+    fn is_committed(&self) -> bool {
+        self.unsent_messages.is_empty()
+            && self.committed_num.load(Ordering::Acquire) == self.sent_num
+    }
+}
 
 pub struct WaveReader {
     wave_length: u64,
+    render_config: RenderConfig,
     cum_current_samples: u64,
-    current_bpm: f32,
-    bpm_change_samples: u64,
-    bpm_change_beats: Beat,
+    timeline: Timeline,
     cum_current_beats: Beat,
     pitch_track_players: HashMap<String, PitchTrackPlayer>,
     sample_track_players: HashMap<String, SampleTrackPlayer>,
-    effect_infos: Vec<EffectInfo>,
-    effects: Vec<Box<dyn Effect + Sync + Send>>,
+    effect_chain: EffectChain,
+    playing: bool,
+    chase_notes: bool,
+    loop_region: Option<LoopRegion>,
+    queued_events: Vec<QueuedEvent>,
+    event_committer: EventCommitter,
+    // 区切りで送ったeventがstoreに反映されるまでは、この位置からnoteを登録しない
+    schedule_after_commit: Option<Beat>,
+    block_size_fixed: bool,
 }
 
 impl WaveReader {
     pub fn get_current_beats(&self) -> Beat {
         self.cum_current_beats
     }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn get_loop_region(&self) -> Option<LoopRegion> {
+        self.loop_region
+    }
+
+    // 鳴っているnoteは作り直したtrack playerに、次のreadで途中から登録し直す
+    fn seek(&mut self, beat: Beat) {
+        let scheduling = self.timeline.get_scheduling();
+        self.cum_current_samples = (scheduling.beat_to_sec(beat)
+            * self.render_config.sample_rate as f64)
+            .round()
+            .max(0.0) as u64;
+        self.cum_current_beats = beat;
+        self.timeline = Timeline::new(
+            scheduling,
+            self.cum_current_samples,
+            beat,
+            self.render_config.sample_rate,
+        );
+        self.pitch_track_players = HashMap::new();
+        self.sample_track_players = HashMap::new();
+        self.chase_notes = true;
+        self.schedule_after_commit = None;
+        // queueされたeventの区切りは、seek先から数え直す
+        for queued_event in self.queued_events.iter_mut() {
+            queued_event.cum_queued_beats = beat;
+        }
+    }
+
+    // sectionのloopは、sectionの位置が変わっても追従するように毎回解決する
+    fn resolve_loop_region(&self, music_state: &MusicState) -> Option<(Beat, Beat)> {
+        match self.loop_region? {
+            LoopRegion::Beats(start, end) => Some((start, end)),
+            LoopRegion::Section(beat) => {
+                let (start, end) = music_state.get_section_range(beat);
+                end.map(|end| (start, end))
+            }
+        }
+    }
+
+    fn schedule_notes(
+        &mut self,
+        section_state: &SectionState,
+        cum_start_beats: &Beat,
+        cum_end_beats: &Beat,
+        resource_manager: Arc<ResourceManager>,
+    ) {
+        // 反映を待っている間も、playできるようにplayerは作っておく
+        let waiting_commit = self.schedule_after_commit.is_some();
+        let render_config = self.render_config;
+        for (key, track) in section_state.pitch_track_map.iter() {
+            let player = self
+                .pitch_track_players
+                .entry(key.clone())
+                .or_insert_with(|| PitchTrackPlayer::new(render_config));
+            if waiting_commit {
+                continue;
+            }
+            if self.chase_notes {
+                player.chase_notes(
+                    track,
+                    &self.timeline,
+                    cum_start_beats,
+                    Arc::clone(&resource_manager),
+                );
+            }
+            player.schedule_notes(
+                track,
+                &self.timeline,
+                cum_start_beats,
+                cum_end_beats,
+                Arc::clone(&resource_manager),
+            );
+        }
+        for (key, track) in section_state.sample_track_map.iter() {
+            let player = self
+                .sample_track_players
+                .entry(key.clone())
+                .or_insert_with(|| SampleTrackPlayer::new(render_config));
+            if waiting_commit {
+                continue;
+            }
+            if self.chase_notes {
+                player.chase_notes(track, &self.timeline, cum_start_beats);
+            }
+            player.schedule_notes(track, &self.timeline, cum_start_beats, cum_end_beats);
+        }
+        if !waiting_commit {
+            self.chase_notes = false;
+        }
+    }
+
+    fn release_notes(&mut self, cum_release_samples: u64) {
+        for player in self.pitch_track_players.values_mut() {
+            player.release_notes(cum_release_samples);
+        }
+        for player in self.sample_track_players.values_mut() {
+            player.release_notes(cum_release_samples);
+        }
+    }
+
+    fn get_next_apply_beats(&self, scheduling: &SchedulingState) -> Option<Beat> {
+        self.queued_events
+            .iter()
+            .map(|queued_event| queued_event.get_apply_beats(scheduling))
+            .min()
+    }
+
+    // cum_beatsまでに区切りが来るeventを、queueした順にEventCommitterからstoreに送る。
+    // Noneならすべて送る。reduceはstore側で行い、readは反映されたstateを次から使う
+    fn apply_queued_events(
+        &mut self,
+        store: &Arc<Store<MusicState, MusicStateEvent>>,
+        scheduling: &SchedulingState,
+        cum_beats: Option<Beat>,
+        cum_schedule_beats: Beat,
+    ) {
+        let queued_events = std::mem::take(&mut self.queued_events);
+        for queued_event in queued_events.into_iter() {
+            let apply = match cum_beats {
+                Some(cum_beats) => queued_event.get_apply_beats(scheduling) <= cum_beats,
+                None => true,
+            };
+            if apply {
+                self.event_committer
+                    .commit(store, queued_event.serialized_event);
+                if self.schedule_after_commit.is_none() {
+                    self.schedule_after_commit = Some(cum_schedule_beats);
+                }
+            } else {
+                self.queued_events.push(queued_event);
+            }
+        }
+    }
+
+    pub fn get_render_config(&self) -> RenderConfig {
+        self.render_config
+    }
+
+    // 鳴っているnoteやEffectの状態はsample rateに依存するので、作り直す。
+    // 出力側はblock_size分のbufferを確保しているので、readを始めた後は変えられない
+    fn set_render_config(&mut self, render_config: RenderConfig) -> Result<(), ToidError> {
+        if self.render_config == render_config {
+            return Ok(());
+        }
+        render_config.validate()?;
+        if self.block_size_fixed && self.render_config.block_size != render_config.block_size {
+            return Err(ToidError::OutOfRange(format!(
+                "block size cannot be changed while reading : {}",
+                render_config.block_size
+            )));
+        }
+        self.render_config = render_config;
+        self.wave_length = render_config.block_size as u64;
+        self.timeline = Timeline::new(
+            self.timeline.get_scheduling(),
+            self.cum_current_samples,
+            self.cum_current_beats,
+            render_config.sample_rate,
+        );
+        self.pitch_track_players = HashMap::new();
+        self.sample_track_players = HashMap::new();
+        self.effect_chain = EffectChain::new(render_config);
+        Ok(())
+    }
 }
 
 impl StoreReader<(Vec<i16>, Vec<i16>), WaveReaderEvent, MusicState, MusicStateEvent>
     for WaveReader
 {
     fn new() -> Self {
+        let render_config = RenderConfig::default();
         WaveReader {
-            wave_length: 512,
+            wave_length: render_config.block_size as u64,
+            render_config,
             cum_current_samples: 0,
-            current_bpm: 120.0,
-            bpm_change_samples: 0,
-            bpm_change_beats: Beat::from(0),
+            timeline: Timeline::new(
+                Arc::new(SchedulingState::new()),
+                0,
+                Beat::from(0),
+                render_config.sample_rate,
+            ),
             cum_current_beats: Beat::from(0),
             pitch_track_players: HashMap::new(),
             sample_track_players: HashMap::new(),
-            effect_infos: vec![],
-            effects: vec![],
+            effect_chain: EffectChain::new(render_config),
+            playing: true,
+            chase_notes: false,
+            loop_region: None,
+            queued_events: Vec::new(),
+            event_committer: EventCommitter::new(),
+            schedule_after_commit: None,
+            block_size_fixed: false,
         }
     }
 
@@ -58,11 +348,23 @@ impl StoreReader<(Vec<i16>, Vec<i16>), WaveReaderEvent, MusicState, MusicStateEv
         store: Arc<Store<MusicState, MusicStateEvent>>,
         resource_manager: Arc<ResourceManager>,
     ) -> (Vec<i16>, Vec<i16>) {
+        self.block_size_fixed = true;
+        // 一時停止中は位置を進めずに無音を返す
+        if !self.playing {
+            return (
+                vec![0; self.wave_length as usize],
+                vec![0; self.wave_length as usize],
+            );
+        }
+
         let mut left_wave: Vec<f32> = Vec::new();
         left_wave.resize(self.wave_length as usize, 0.0);
         let mut right_wave: Vec<f32> = Vec::new();
         right_wave.resize(self.wave_length as usize, 0.0);
 
+        // 送ったeventがすべて入っていれば、この後に読むstateにも入っている
+        self.event_committer.flush();
+        let committed = self.event_committer.is_committed();
         let music_state = match store.get_state() {
             Ok(music_state) => music_state,
             Err(e) => {
@@ -90,126 +392,204 @@ impl StoreReader<(Vec<i16>, Vec<i16>), WaveReaderEvent, MusicState, MusicStateEv
                 return (left_wave, right_wave);
             }
         };
-        let scheduling_state = &music_state.scheduling;
+        // bpm_scheduleが変わったら、現在の位置を基準にしてbeatとの対応を取り直す
+        if !Arc::ptr_eq(&music_state.scheduling, &self.timeline.get_scheduling()) {
+            self.timeline = Timeline::new(
+                Arc::clone(&music_state.scheduling),
+                self.cum_current_samples,
+                self.cum_current_beats,
+                self.render_config.sample_rate,
+            );
+        }
 
         let cum_next_samples = self.cum_current_samples + self.wave_length;
+        let loop_region = self.resolve_loop_region(&music_state);
+        let mut section_states = Vec::new();
 
-        if let Some((&_, &new_bpm)) = scheduling_state
-            .bpm_schedule
-            .range((Unbounded, Included(self.cum_current_beats)))
-            .rev()
-            .next()
-        {
-            if new_bpm != self.current_bpm {
-                self.current_bpm = new_bpm;
-                self.bpm_change_samples = self.cum_current_samples;
-                self.bpm_change_beats = self.cum_current_beats;
-            }
+        // 区切りからstoreに反映されるまでの間に始まるnoteは、反映されたstateで途中から鳴らす
+        if let (Some(cum_schedule_beats), true) = (self.schedule_after_commit, committed) {
+            self.schedule_after_commit = None;
+            let cum_current_beats = self.cum_current_beats;
+            let section_state = music_state.get_section_state_by_beat(
+                (cum_current_beats - Beat::from_num(1)).max(cum_schedule_beats),
+            );
+            self.schedule_notes(
+                &section_state,
+                &cum_schedule_beats,
+                &cum_current_beats,
+                Arc::clone(&resource_manager),
+            );
+            section_states.push(section_state);
         }
-        let cum_next_beats = self.cum_current_beats
-            + Beat::from(self.wave_length as f32 * self.current_bpm / 44100.0 / 60.0);
 
-        // track
-        {
-            let state_track_keys: HashSet<String> = HashSet::from_iter(
-                music_state
-                    .get_section_state_by_beat(cum_next_beats)
-                    .pitch_track_map
-                    .keys()
-                    .cloned(),
-            );
-            let track_player_keys: HashSet<String> =
-                HashSet::from_iter(self.pitch_track_players.keys().cloned());
+        // queueされたeventの区切りや、loop regionの終わりをまたぐときは、
+        // その位置で区切ってnoteを登録する。
+        // loopの終わりでは鳴っているnoteを止めてからloopの始めに戻る
+        let mut cum_segment_start_samples = self.cum_current_samples;
+        let mut cum_segment_start_beats = self.cum_current_beats;
+        let mut wrapped = false;
+        let cum_next_beats = loop {
+            let cum_segment_end_beats = self.timeline.samples_to_beat(cum_next_samples);
+            let wrap = match loop_region {
+                Some((loop_start, loop_end)) if cum_segment_end_beats > loop_end => {
+                    let cum_wrap_samples = self
+                        .timeline
+                        .beat_to_samples(loop_end)
+                        .max(cum_segment_start_samples);
+                    // loopが1sampleより短いときは、同じ位置で戻り続けないようにする
+                    if wrapped && cum_wrap_samples == cum_segment_start_samples {
+                        None
+                    } else {
+                        Some((loop_start, loop_end, cum_wrap_samples))
+                    }
+                }
+                _ => None,
+            };
+            let apply_beats = self
+                .get_next_apply_beats(&music_state.scheduling)
+                .filter(|&apply_beats| apply_beats < cum_segment_end_beats)
+                .filter(|&apply_beats| match wrap {
+                    Some((_, loop_end, _)) => apply_beats <= loop_end,
+                    None => true,
+                });
+
+            if let Some(apply_beats) = apply_beats {
+                let section_state = music_state.get_section_state_by_beat(
+                    (apply_beats - Beat::from_num(1)).max(cum_segment_start_beats),
+                );
+                self.schedule_notes(
+                    &section_state,
+                    &cum_segment_start_beats,
+                    &apply_beats,
+                    Arc::clone(&resource_manager),
+                );
+                section_states.push(section_state);
+
+                let cum_apply_samples = self
+                    .timeline
+                    .beat_to_samples(apply_beats)
+                    .max(cum_segment_start_samples);
+                self.apply_queued_events(
+                    &store,
+                    &music_state.scheduling,
+                    Some(apply_beats),
+                    apply_beats,
+                );
+                cum_segment_start_samples = cum_apply_samples;
+                cum_segment_start_beats = apply_beats;
+                continue;
+            }
 
-            for key in track_player_keys.difference(&state_track_keys) {
-                self.pitch_track_players.remove(key);
+            match wrap {
+                Some((loop_start, loop_end, cum_wrap_samples)) => {
+                    let section_state = music_state.get_section_state_by_beat(
+                        (loop_end - Beat::from_num(1)).max(cum_segment_start_beats),
+                    );
+                    self.schedule_notes(
+                        &section_state,
+                        &cum_segment_start_beats,
+                        &loop_end,
+                        Arc::clone(&resource_manager),
+                    );
+                    section_states.push(section_state);
+
+                    self.release_notes(cum_wrap_samples);
+                    // loopの中で区切りが来ないeventは、loopの終わりで反映する
+                    self.apply_queued_events(&store, &music_state.scheduling, None, loop_start);
+                    // 反映を待っている間にloopしたら、loopの始めから登録する
+                    if let Some(cum_schedule_beats) = self.schedule_after_commit.as_mut() {
+                        *cum_schedule_beats = loop_start;
+                    }
+                    self.timeline = Timeline::new(
+                        Arc::clone(&music_state.scheduling),
+                        cum_wrap_samples,
+                        loop_start,
+                        self.render_config.sample_rate,
+                    );
+                    cum_segment_start_samples = cum_wrap_samples;
+                    cum_segment_start_beats = loop_start;
+                    wrapped = true;
+                }
+                None => {
+                    let section_state = music_state.get_section_state_by_beat(
+                        (cum_segment_end_beats - Beat::from_num(1)).max(cum_segment_start_beats),
+                    );
+                    self.schedule_notes(
+                        &section_state,
+                        &cum_segment_start_beats,
+                        &cum_segment_end_beats,
+                        Arc::clone(&resource_manager),
+                    );
+                    section_states.push(section_state);
+                    break cum_segment_end_beats;
+                }
             }
-            for key in state_track_keys.difference(&track_player_keys) {
-                self.pitch_track_players
-                    .insert(key.clone(), PitchTrackPlayer::new());
+        };
+
+        // track
+        {
+            // 後の区間のsectionのtrackを優先する
+            let mut tracks = HashMap::new();
+            for section_state in section_states.iter() {
+                for (key, track) in section_state.pitch_track_map.iter() {
+                    tracks.insert(key, (track, section_state.is_pitch_track_audible(key)));
+                }
             }
-            for (key, track) in music_state
-                .get_section_state_by_beat(cum_next_beats)
-                .pitch_track_map
-                .iter()
-            {
+            self.pitch_track_players
+                .retain(|key, _| tracks.contains_key(key));
+
+            for (key, (track, audible)) in tracks.iter() {
+                // muteされたtrackも再生位置を進めるためにplayは呼び、出力だけ捨てる
                 let (left_wave_of_track, right_wave_of_track) =
-                    self.pitch_track_players.get_mut(key).unwrap().play(
-                        &track,
+                    self.pitch_track_players.get_mut(*key).unwrap().play(
+                        track,
                         Arc::clone(&resource_manager),
                         &self.cum_current_samples,
-                        &self.cum_current_beats,
-                        &self.current_bpm,
                     );
+                if !audible {
+                    continue;
+                }
                 for i in 0..self.wave_length as usize {
-                    left_wave[i] = left_wave[i] + left_wave_of_track[i];
-                    right_wave[i] = right_wave[i] + right_wave_of_track[i];
+                    left_wave[i] += left_wave_of_track[i];
+                    right_wave[i] += right_wave_of_track[i];
                 }
             }
         }
 
         // sample track
         {
-            let state_track_keys: HashSet<String> = HashSet::from_iter(
-                music_state
-                    .get_section_state_by_beat(cum_next_beats)
-                    .sample_track_map
-                    .keys()
-                    .cloned(),
-            );
-            let track_player_keys: HashSet<String> =
-                HashSet::from_iter(self.sample_track_players.keys().cloned());
-
-            for key in track_player_keys.difference(&state_track_keys) {
-                self.sample_track_players.remove(key);
-            }
-            for key in state_track_keys.difference(&track_player_keys) {
-                self.sample_track_players
-                    .insert(key.clone(), SampleTrackPlayer::new());
+            let mut tracks = HashMap::new();
+            for section_state in section_states.iter() {
+                for (key, track) in section_state.sample_track_map.iter() {
+                    tracks.insert(key, (track, section_state.is_sample_track_audible(key)));
+                }
             }
-            for (key, track) in music_state
-                .get_section_state_by_beat(cum_next_beats)
-                .sample_track_map
-                .iter()
-            {
+            self.sample_track_players
+                .retain(|key, _| tracks.contains_key(key));
+
+            for (key, (track, audible)) in tracks.iter() {
                 let (left_wave_of_track, right_wave_of_track) =
-                    self.sample_track_players.get_mut(key).unwrap().play(
-                        &track,
+                    self.sample_track_players.get_mut(*key).unwrap().play(
+                        track,
                         Arc::clone(&resource_manager),
                         &self.cum_current_samples,
-                        &self.cum_current_beats,
-                        &self.current_bpm,
                     );
+                if !audible {
+                    continue;
+                }
                 for i in 0..self.wave_length as usize {
-                    left_wave[i] = left_wave[i] + left_wave_of_track[i];
-                    right_wave[i] = right_wave[i] + right_wave_of_track[i];
+                    left_wave[i] += left_wave_of_track[i];
+                    right_wave[i] += right_wave_of_track[i];
                 }
             }
         }
 
-        // Effect更新
-        if self.effect_infos
-            != music_state
-                .get_section_state_by_beat(cum_next_beats)
-                .effects
-        {
-            self.effect_infos = music_state
-                .get_section_state_by_beat(cum_next_beats)
-                .effects
-                .clone();
-            let mut effects = vec![];
-            for efi in self.effect_infos.iter() {
-                effects.push(efi.get_effect(Arc::clone(&resource_manager)));
-            }
-            self.effects = effects;
-        }
-
         // Effect
-        for effect in self.effects.iter_mut() {
-            let (l, r) = effect.effect(&left_wave, &right_wave);
-            left_wave = l;
-            right_wave = r;
-        }
+        self.effect_chain.update(
+            &section_states.last().unwrap().effects,
+            Arc::clone(&resource_manager),
+        );
+        let (left_wave, right_wave) = self.effect_chain.effect(left_wave, right_wave);
 
         self.cum_current_samples = cum_next_samples;
         self.cum_current_beats = cum_next_beats;
@@ -238,13 +618,51 @@ impl StoreReader<(Vec<i16>, Vec<i16>), WaveReaderEvent, MusicState, MusicStateEv
     fn apply(&mut self, event: WaveReaderEvent) {
         match event {
             WaveReaderEvent::MoveStart => {
-                self.cum_current_samples = 0;
-                self.current_bpm = 120.0;
-                self.bpm_change_samples = 0;
-                self.bpm_change_beats = Beat::from(0);
-                self.cum_current_beats = Beat::from(0);
-                self.pitch_track_players = HashMap::new();
-                self.sample_track_players = HashMap::new();
+                self.seek(Beat::from(0));
+            }
+            WaveReaderEvent::Seek(beat) => {
+                self.seek(beat);
+            }
+            WaveReaderEvent::Pause => {
+                self.playing = false;
+            }
+            WaveReaderEvent::Resume => {
+                self.playing = true;
+            }
+            WaveReaderEvent::Stop => {
+                self.playing = false;
+                self.seek(Beat::from(0));
+            }
+            WaveReaderEvent::SetLoopRegion(start, end) => {
+                self.loop_region = if start < end {
+                    Some(LoopRegion::Beats(start, end))
+                } else {
+                    None
+                };
+            }
+            WaveReaderEvent::LoopSection(beat) => {
+                self.loop_region = Some(LoopRegion::Section(beat));
+            }
+            WaveReaderEvent::QueueEvent(quantize, event) => {
+                match serialize::Serialize::serialize(&*event) {
+                    Ok(serialized_event) => self.queued_events.push(QueuedEvent {
+                        cum_queued_beats: self.cum_current_beats,
+                        quantize,
+                        serialized_event,
+                    }),
+                    Err(e) => error!("serialize Error {}", e),
+                }
+            }
+            WaveReaderEvent::ClearQueuedEvents => {
+                self.queued_events = Vec::new();
+            }
+            WaveReaderEvent::ClearLoopRegion => {
+                self.loop_region = None;
+            }
+            WaveReaderEvent::SetRenderConfig(render_config) => {
+                if let Err(e) = self.set_render_config(render_config) {
+                    error!("set_render_config Error {}", e);
+                }
             }
         }
     }
@@ -253,19 +671,29 @@ impl StoreReader<(Vec<i16>, Vec<i16>), WaveReaderEvent, MusicState, MusicStateEv
 #[derive(Serialize, Deserialize)]
 pub enum WaveReaderEvent {
     MoveStart,
+    Seek(Beat),
+    Pause,
+    Resume,
+    Stop,
+    SetLoopRegion(Beat, Beat),
+    LoopSection(Beat),
+    ClearLoopRegion,
+    QueueEvent(Quantize, Box<MusicStateEvent>),
+    ClearQueuedEvents,
+    SetRenderConfig(RenderConfig),
 }
 
 impl serialize::Serialize<WaveReaderEvent> for WaveReaderEvent {
-    fn serialize(&self) -> Result<String, String> {
+    fn serialize(&self) -> Result<String, ToidError> {
         match serde_json::to_string(&self) {
             Ok(serialized) => Ok(serialized),
-            Err(err) => Err(format!("error in serizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
-    fn deserialize(serialized: String) -> Result<Self, String> {
+    fn deserialize(serialized: String) -> Result<Self, ToidError> {
         match serde_json::from_str(serialized.as_str()) {
             Ok(deserialized) => Ok(deserialized),
-            Err(err) => Err(format!("error in deserizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
 }
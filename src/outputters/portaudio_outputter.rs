@@ -2,18 +2,19 @@ extern crate portaudio;
 
 use std::option::Option;
 use std::sync::Arc;
-use std::sync::RwLock;
+use std::sync::{RwLock, TryLockError};
 
 use portaudio as pa;
 
+use super::super::error::ToidError;
+
+use super::super::music_state::render_config::RenderConfig;
 use super::super::music_state::states::{MusicState, MusicStateEvent};
 use super::super::music_state::wave_reader::{WaveReader, WaveReaderEvent};
 use super::super::players::player::Player;
 use super::super::state_management::store_reader::StoreReader;
 
 const CHANNELS: i32 = 2;
-const SAMPLE_RATE: f64 = 44_100.0;
-const FRAMES_PER_BUFFER: u32 = 512;
 
 struct PortAudioOutputterConfig {
     volume: f32,
@@ -32,6 +33,7 @@ pub struct PortAudioOutputter {
     portaudio: pa::PortAudio,
     stream: Option<pa::Stream<pa::NonBlocking, pa::Output<i16>>>,
     config: Arc<RwLock<PortAudioOutputterConfig>>,
+    render_config: RenderConfig,
 }
 
 impl PortAudioOutputter {
@@ -45,44 +47,68 @@ impl PortAudioOutputter {
                 WaveReaderEvent,
             >,
         >,
-    ) -> Result<Self, String> {
-        let portaudio = pa::PortAudio::new().map_err(|e| e.to_string())?;
+    ) -> Result<Self, ToidError> {
+        let portaudio = pa::PortAudio::new()?;
 
         Ok(PortAudioOutputter {
             player,
             portaudio,
             stream: None,
             config: Arc::new(RwLock::new(PortAudioOutputterConfig { volume: 1.0 })),
+            render_config: RenderConfig::default(),
         })
     }
 
+    // runより前に呼ぶ
+    pub fn set_render_config(&mut self, render_config: RenderConfig) {
+        self.render_config = render_config;
+    }
+
     pub fn set_volume(&self, volume: f32) {
         self.config.write().unwrap().set_volume(volume);
     }
 
-    pub fn run(&mut self) -> Result<(), String> {
+    pub fn run(&mut self) -> Result<(), ToidError> {
         let wave_reader = Arc::clone(&self.player.get_reader());
+        wave_reader
+            .write()?
+            .apply(WaveReaderEvent::SetRenderConfig(self.render_config));
+        if wave_reader.read()?.get_render_config() != self.render_config {
+            return Err(ToidError::Audio(
+                "render config is not applied to the reader".to_string(),
+            ));
+        }
         let store = Arc::clone(&self.player.get_store());
         let resource_manager = Arc::clone(&self.player.get_resource_manager());
         let config = Arc::clone(&self.config);
+        let mut volume = config.read()?.volume;
         let callback = move |pa::OutputStreamCallbackArgs::<'static, i16> {
                                  buffer,
                                  frames,
                                  ..
                              }|
               -> pa::StreamCallbackResult {
-            let (left_waves, right_waves) = match wave_reader.write() {
+            // 他のthreadがwave_readerのlockを持っているときは、待たずに無音を返す
+            let (left_waves, right_waves) = match wave_reader.try_write() {
                 Ok(mut wave_reader) => {
                     wave_reader.read(Arc::clone(&store), Arc::clone(&resource_manager))
                 }
-                Err(_) => {
+                Err(TryLockError::WouldBlock) => {
+                    for sample in buffer.iter_mut() {
+                        *sample = 0;
+                    }
+                    return pa::Continue;
+                }
+                Err(TryLockError::Poisoned(_)) => {
                     // TODO: rethinking
                     return pa::StreamCallbackResult::Abort;
                 }
             };
 
             let mut idx = 0;
-            let volume = config.read().unwrap().volume;
+            if let Ok(config) = config.try_read() {
+                volume = config.volume;
+            }
             for i in 0..frames {
                 buffer[idx] = (volume * left_waves[i] as f32) as i16;
                 buffer[idx + 1] = (volume * right_waves[i] as f32) as i16;
@@ -91,32 +117,30 @@ impl PortAudioOutputter {
             pa::Continue
         };
 
-        let mut settings = self
-            .portaudio
-            .default_output_stream_settings::<i16>(CHANNELS, SAMPLE_RATE, FRAMES_PER_BUFFER)
-            .map_err(|e| e.to_string())?;
+        let mut settings = self.portaudio.default_output_stream_settings::<i16>(
+            CHANNELS,
+            self.render_config.sample_rate as f64,
+            self.render_config.block_size as u32,
+        )?;
         settings.flags = pa::stream_flags::CLIP_OFF;
 
         let mut stream = self
             .portaudio
-            .open_non_blocking_stream(settings, callback)
-            .map_err(|e| e.to_string())?;
+            .open_non_blocking_stream(settings, callback)?;
 
-        stream.start().map_err(|e| e.to_string())?;
+        stream.start()?;
         self.stream = Some(stream);
 
         Ok(())
     }
 
-    pub fn stop(&mut self) -> Result<(), String> {
+    pub fn stop(&mut self) -> Result<(), ToidError> {
         Option::as_mut(&mut self.stream)
-            .ok_or("as_mut failed")?
-            .stop()
-            .map_err(|e| e.to_string())?;
+            .ok_or_else(|| ToidError::Audio("stream is not running".to_string()))?
+            .stop()?;
         Option::as_mut(&mut self.stream)
-            .ok_or("as_mut failed")?
-            .close()
-            .map_err(|e| e.to_string())?;
+            .ok_or_else(|| ToidError::Audio("stream is not running".to_string()))?
+            .close()?;
         self.stream = None;
         Ok(())
     }
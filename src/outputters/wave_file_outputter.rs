@@ -1,18 +1,19 @@
 use std::sync::Arc;
 
 use super::super::data::wave::{Data, Wave};
+use super::super::error::ToidError;
+use super::super::music_state::render_config::RenderConfig;
 use super::super::music_state::states::{MusicState, MusicStateEvent};
 use super::super::music_state::wave_reader::{WaveReader, WaveReaderEvent};
 use super::super::players::player::Player;
+use super::super::state_management::journal::JournalEntry;
 use super::super::state_management::store_reader::StoreReader;
 
-const SAMPLE_RATE: f32 = 44_100.0;
-const FRAMES_PER_BUFFER: u32 = 512;
-
 pub struct WaveFileOutputter {
     player: Arc<
         dyn Player<MusicState, MusicStateEvent, WaveReader, (Vec<i16>, Vec<i16>), WaveReaderEvent>,
     >,
+    render_config: RenderConfig,
 }
 
 impl WaveFileOutputter {
@@ -26,19 +27,72 @@ impl WaveFileOutputter {
                 WaveReaderEvent,
             >,
         >,
-    ) -> Result<Self, String> {
-        Ok(WaveFileOutputter { player })
+    ) -> Result<Self, ToidError> {
+        Ok(WaveFileOutputter {
+            player,
+            render_config: RenderConfig::default(),
+        })
+    }
+
+    pub fn set_render_config(&mut self, render_config: RenderConfig) {
+        self.render_config = render_config;
+    }
+
+    pub fn save(&mut self, path: String, sec: f32) -> Result<(), ToidError> {
+        self.save_journal(path, vec![], sec)
+    }
+
+    // journalのeventを記録された時刻に相当するsampleで適用しながら書き出す
+    pub fn save_journal(
+        &mut self,
+        path: String,
+        entries: Vec<JournalEntry>,
+        sec: f32,
+    ) -> Result<(), ToidError> {
+        let (all_left_wave, all_right_wave) = self.render_journal(entries, sec)?;
+        let sample_num = all_left_wave.len();
+        let wave = Wave {
+            data: Data::Stereo((all_left_wave, all_right_wave)),
+            sample_num,
+            sample_rate: self.render_config.sample_rate,
+        };
+
+        wave.save(path);
+        Ok(())
     }
 
-    pub fn save(&mut self, path: String, sec: f32) {
+    // 各bufferの終わりより前に記録されたentryは、そのbufferを読む前に適用する
+    fn render_journal(
+        &mut self,
+        entries: Vec<JournalEntry>,
+        sec: f32,
+    ) -> Result<(Vec<f32>, Vec<f32>), ToidError> {
         let mut all_left_wave: Vec<f32> = vec![];
         let mut all_right_wave: Vec<f32> = vec![];
 
         let wave_reader = self.player.get_reader();
+        wave_reader
+            .write()?
+            .apply(WaveReaderEvent::SetRenderConfig(self.render_config));
+        if wave_reader.read()?.get_render_config() != self.render_config {
+            return Err(ToidError::Audio(
+                "render config is not applied to the reader".to_string(),
+            ));
+        }
+        let sample_rate = self.render_config.sample_rate;
+        let frames_per_buffer = self.render_config.block_size;
         let store = Arc::clone(&self.player.get_store());
         let resource_manager = Arc::clone(&self.player.get_resource_manager());
 
-        for _ in 0..((sec * SAMPLE_RATE) / FRAMES_PER_BUFFER as f32) as usize {
+        let mut entries = entries.into_iter().peekable();
+        for buffer_idx in 0..((sec * sample_rate) / frames_per_buffer as f32) as usize {
+            let buffer_end_micros = ((buffer_idx + 1) as f64 * frames_per_buffer as f64
+                / sample_rate as f64
+                * 1_000_000.0) as u64;
+            while let Some(entry) = entries.next_if(|e| e.elapsed_micros < buffer_end_micros) {
+                store.apply_journal_entry(entry)?;
+            }
+
             let (left_waves, right_waves) = match wave_reader.write() {
                 Ok(mut wave_reader) => {
                     wave_reader.read(Arc::clone(&store), Arc::clone(&resource_manager))
@@ -56,13 +110,67 @@ impl WaveFileOutputter {
             }
         }
 
-        let sample_num = all_left_wave.len();
-        let wave = Wave {
-            data: Data::Stereo((all_left_wave, all_right_wave)),
-            sample_num,
-            sample_rate: SAMPLE_RATE,
-        };
+        Ok((all_left_wave, all_right_wave))
+    }
+}
 
-        wave.save(path);
+#[cfg(test)]
+mod tests {
+    use super::super::super::data::music_info::{
+        Beat, Instrument, Phrase, Pitch, PitchNote, Track,
+    };
+    use super::super::super::music_state::states::SectionStateEvent;
+    use super::super::super::players::local_player::LocalPlayer;
+    use super::super::super::state_management::journal::JournalRecord;
+    use super::super::super::state_management::serialize::Serialize;
+    use super::*;
+
+    type MusicLocalPlayer =
+        LocalPlayer<MusicState, MusicStateEvent, WaveReader, (Vec<i16>, Vec<i16>), WaveReaderEvent>;
+
+    fn new_track_entry(elapsed_micros: u64, key: &str, start: f32) -> JournalEntry {
+        let phrase = Phrase::new()
+            .add_note(PitchNote {
+                pitch: Pitch::from(69),
+                duration: Beat::from(1),
+                start: Beat::from(start),
+                velocity: 100,
+            })
+            .set_length(Beat::from(8));
+        let event = MusicStateEvent::SectionStateEvent(
+            Beat::from(0),
+            SectionStateEvent::NewPitchTrack(
+                key.to_string(),
+                Track::new().set_phrase(phrase).set_inst(Instrument::Sin),
+            ),
+        );
+        JournalEntry {
+            elapsed_micros,
+            record: JournalRecord::Event(event.serialize().unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_render_journal() {
+        let player: Arc<MusicLocalPlayer> = Arc::new(LocalPlayer::new());
+        let mut outputter = WaveFileOutputter::new(player.clone()).unwrap();
+
+        // 1bufferは512sampleで約11610μs。
+        // buffer_idxが10のbufferの途中に記録されたentryは、そのbufferを読む前に適用される
+        let entries = vec![
+            new_track_entry(10 * 11610 + 5000, "main", 0.25),
+            new_track_entry(11 * 11610 + 5000, "late", 0.24),
+        ];
+        let (left_wave, _) = outputter.render_journal(entries, 0.5).unwrap();
+        assert_eq!(left_wave.len(), 43 * 512);
+        // 0.25拍(5512.5sample目)はbuffer_idxが10のbufferの中。
+        // 0.24拍(5292sample目)のnoteは、"late"が適用される前に過ぎている
+        assert!(left_wave[..5513].iter().all(|&x| x == 0.0));
+        assert!(left_wave[5513..5632].iter().any(|&x| x != 0.0));
+        assert!(
+            player.get_store().get_state().unwrap().section_map[&Beat::from(0)]
+                .pitch_track_map
+                .contains_key("late")
+        );
     }
 }
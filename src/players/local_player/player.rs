@@ -4,6 +4,7 @@ use std::marker::PhantomData;
 use std::sync::Arc;
 use std::sync::RwLock;
 
+use super::super::super::error::ToidError;
 use super::super::super::resource_management::resource_manager::{
     ResourceManager, ResourceManagerEvent,
 };
@@ -21,8 +22,13 @@ pub struct LocalPlayer<S, E, R, O, RE> {
     reader_event_marker: PhantomData<RE>,
 }
 
-impl<S: State<E>, E: Sized + Serialize<E>, R: StoreReader<O, RE, S, E>, O, RE: Sized>
-    LocalPlayer<S, E, R, O, RE>
+impl<
+        S: State<E> + Serialize<S>,
+        E: Sized + Serialize<E>,
+        R: StoreReader<O, RE, S, E>,
+        O,
+        RE: Sized,
+    > LocalPlayer<S, E, R, O, RE>
 {
     pub fn new() -> Self {
         Self {
@@ -50,39 +56,54 @@ impl<S: State<E> + Serialize<S>, E: Sized + Serialize<E>, R: StoreReader<O, RE,
         Arc::clone(&self.reader)
     }
 
-    fn send_event(&self, event: E) -> Result<(), String> {
+    fn send_event(&self, event: E) -> Result<(), ToidError> {
         self.store.update_state(event)?;
         Ok(())
     }
 
-    fn send_reader_event(&self, event: RE) -> Result<(), String> {
-        self.reader
-            .write()
-            .map_err(|_| "rwlock error")?
-            .apply(event);
+    fn undo(&self) -> Result<(), ToidError> {
+        self.store.undo()
+    }
+
+    fn redo(&self) -> Result<(), ToidError> {
+        self.store.redo()
+    }
+
+    fn jump_to(&self, idx: usize) -> Result<(), ToidError> {
+        self.store.jump_to(idx)
+    }
+
+    fn send_reader_event(&self, event: RE) -> Result<(), ToidError> {
+        self.reader.write()?.apply(event);
         Ok(())
     }
 
-    fn send_resource_event(&self, event: ResourceManagerEvent) -> Result<(), String> {
+    fn send_resource_event(&self, event: ResourceManagerEvent) -> Result<(), ToidError> {
         self.resource_manager.apply(event)?;
         Ok(())
     }
 
-    fn save_state(&self, path: String) -> Result<(), String> {
+    fn save_state(&self, path: String) -> Result<(), ToidError> {
         let serialized_state: String = self.store.get_state()?.serialize()?;
-        let mut file = File::create(path).or_else(|e| Err(e.to_string()))?;
-        file.write_all(serialized_state.as_bytes())
-            .or_else(|e| Err(e.to_string()))?;
+        let mut file = File::create(path)?;
+        file.write_all(serialized_state.as_bytes())?;
         Ok(())
     }
 
-    fn load_state(&self, path: String) -> Result<(), String> {
-        let mut file = File::open(path).or_else(|e| Err(e.to_string()))?;
+    fn load_state(&self, path: String) -> Result<(), ToidError> {
+        let mut file = File::open(path)?;
         let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .or_else(|e| Err(e.to_string()))?;
+        file.read_to_string(&mut contents)?;
         let state: S = S::deserialize(contents)?;
         self.store.set_state(state)?;
         Ok(())
     }
+
+    fn start_journal(&self, path: String) -> Result<(), ToidError> {
+        self.store.start_journal(path)
+    }
+
+    fn stop_journal(&self) -> Result<(), ToidError> {
+        self.store.stop_journal()
+    }
 }
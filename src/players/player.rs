@@ -1,6 +1,7 @@
 use std::sync::Arc;
 use std::sync::RwLock;
 
+use super::super::error::ToidError;
 use super::super::resource_management::resource_manager::{ResourceManager, ResourceManagerEvent};
 use super::super::state_management::store::Store;
 use super::super::state_management::store_reader::StoreReader;
@@ -9,9 +10,14 @@ pub trait Player<S, E, R: StoreReader<O, RE, S, E>, O, RE> {
     fn get_store(&self) -> Arc<Store<S, E>>;
     fn get_resource_manager(&self) -> Arc<ResourceManager>;
     fn get_reader(&self) -> Arc<RwLock<R>>;
-    fn send_event(&self, event: E) -> Result<(), String>;
-    fn send_reader_event(&self, event: RE) -> Result<(), String>;
-    fn send_resource_event(&self, event: ResourceManagerEvent) -> Result<(), String>;
-    fn save_state(&self, path: String) -> Result<(), String>;
-    fn load_state(&self, path: String) -> Result<(), String>;
+    fn send_event(&self, event: E) -> Result<(), ToidError>;
+    fn undo(&self) -> Result<(), ToidError>;
+    fn redo(&self) -> Result<(), ToidError>;
+    fn jump_to(&self, idx: usize) -> Result<(), ToidError>;
+    fn send_reader_event(&self, event: RE) -> Result<(), ToidError>;
+    fn send_resource_event(&self, event: ResourceManagerEvent) -> Result<(), ToidError>;
+    fn save_state(&self, path: String) -> Result<(), ToidError>;
+    fn load_state(&self, path: String) -> Result<(), ToidError>;
+    fn start_journal(&self, path: String) -> Result<(), ToidError>;
+    fn stop_journal(&self) -> Result<(), ToidError>;
 }
@@ -13,6 +13,7 @@ use url;
 use ws;
 use ws::util::TcpStream;
 
+use super::super::super::error::ToidError;
 use super::super::super::resource_management::resource_manager::{
     ResourceManager, ResourceManagerEvent,
 };
@@ -95,15 +96,32 @@ impl<
         });
     }
 
-    pub fn sync_state(&self) -> Result<(), String> {
-        match &self.sender_holder.read().map_err(|_| "rwlock error")?.out {
+    pub fn sync_state(&self) -> Result<(), ToidError> {
+        match &self.sender_holder.read()?.out {
             Some(out) => {
                 let serialized_state = self.store.get_state()?.serialize()?;
                 let msg = SendData::SyncState(serialized_state).serialize()?;
-                out.send(msg).map_err(|e| e.to_string())?;
+                out.send(msg)?;
                 Ok(())
             }
-            None => Err("sender have not been prepared yet".to_string()),
+            None => Err(ToidError::Network(
+                "sender have not been prepared yet".to_string(),
+            )),
+        }
+    }
+}
+
+impl<S, E, R, O, RE> WebSocketPlayer<S, E, R, O, RE> {
+    fn send_data(&self, send_data: SendData) -> Result<(), ToidError> {
+        match &self.sender_holder.read()?.out {
+            Some(out) => {
+                let msg = send_data.serialize()?;
+                out.send(msg)?;
+                Ok(())
+            }
+            None => Err(ToidError::Network(
+                "sender have not been prepared yet".to_string(),
+            )),
         }
     }
 }
@@ -128,59 +146,83 @@ impl<
         Arc::clone(&self.reader)
     }
 
-    fn send_event(&self, event: E) -> Result<(), String> {
-        match &self.sender_holder.read().map_err(|_| "rwlock error")?.out {
+    fn send_event(&self, event: E) -> Result<(), ToidError> {
+        match &self.sender_holder.read()?.out {
             Some(out) => {
                 let serialized_event = event.serialize()?;
                 let msg = SendData::StateUpdate(serialized_event).serialize()?;
-                out.send(msg).map_err(|e| e.to_string())?;
+                out.send(msg)?;
                 Ok(())
             }
-            None => Err("sender have not been prepared yet".to_string()),
+            None => Err(ToidError::Network(
+                "sender have not been prepared yet".to_string(),
+            )),
         }
     }
 
-    fn send_reader_event(&self, event: RE) -> Result<(), String> {
-        match &self.sender_holder.read().map_err(|_| "rwlock error")?.out {
+    fn undo(&self) -> Result<(), ToidError> {
+        self.send_data(SendData::Undo)
+    }
+
+    fn redo(&self) -> Result<(), ToidError> {
+        self.send_data(SendData::Redo)
+    }
+
+    fn jump_to(&self, idx: usize) -> Result<(), ToidError> {
+        self.send_data(SendData::JumpTo(idx))
+    }
+
+    fn send_reader_event(&self, event: RE) -> Result<(), ToidError> {
+        match &self.sender_holder.read()?.out {
             Some(out) => {
                 let serialized_event = event.serialize()?;
                 let msg = SendData::ApplyReader(serialized_event).serialize()?;
-                out.send(msg).map_err(|e| e.to_string())?;
+                out.send(msg)?;
                 Ok(())
             }
-            None => Err("sender have not been prepared yet".to_string()),
+            None => Err(ToidError::Network(
+                "sender have not been prepared yet".to_string(),
+            )),
         }
     }
 
-    fn send_resource_event(&self, event: ResourceManagerEvent) -> Result<(), String> {
-        match &self.sender_holder.read().map_err(|_| "rwlock error")?.out {
+    fn send_resource_event(&self, event: ResourceManagerEvent) -> Result<(), ToidError> {
+        match &self.sender_holder.read()?.out {
             Some(out) => {
                 let serialized_event = event.serialize()?;
                 let msg = SendData::ApplyResourceManager(serialized_event).serialize()?;
-                out.send(msg).map_err(|e| e.to_string())?;
+                out.send(msg)?;
                 Ok(())
             }
-            None => Err("sender have not been prepared yet".to_string()),
+            None => Err(ToidError::Network(
+                "sender have not been prepared yet".to_string(),
+            )),
         }
     }
 
-    fn save_state(&self, path: String) -> Result<(), String> {
+    fn save_state(&self, path: String) -> Result<(), ToidError> {
         let serialized_state: String = self.store.get_state()?.serialize()?;
-        let mut file = File::create(path).or_else(|e| Err(e.to_string()))?;
-        file.write_all(serialized_state.as_bytes())
-            .or_else(|e| Err(e.to_string()))?;
+        let mut file = File::create(path)?;
+        file.write_all(serialized_state.as_bytes())?;
         Ok(())
     }
 
-    fn load_state(&self, path: String) -> Result<(), String> {
-        let mut file = File::open(path).or_else(|e| Err(e.to_string()))?;
+    fn load_state(&self, path: String) -> Result<(), ToidError> {
+        let mut file = File::open(path)?;
         let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .or_else(|e| Err(e.to_string()))?;
+        file.read_to_string(&mut contents)?;
         let state: S = S::deserialize(contents)?;
         self.store.set_state(state)?;
         Ok(())
     }
+
+    fn start_journal(&self, path: String) -> Result<(), ToidError> {
+        self.store.start_journal(path)
+    }
+
+    fn stop_journal(&self) -> Result<(), ToidError> {
+        self.store.stop_journal()
+    }
 }
 
 impl<
@@ -201,35 +243,53 @@ impl<
         let send_data: SendData =
             SendData::deserialize(msg.to_string()).map_err(|e| ws::Error {
                 kind: ws::ErrorKind::Internal,
-                details: Cow::from(e),
+                details: Cow::from(e.to_string()),
             })?;
         match send_data {
             SendData::StateUpdate(event_string) => {
                 let event: E = E::deserialize(event_string).map_err(|e| ws::Error {
                     kind: ws::ErrorKind::Internal,
-                    details: Cow::from(e),
+                    details: Cow::from(e.to_string()),
                 })?;
                 self.store.update_state(event).map_err(|e| ws::Error {
                     kind: ws::ErrorKind::Internal,
-                    details: Cow::from(e),
+                    details: Cow::from(e.to_string()),
                 })?;
                 Ok(())
             }
             SendData::SyncState(state_string) => {
                 let state: S = S::deserialize(state_string).map_err(|e| ws::Error {
                     kind: ws::ErrorKind::Internal,
-                    details: Cow::from(e),
+                    details: Cow::from(e.to_string()),
                 })?;
                 self.store.set_state(state).map_err(|e| ws::Error {
                     kind: ws::ErrorKind::Internal,
-                    details: Cow::from(e),
+                    details: Cow::from(e.to_string()),
                 })?;
                 Ok(())
             }
+            SendData::Undo => {
+                if let Err(error) = self.store.undo() {
+                    error!("undo error !: {}", error);
+                }
+                Ok(())
+            }
+            SendData::Redo => {
+                if let Err(error) = self.store.redo() {
+                    error!("redo error !: {}", error);
+                }
+                Ok(())
+            }
+            SendData::JumpTo(idx) => {
+                if let Err(error) = self.store.jump_to(idx) {
+                    error!("jump_to error !: {}", error);
+                }
+                Ok(())
+            }
             SendData::ApplyReader(event_string) => {
                 let event: RE = RE::deserialize(event_string).map_err(|e| ws::Error {
                     kind: ws::ErrorKind::Internal,
-                    details: Cow::from(e),
+                    details: Cow::from(e.to_string()),
                 })?;
                 self.reader
                     .write()
@@ -244,7 +304,7 @@ impl<
                 let event: ResourceManagerEvent = ResourceManagerEvent::deserialize(event_string)
                     .map_err(|e| ws::Error {
                     kind: ws::ErrorKind::Internal,
-                    details: Cow::from(e),
+                    details: Cow::from(e.to_string()),
                 })?;
                 if let Err(error) = self.resource_manager.apply(event) {
                     error!("send resource event error !: {}", error);
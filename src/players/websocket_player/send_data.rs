@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use super::super::super::error::ToidError;
 use super::super::super::state_management::serialize;
 
 #[derive(Serialize, Deserialize)]
@@ -8,21 +9,28 @@ pub enum SendData {
     SyncState(String),
     ApplyReader(String),
     ApplyResourceManager(String),
+    Undo,
+    Redo,
+    JumpTo(usize),
 }
 
 impl serialize::Serialize<SendData> for SendData {
-    fn serialize(&self) -> Result<String, String> {
+    fn serialize(&self) -> Result<String, ToidError> {
         if let Ok(serialized) = serde_json::to_string(&self) {
             Ok(serialized)
         } else {
-            Err(String::from("error in serizalization"))
+            Err(ToidError::Serialization(
+                "error in serizalization".to_string(),
+            ))
         }
     }
-    fn deserialize(serialized: String) -> Result<Self, String> {
+    fn deserialize(serialized: String) -> Result<Self, ToidError> {
         if let Ok(string) = serde_json::from_str(serialized.as_str()) {
             Ok(string)
         } else {
-            Err(String::from("error in deserizalization"))
+            Err(ToidError::Serialization(
+                "error in deserizalization".to_string(),
+            ))
         }
     }
 }
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 
 use super::super::data::sf2::SF2;
 use super::super::data::wave::Wave;
+use super::super::error::ToidError;
 use super::super::state_management::serialize;
 use super::resource_units::ResourceUnitEnum;
 
@@ -20,45 +21,48 @@ impl ResourceManager {
         }
     }
 
-    pub fn register(&self, path: String) -> Result<(), String> {
+    pub fn register(&self, path: String) -> Result<(), ToidError> {
         let new_unit = ResourceUnitEnum::load_toml(path)?;
         self.units
-            .write()
-            .map_err(|_| "RwLock Error")?
+            .write()?
             .insert(new_unit.get_name().clone(), new_unit);
         Ok(())
     }
 
-    pub fn get_sf2(&self, name: String) -> Result<Arc<SF2>, String> {
+    pub fn get_sf2(&self, name: String) -> Result<Arc<SF2>, ToidError> {
         match self
             .units
-            .read()
-            .map_err(|_| "RwLock Error")?
+            .read()?
             .get(&name)
-            .ok_or("get Error")?
+            .ok_or_else(|| ToidError::ResourceNotFound(name.clone()))?
         {
             ResourceUnitEnum::SF2(sf2) => Ok(Arc::clone(&sf2.sf2)),
-            _ => Err("this name is not sf2".to_string()),
+            _ => Err(ToidError::InvalidResourceType(format!(
+                "{} is not sf2",
+                name
+            ))),
         }
     }
 
-    pub fn get_sample_wave(&self, name: String, sound: String) -> Result<Arc<Wave>, String> {
+    pub fn get_sample_wave(&self, name: String, sound: String) -> Result<Arc<Wave>, ToidError> {
         match self
             .units
-            .read()
-            .map_err(|_| "RwLock Error")?
+            .read()?
             .get(&name)
-            .ok_or("get Error")?
+            .ok_or_else(|| ToidError::ResourceNotFound(name.clone()))?
         {
             ResourceUnitEnum::Samples(samples) => match samples.waves.get(&sound) {
                 Some(wave) => Ok(Arc::clone(&wave)),
-                None => Err("there is not wave of sound string".to_string()),
+                None => Err(ToidError::ResourceNotFound(format!("{}/{}", name, sound))),
             },
-            _ => Err("this name is not sf2".to_string()),
+            _ => Err(ToidError::InvalidResourceType(format!(
+                "{} is not samples",
+                name
+            ))),
         }
     }
 
-    pub fn apply(&self, _: ResourceManagerEvent) -> Result<(), String> {
+    pub fn apply(&self, _: ResourceManagerEvent) -> Result<(), ToidError> {
         // match event {}
         Ok(())
     }
@@ -68,16 +72,16 @@ impl ResourceManager {
 pub enum ResourceManagerEvent {}
 
 impl serialize::Serialize<ResourceManagerEvent> for ResourceManagerEvent {
-    fn serialize(&self) -> Result<String, String> {
+    fn serialize(&self) -> Result<String, ToidError> {
         match serde_json::to_string(&self) {
             Ok(serialized) => Ok(serialized),
-            Err(err) => Err(format!("error in serizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
-    fn deserialize(serialized: String) -> Result<Self, String> {
+    fn deserialize(serialized: String) -> Result<Self, ToidError> {
         match serde_json::from_str(serialized.as_str()) {
             Ok(deserialized) => Ok(deserialized),
-            Err(err) => Err(format!("error in deserizalization : {}", err)),
+            Err(err) => Err(ToidError::Serialization(err.to_string())),
         }
     }
 }
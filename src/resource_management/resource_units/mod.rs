@@ -6,6 +6,8 @@ use std::fs;
 use serde_derive::Deserialize;
 use toml;
 
+use super::super::error::ToidError;
+
 use samples::SamplesResourceUnit;
 use sf2::SF2ResourceUnit;
 
@@ -18,7 +20,7 @@ pub trait ResourceUnit
 where
     Self: Sized,
 {
-    fn load_toml(path: String) -> Result<Self, String>;
+    fn load_toml(path: String) -> Result<Self, ToidError>;
     fn get_name(&self) -> String;
 }
 
@@ -28,19 +30,16 @@ pub enum ResourceUnitEnum {
 }
 
 impl ResourceUnitEnum {
-    pub fn load_toml(path: String) -> Result<Self, String> {
-        let config_toml = fs::read_to_string(path.clone()).map_err(|_| "read error")?;
-        let decoded_config: ResourceConfig =
-            toml::from_str(config_toml.as_str()).map_err(|e| e.to_string())?;
+    pub fn load_toml(path: String) -> Result<Self, ToidError> {
+        let config_toml = fs::read_to_string(path.clone())?;
+        let decoded_config: ResourceConfig = toml::from_str(config_toml.as_str())?;
 
         match decoded_config.resourcetype.as_str() {
-            "sf2" => Ok(ResourceUnitEnum::SF2(
-                SF2ResourceUnit::load_toml(path).unwrap(),
-            )),
-            "samples" => Ok(ResourceUnitEnum::Samples(
-                SamplesResourceUnit::load_toml(path).unwrap(),
-            )),
-            _ => Err("invalid resource_type".to_string()),
+            "sf2" => Ok(ResourceUnitEnum::SF2(SF2ResourceUnit::load_toml(path)?)),
+            "samples" => Ok(ResourceUnitEnum::Samples(SamplesResourceUnit::load_toml(
+                path,
+            )?)),
+            resourcetype => Err(ToidError::InvalidResourceType(resourcetype.to_string())),
         }
     }
 
@@ -8,6 +8,7 @@ use serde_derive::Deserialize;
 use toml;
 
 use super::super::super::data::wave::Wave;
+use super::super::super::error::ToidError;
 use super::ResourceUnit;
 
 #[derive(Deserialize)]
@@ -25,13 +26,12 @@ pub struct SamplesResourceUnit {
 }
 
 impl ResourceUnit for SamplesResourceUnit {
-    fn load_toml(path: String) -> Result<Self, String> {
-        let config_toml = fs::read_to_string(path.clone()).map_err(|_| "read error")?;
-        let decoded_config: SamplesConfig =
-            toml::from_str(config_toml.as_str()).map_err(|e| e.to_string())?;
+    fn load_toml(path: String) -> Result<Self, ToidError> {
+        let config_toml = fs::read_to_string(path.clone())?;
+        let decoded_config: SamplesConfig = toml::from_str(config_toml.as_str())?;
 
         if decoded_config.resourcetype != "samples" {
-            return Err("is not samples".to_string());
+            return Err(ToidError::InvalidResourceType(decoded_config.resourcetype));
         }
 
         let mut file_paths = HashMap::new();
@@ -40,9 +40,9 @@ impl ResourceUnit for SamplesResourceUnit {
             let file_path = Path::new(&path).with_file_name(value);
             file_paths.insert(key.clone(), Box::<Path>::from(file_path.clone()));
 
-            let mut f = fs::File::open(file_path.clone()).map_err(|_| "file open error")?;
+            let mut f = fs::File::open(file_path.clone())?;
             let mut buffer = Vec::new();
-            f.read_to_end(&mut buffer).map_err(|_| "read error")?;
+            f.read_to_end(&mut buffer)?;
             let buffer = buffer.as_slice();
             let wave = Wave::parse(buffer)?;
             let wave = Arc::new(wave);
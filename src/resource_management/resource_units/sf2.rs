@@ -7,6 +7,7 @@ use serde_derive::Deserialize;
 use toml;
 
 use super::super::super::data::sf2::SF2;
+use super::super::super::error::ToidError;
 use super::ResourceUnit;
 
 #[derive(Deserialize)]
@@ -24,19 +25,18 @@ pub struct SF2ResourceUnit {
 }
 
 impl ResourceUnit for SF2ResourceUnit {
-    fn load_toml(path: String) -> Result<Self, String> {
-        let config_toml = fs::read_to_string(path.clone()).map_err(|_| "read error")?;
-        let decoded_config: SF2Config =
-            toml::from_str(config_toml.as_str()).map_err(|e| e.to_string())?;
+    fn load_toml(path: String) -> Result<Self, ToidError> {
+        let config_toml = fs::read_to_string(path.clone())?;
+        let decoded_config: SF2Config = toml::from_str(config_toml.as_str())?;
 
         if decoded_config.resourcetype != "sf2" {
-            return Err("is not sf2".to_string());
+            return Err(ToidError::InvalidResourceType(decoded_config.resourcetype));
         }
 
         let file_path = Path::new(&path).with_file_name(decoded_config.path);
-        let mut f = fs::File::open(file_path.clone()).map_err(|_| "file open error")?;
+        let mut f = fs::File::open(file_path.clone())?;
         let mut buffer = Vec::new();
-        f.read_to_end(&mut buffer).map_err(|_| "read error")?;
+        f.read_to_end(&mut buffer)?;
         let buffer = buffer.as_slice();
         let sf2 = SF2::parse(buffer)?;
         let sf2 = Arc::new(sf2);
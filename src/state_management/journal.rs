@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use super::super::error::ToidError;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum JournalRecord {
+    SetState(String),
+    Event(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JournalEntry {
+    pub elapsed_micros: u64,
+    pub record: JournalRecord,
+}
+
+// 1行に1つのJournalEntryをjsonで書き込む
+pub struct Journal {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl Journal {
+    pub fn create(path: String) -> Result<Self, ToidError> {
+        let file = File::create(path)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn write(&mut self, record: JournalRecord) -> Result<(), ToidError> {
+        let entry = JournalEntry {
+            elapsed_micros: self.start.elapsed().as_micros() as u64,
+            record,
+        };
+        let line = serde_json::to_string(&entry)?;
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn read_journal(path: String) -> Result<Vec<JournalEntry>, ToidError> {
+    let file = File::open(path)?;
+    let mut entries = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let entry: JournalEntry = serde_json::from_str(line.as_str())?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ReplayTiming {
+    Original,
+    AsFastAsPossible,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_read() {
+        // 並行して動くtestとぶつからないように、process idを付ける
+        let path = std::env::temp_dir().join(format!(
+            "toid_test_journal_write_read_{}.jsonl",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap().to_string();
+
+        let mut journal = Journal::create(path.clone()).unwrap();
+        journal
+            .write(JournalRecord::SetState("state".to_string()))
+            .unwrap();
+        journal
+            .write(JournalRecord::Event("event".to_string()))
+            .unwrap();
+
+        let entries = read_journal(path.clone()).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].record,
+            JournalRecord::SetState("state".to_string())
+        );
+        assert_eq!(entries[1].record, JournalRecord::Event("event".to_string()));
+        assert!(entries[0].elapsed_micros <= entries[1].elapsed_micros);
+    }
+}
@@ -1,5 +1,7 @@
+pub mod journal;
 pub mod serialize;
 pub mod state;
 pub mod state_holder;
 pub mod store;
 pub mod store_reader;
+pub mod subscriber;
@@ -1,6 +1,8 @@
 use std::marker::Sized;
 
+use super::super::error::ToidError;
+
 pub trait Serialize<T: Sized> {
-    fn serialize(&self) -> Result<String, String>;
-    fn deserialize(serialized: String) -> Result<T, String>;
+    fn serialize(&self) -> Result<String, ToidError>;
+    fn deserialize(serialized: String) -> Result<T, ToidError>;
 }
@@ -1,21 +1,174 @@
+use std::collections::VecDeque;
 use std::sync::Arc;
 
+use super::super::error::ToidError;
+
+const DEFAULT_MAX_HISTORY_LENGTH: usize = 100;
+
+// eventはシリアライズした文字列で持つ。set_stateで置き換えた場合はNone
+pub struct HistoryEntry<S> {
+    pub state: Arc<S>,
+    pub event: Option<String>,
+}
+
+impl<S> Clone for HistoryEntry<S> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Arc::clone(&self.state),
+            event: self.event.clone(),
+        }
+    }
+}
+
 pub struct StateHolder<S> {
-    state: Arc<S>,
+    history: VecDeque<HistoryEntry<S>>,
+    current_idx: usize,
+    max_history_length: usize,
 }
 
 impl<S> StateHolder<S> {
     pub fn new(state: S) -> Self {
-        StateHolder {
+        let mut history = VecDeque::new();
+        history.push_back(HistoryEntry {
             state: Arc::new(state),
+            event: None,
+        });
+        StateHolder {
+            history,
+            current_idx: 0,
+            max_history_length: DEFAULT_MAX_HISTORY_LENGTH,
         }
     }
 
     pub fn get_state(&self) -> Arc<S> {
-        Arc::clone(&self.state)
+        Arc::clone(&self.history[self.current_idx].state)
     }
 
     pub fn set_state(&mut self, new_state: S) {
-        self.state = Arc::new(new_state);
+        self.push(new_state, None);
+    }
+
+    // undoした後にpushした場合、redo先のstateは捨てる
+    pub fn push(&mut self, new_state: S, event: Option<String>) {
+        self.history.truncate(self.current_idx + 1);
+        self.history.push_back(HistoryEntry {
+            state: Arc::new(new_state),
+            event,
+        });
+        while self.history.len() > self.max_history_length {
+            self.history.pop_front();
+        }
+        self.current_idx = self.history.len() - 1;
+    }
+
+    pub fn undo(&mut self) -> Result<(), ToidError> {
+        if self.current_idx == 0 {
+            return Err(ToidError::OutOfRange(
+                "there is no state to undo".to_string(),
+            ));
+        }
+        self.current_idx -= 1;
+        Ok(())
+    }
+
+    pub fn redo(&mut self) -> Result<(), ToidError> {
+        if self.current_idx + 1 >= self.history.len() {
+            return Err(ToidError::OutOfRange(
+                "there is no state to redo".to_string(),
+            ));
+        }
+        self.current_idx += 1;
+        Ok(())
+    }
+
+    pub fn jump_to(&mut self, idx: usize) -> Result<(), ToidError> {
+        if idx >= self.history.len() {
+            return Err(ToidError::OutOfRange(format!(
+                "history index {} is out of range (length {})",
+                idx,
+                self.history.len()
+            )));
+        }
+        self.current_idx = idx;
+        Ok(())
+    }
+
+    pub fn get_history(&self) -> Vec<HistoryEntry<S>> {
+        self.history.iter().cloned().collect()
+    }
+
+    pub fn get_current_idx(&self) -> usize {
+        self.current_idx
+    }
+
+    pub fn set_max_history_length(&mut self, max_history_length: usize) {
+        let max_history_length = max_history_length.max(1);
+        while self.history.len() > max_history_length {
+            if self.current_idx == 0 {
+                self.history.pop_back();
+            } else {
+                self.history.pop_front();
+                self.current_idx -= 1;
+            }
+        }
+        self.max_history_length = max_history_length;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_redo() {
+        let mut holder = StateHolder::new(0);
+        holder.push(1, Some("1".to_string()));
+        holder.push(2, Some("2".to_string()));
+        assert_eq!(*holder.get_state(), 2);
+
+        holder.undo().unwrap();
+        assert_eq!(*holder.get_state(), 1);
+        holder.undo().unwrap();
+        assert_eq!(*holder.get_state(), 0);
+        assert!(holder.undo().is_err());
+
+        holder.redo().unwrap();
+        assert_eq!(*holder.get_state(), 1);
+
+        holder.push(3, Some("3".to_string()));
+        assert!(holder.redo().is_err());
+        let events: Vec<Option<String>> = holder
+            .get_history()
+            .iter()
+            .map(|entry| entry.event.clone())
+            .collect();
+        assert_eq!(
+            events,
+            vec![None, Some("1".to_string()), Some("3".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_jump_to() {
+        let mut holder = StateHolder::new(0);
+        holder.push(1, None);
+        holder.push(2, None);
+        holder.jump_to(0).unwrap();
+        assert_eq!(*holder.get_state(), 0);
+        holder.jump_to(2).unwrap();
+        assert_eq!(*holder.get_state(), 2);
+        assert!(holder.jump_to(3).is_err());
+    }
+
+    #[test]
+    fn test_max_history_length() {
+        let mut holder = StateHolder::new(0);
+        holder.set_max_history_length(3);
+        for i in 1..10 {
+            holder.push(i, None);
+        }
+        assert_eq!(holder.get_history().len(), 3);
+        assert_eq!(*holder.get_history()[0].state, 7);
+        assert_eq!(holder.get_current_idx(), 2);
     }
 }
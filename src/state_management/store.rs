@@ -8,50 +8,301 @@ pub trait Store<S, E> {
 */
 
 use std::marker::PhantomData;
+use std::sync::mpsc::{channel, Receiver};
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use arc_swap::ArcSwap;
+
+use super::super::error::ToidError;
+use super::journal::{Journal, JournalEntry, JournalRecord, ReplayTiming};
 use super::serialize::Serialize;
 use super::state::State;
-use super::state_holder::StateHolder;
+use super::state_holder::{HistoryEntry, StateHolder};
+use super::subscriber::{StateChange, SubscriberCallback, Subscribers};
 
+// 読み出しはcurrent_stateからlockを取らずに行い、書き込み同士だけstate_holderのlockで直列化する
 pub struct Store<S, E> {
+    current_state: ArcSwap<S>,
     state_holder: RwLock<StateHolder<S>>,
+    journal: RwLock<Option<Journal>>,
+    subscribers: RwLock<Subscribers<S>>,
     event_marker: PhantomData<E>,
 }
 
-impl<S: State<E>, E: Sized + Serialize<E>> Store<S, E> {
+impl<S: State<E> + Serialize<S>, E: Sized + Serialize<E>> Store<S, E> {
     pub fn new(state: S) -> Self {
+        let state_holder = StateHolder::new(state);
         Self {
-            state_holder: RwLock::new(StateHolder::new(state)),
+            current_state: ArcSwap::new(state_holder.get_state()),
+            state_holder: RwLock::new(state_holder),
+            journal: RwLock::new(None),
+            subscribers: RwLock::new(Subscribers::new()),
             event_marker: PhantomData,
         }
     }
 
-    pub fn get_state(&self) -> Result<Arc<S>, String> {
-        Ok(Arc::clone(
-            &self
-                .state_holder
-                .read()
-                .map_err(|_| "RwLock Error")?
-                .get_state(),
-        ))
+    pub fn get_state(&self) -> Result<Arc<S>, ToidError> {
+        Ok(self.current_state.load_full())
+    }
+
+    pub fn update_state(&self, event: E) -> Result<(), ToidError> {
+        let serialized_event = event.serialize()?;
+        self.change_state(Some(serialized_event.clone()), |state_holder| {
+            let new_state = state_holder.get_state().reduce(event);
+            state_holder.push(new_state, Some(serialized_event));
+            Ok(())
+        })
+    }
+
+    pub fn set_state(&self, state: S) -> Result<(), ToidError> {
+        self.change_state(None, |state_holder| {
+            state_holder.set_state(state);
+            Ok(())
+        })
+    }
+
+    pub fn undo(&self) -> Result<(), ToidError> {
+        self.change_state(None, |state_holder| state_holder.undo())
+    }
+
+    pub fn redo(&self) -> Result<(), ToidError> {
+        self.change_state(None, |state_holder| state_holder.redo())
     }
 
-    pub fn update_state(&self, event: E) -> Result<(), String> {
-        let new_state = self.get_state()?.reduce(event);
+    pub fn jump_to(&self, idx: usize) -> Result<(), ToidError> {
+        self.change_state(None, |state_holder| state_holder.jump_to(idx))
+    }
+
+    pub fn subscribe(&self, callback: SubscriberCallback<S>) -> Result<usize, ToidError> {
+        Ok(self.subscribers.write()?.subscribe(callback))
+    }
+
+    pub fn unsubscribe(&self, id: usize) -> Result<(), ToidError> {
+        self.subscribers.write()?.unsubscribe(id)
+    }
+
+    pub fn get_history(&self) -> Result<Vec<HistoryEntry<S>>, ToidError> {
+        Ok(self.state_holder.read()?.get_history())
+    }
+
+    pub fn get_history_idx(&self) -> Result<usize, ToidError> {
+        Ok(self.state_holder.read()?.get_current_idx())
+    }
+
+    pub fn get_event_log(&self) -> Result<Vec<Option<String>>, ToidError> {
+        Ok(self
+            .get_history()?
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect())
+    }
+
+    pub fn set_max_history_length(&self, max_history_length: usize) -> Result<(), ToidError> {
         self.state_holder
-            .write()
-            .map_err(|_| "RwLock Error")?
-            .set_state(new_state);
+            .write()?
+            .set_max_history_length(max_history_length);
         Ok(())
     }
 
-    pub fn set_state(&self, state: S) -> Result<(), String> {
-        self.state_holder
-            .write()
-            .map_err(|_| "RwLock Error")?
-            .set_state(state);
+    pub fn start_journal(&self, path: String) -> Result<(), ToidError> {
+        let mut journal = Journal::create(path)?;
+        // 記録の開始までにstateが変わらないように、state_holderのlockを持ったまま記録を始める
+        let state_holder = self.state_holder.read()?;
+        journal.write(JournalRecord::SetState(
+            state_holder.get_state().serialize()?,
+        ))?;
+        *self.journal.write()? = Some(journal);
+        drop(state_holder);
         Ok(())
     }
+
+    pub fn stop_journal(&self) -> Result<(), ToidError> {
+        *self.journal.write()? = None;
+        Ok(())
+    }
+
+    pub fn apply_journal_entry(&self, entry: JournalEntry) -> Result<(), ToidError> {
+        match entry.record {
+            JournalRecord::SetState(state) => self.set_state(S::deserialize(state)?),
+            JournalRecord::Event(event) => self.update_state(E::deserialize(event)?),
+        }
+    }
+
+    pub fn replay(
+        &self,
+        entries: Vec<JournalEntry>,
+        timing: ReplayTiming,
+    ) -> Result<(), ToidError> {
+        let start = Instant::now();
+        for entry in entries {
+            if let ReplayTiming::Original = timing {
+                let entry_time = Duration::from_micros(entry.elapsed_micros);
+                let now = start.elapsed();
+                if entry_time > now {
+                    thread::sleep(entry_time - now);
+                }
+            }
+            self.apply_journal_entry(entry)?;
+        }
+        Ok(())
+    }
+
+    // stateの変更後に、journalへの記録とsubscriberへの通知を行う
+    fn change_state<F>(&self, event: Option<String>, change: F) -> Result<(), ToidError>
+    where
+        F: FnOnce(&mut StateHolder<S>) -> Result<(), ToidError>,
+    {
+        let (old_state, new_state) = {
+            let mut state_holder = self.state_holder.write()?;
+            let old_state = state_holder.get_state();
+            change(&mut state_holder)?;
+            let new_state = state_holder.get_state();
+            self.current_state.store(Arc::clone(&new_state));
+
+            // 適用した順にjournalへ記録されるように、state_holderのlockを持ったまま書き込む
+            if let Some(journal) = self.journal.write()?.as_mut() {
+                match &event {
+                    Some(event) => journal.write(JournalRecord::Event(event.clone()))?,
+                    // undoなどでeventを介さずにstateが変わった場合は、state全体を記録する
+                    None => journal.write(JournalRecord::SetState(new_state.serialize()?))?,
+                };
+            }
+            (old_state, new_state)
+        };
+
+        // callbackの中でsubscribeやunsubscribeができるように、lockを外してから通知する
+        let subscribers = self.subscribers.read()?.clone();
+        subscribers.notify(&StateChange {
+            old_state,
+            new_state,
+            event,
+        });
+        Ok(())
+    }
+}
+
+impl<S: 'static + State<E> + Serialize<S> + Send + Sync, E: Sized + Serialize<E>> Store<S, E> {
+    pub fn subscribe_channel(&self) -> Result<(usize, Receiver<StateChange<S>>), ToidError> {
+        let (sender, receiver) = channel();
+        let id = self.subscribers.write()?.subscribe_channel(sender);
+        Ok((id, receiver))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::journal::read_journal;
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    struct SlowState {
+        num: u64,
+    }
+
+    impl State<u64> for SlowState {
+        fn new() -> Self {
+            Self { num: 0 }
+        }
+
+        fn reduce(&self, event: u64) -> Self {
+            thread::sleep(Duration::from_millis(50));
+            Self {
+                num: self.num + event,
+            }
+        }
+    }
+
+    impl Serialize<SlowState> for SlowState {
+        fn serialize(&self) -> Result<String, ToidError> {
+            Ok(self.num.to_string())
+        }
+        fn deserialize(serialized: String) -> Result<Self, ToidError> {
+            Ok(Self {
+                num: serialized
+                    .parse()
+                    .map_err(|_| ToidError::Serialization("parse error".to_string()))?,
+            })
+        }
+    }
+
+    impl Serialize<u64> for u64 {
+        fn serialize(&self) -> Result<String, ToidError> {
+            Ok(self.to_string())
+        }
+        fn deserialize(serialized: String) -> Result<Self, ToidError> {
+            serialized
+                .parse()
+                .map_err(|_| ToidError::Serialization("parse error".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_get_state_does_not_wait_for_update_state() {
+        let store: Store<SlowState, u64> = Store::new(SlowState::new());
+        store.update_state(1).unwrap();
+
+        // 書き込み中でもlockを取らずに読める
+        let state_holder = store.state_holder.write().unwrap();
+        assert_eq!(store.get_state().unwrap().num, 1);
+        drop(state_holder);
+
+        store.update_state(1).unwrap();
+        assert_eq!(store.get_state().unwrap().num, 2);
+    }
+
+    #[test]
+    fn test_unsubscribe_in_callback() {
+        let store: Arc<Store<SlowState, u64>> = Arc::new(Store::new(SlowState::new()));
+        let subscriber_store = Arc::clone(&store);
+        let (sender, receiver) = channel();
+        let sender = Mutex::new(sender);
+        let id = store
+            .subscribe(Box::new(move |_| {
+                let result = subscriber_store.unsubscribe(0);
+                sender.lock().unwrap().send(result).unwrap();
+            }))
+            .unwrap();
+        assert_eq!(id, 0);
+
+        // 通知中にsubscribersのlockを持っていると、ここでdeadlockする
+        store.set_state(SlowState::new()).unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), Ok(()));
+        store.set_state(SlowState::new()).unwrap();
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_replay_journal() {
+        // 並行して動くtestとぶつからないように、process idを付ける
+        let path = std::env::temp_dir().join(format!(
+            "toid_test_store_replay_journal_{}.jsonl",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap().to_string();
+
+        let store: Store<SlowState, u64> = Store::new(SlowState::new());
+        store.update_state(1).unwrap();
+        store.start_journal(path.clone()).unwrap();
+        store.update_state(2).unwrap();
+        store.update_state(4).unwrap();
+        store.undo().unwrap();
+        store.update_state(8).unwrap();
+        store.stop_journal().unwrap();
+        assert_eq!(store.get_state().unwrap().num, 11);
+
+        let entries = read_journal(path.clone()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(entries[0].record, JournalRecord::SetState("1".to_string()));
+
+        // 記録を始める前のstateも含めて、同じstateが組み立てられる
+        let replayed_store: Store<SlowState, u64> = Store::new(SlowState::new());
+        replayed_store
+            .replay(entries, ReplayTiming::AsFastAsPossible)
+            .unwrap();
+        assert_eq!(replayed_store.get_state().unwrap().num, 11);
+    }
 }
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+use super::super::error::ToidError;
+
+// eventはHistoryEntryと同じくシリアライズした文字列。undoなどeventを介さない変更ではNone
+pub struct StateChange<S> {
+    pub old_state: Arc<S>,
+    pub new_state: Arc<S>,
+    pub event: Option<String>,
+}
+
+impl<S> Clone for StateChange<S> {
+    fn clone(&self) -> Self {
+        Self {
+            old_state: Arc::clone(&self.old_state),
+            new_state: Arc::clone(&self.new_state),
+            event: self.event.clone(),
+        }
+    }
+}
+
+pub type SubscriberCallback<S> = Box<dyn Fn(&StateChange<S>) + Send + Sync>;
+
+// 通知の前にcloneしてlockを外せるように、callbackはArcで持つ
+type SharedSubscriberCallback<S> = Arc<dyn Fn(&StateChange<S>) + Send + Sync>;
+
+pub struct Subscribers<S> {
+    callbacks: BTreeMap<usize, SharedSubscriberCallback<S>>,
+    next_id: usize,
+}
+
+impl<S> Clone for Subscribers<S> {
+    fn clone(&self) -> Self {
+        Self {
+            callbacks: self.callbacks.clone(),
+            next_id: self.next_id,
+        }
+    }
+}
+
+impl<S> Default for Subscribers<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Subscribers<S> {
+    pub fn new() -> Self {
+        Self {
+            callbacks: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn subscribe(&mut self, callback: SubscriberCallback<S>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.callbacks.insert(id, Arc::from(callback));
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: usize) -> Result<(), ToidError> {
+        match self.callbacks.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(ToidError::OutOfRange(format!(
+                "subscriber {} is not found",
+                id
+            ))),
+        }
+    }
+
+    pub fn notify(&self, change: &StateChange<S>) {
+        for callback in self.callbacks.values() {
+            callback(change);
+        }
+    }
+}
+
+impl<S: 'static + Send + Sync> Subscribers<S> {
+    // 受信側がdropされた後は、送信に失敗しても無視する
+    pub fn subscribe_channel(&mut self, sender: Sender<StateChange<S>>) -> usize {
+        let sender = Mutex::new(sender);
+        self.subscribe(Box::new(move |change| {
+            if let Ok(sender) = sender.lock() {
+                sender.send(change.clone()).ok();
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_subscribe_channel() {
+        let mut subscribers = Subscribers::new();
+        let (sender, receiver) = channel();
+        let id = subscribers.subscribe_channel(sender);
+
+        subscribers.notify(&StateChange {
+            old_state: Arc::new(0),
+            new_state: Arc::new(1),
+            event: Some("1".to_string()),
+        });
+        let change = receiver.try_recv().unwrap();
+        assert_eq!(*change.old_state, 0);
+        assert_eq!(*change.new_state, 1);
+        assert_eq!(change.event, Some("1".to_string()));
+
+        subscribers.unsubscribe(id).unwrap();
+        subscribers.notify(&StateChange {
+            old_state: Arc::new(1),
+            new_state: Arc::new(2),
+            event: None,
+        });
+        assert!(receiver.try_recv().is_err());
+        assert!(subscribers.unsubscribe(id).is_err());
+    }
+}
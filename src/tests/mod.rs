@@ -1 +1,545 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use super::data::music_info::{
+    Beat, Envelope, Glide, Instrument, Note, Phrase, Pitch, PitchNote, Synth, SynthOscillator,
+    Track, Waveform, DEFAULT_VELOCITY,
+};
+use super::music_state::effects::EffectInfo;
+use super::music_state::quantize::Quantize;
+use super::music_state::render_config::RenderConfig;
+use super::music_state::states::{
+    MusicState, MusicStateEvent, SchedulingStateEvent, SectionState, SectionStateEvent, TrackEvent,
+};
+use super::music_state::wave_reader::{WaveReader, WaveReaderEvent};
+use super::players::local_player::LocalPlayer;
+use super::players::player::Player;
+use super::state_management::state::State;
+use super::state_management::store_reader::StoreReader;
+
+type MusicLocalPlayer =
+    LocalPlayer<MusicState, MusicStateEvent, WaveReader, (Vec<i16>, Vec<i16>), WaveReaderEvent>;
+
+fn make_note(pitch: i32, start: f32, duration: f32) -> PitchNote {
+    PitchNote {
+        pitch: Pitch::from(pitch),
+        duration: Beat::from(duration),
+        start: Beat::from(start),
+        velocity: DEFAULT_VELOCITY,
+    }
+}
+
+fn make_sin_track(notes: Vec<PitchNote>, length: f32) -> Track<PitchNote> {
+    let phrase = notes
+        .into_iter()
+        .fold(Phrase::new(), |phrase, note| phrase.add_note(note))
+        .set_length(Beat::from(length));
+    Track::new().set_phrase(phrase).set_inst(Instrument::Sin)
+}
+
+fn make_track(num: usize) -> Track<PitchNote> {
+    let notes = (0..num)
+        .map(|i| make_note(60 + (i % 12) as i32, i as f32 * 0.25, 0.25))
+        .collect();
+    make_sin_track(notes, num as f32 * 0.25)
+}
+
+// 最初のsectionに"main"というtrackを置いたplayer
+fn make_player(track: Track<PitchNote>) -> Arc<MusicLocalPlayer> {
+    let player: Arc<MusicLocalPlayer> = Arc::new(LocalPlayer::new());
+    player
+        .send_event(MusicStateEvent::SectionStateEvent(
+            Beat::from(0),
+            SectionStateEvent::NewPitchTrack("main".to_string(), track),
+        ))
+        .unwrap();
+    player
+}
+
+fn read_left_wave(player: &MusicLocalPlayer, read_num: usize) -> Vec<i16> {
+    let mut wave = Vec::new();
+    for _ in 0..read_num {
+        let (left_wave, _) = player
+            .get_reader()
+            .write()
+            .unwrap()
+            .read(player.get_store(), player.get_resource_manager());
+        wave.extend(left_wave);
+    }
+    wave
+}
+
+#[test]
+fn test_render_while_updating_state() {
+    let player = make_player(make_track(16));
+
+    let finished = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let player = Arc::clone(&player);
+        let finished = Arc::clone(&finished);
+        thread::spawn(move || {
+            let mut update_num = 0;
+            while !finished.load(Ordering::Relaxed) {
+                player
+                    .send_event(MusicStateEvent::SectionStateEvent(
+                        Beat::from(0),
+                        SectionStateEvent::NewPitchTrack(
+                            format!("stress{}", update_num % 4),
+                            make_track(256),
+                        ),
+                    ))
+                    .unwrap();
+                update_num += 1;
+            }
+            update_num
+        })
+    };
+
+    // audio threadと同じように1buffer分の時間ごとにreadし、どのreadもその時間内に終わる。
+    // OSのschedulingによる揺れで落ちないように、bufferは大きめにする
+    let reader = player.get_reader();
+    let store = player.get_store();
+    let resource_manager = player.get_resource_manager();
+    let render_config = RenderConfig::new(44_100.0, 2048).unwrap();
+    reader
+        .write()
+        .unwrap()
+        .apply(WaveReaderEvent::SetRenderConfig(render_config));
+    let buffer_period =
+        Duration::from_secs_f64(render_config.block_size as f64 / render_config.sample_rate as f64);
+    let mut sounded_buffers = 0;
+    let mut max_read_duration = Duration::from_secs(0);
+    let read_start = Instant::now();
+    for i in 0..50 {
+        let read_time = read_start + buffer_period * i;
+        let now = Instant::now();
+        if read_time > now {
+            thread::sleep(read_time - now);
+        }
+        let start = Instant::now();
+        let (left_wave, _) = reader
+            .write()
+            .unwrap()
+            .read(Arc::clone(&store), Arc::clone(&resource_manager));
+        max_read_duration = max_read_duration.max(start.elapsed());
+        if left_wave.iter().any(|&x| x != 0) {
+            sounded_buffers += 1;
+        }
+    }
+
+    finished.store(true, Ordering::Relaxed);
+    let update_num = writer.join().unwrap();
+
+    assert!(update_num > 0);
+    assert_eq!(sounded_buffers, 50);
+    assert!(
+        max_read_duration < buffer_period,
+        "read took {:?}, buffer period is {:?}",
+        max_read_duration,
+        buffer_period
+    );
+}
+
+#[test]
+fn test_remove_move_duplicate_section() {
+    let state = MusicState::new()
+        .reduce(MusicStateEvent::NewSection(Beat::from(8)))
+        .reduce(MusicStateEvent::SectionStateEvent(
+            Beat::from(8),
+            SectionStateEvent::NewPitchTrack("main".to_string(), make_track(4)),
+        ))
+        .reduce(MusicStateEvent::DuplicateSection(
+            Beat::from(8),
+            Beat::from(16),
+        ));
+    assert_eq!(
+        state.get_section_beats(),
+        vec![Beat::from(0), Beat::from(8), Beat::from(16)]
+    );
+    assert_eq!(
+        state
+            .get_section_state_by_beat(Beat::from(16))
+            .get_pitch_track_names(),
+        vec!["main".to_string()]
+    );
+
+    let state = state.reduce(MusicStateEvent::MoveSection(Beat::from(8), Beat::from(4)));
+    assert_eq!(
+        state.get_section_beats(),
+        vec![Beat::from(0), Beat::from(4), Beat::from(16)]
+    );
+
+    let state = state
+        .reduce(MusicStateEvent::RemoveSection(Beat::from(4)))
+        .reduce(MusicStateEvent::RemoveSection(Beat::from(0)));
+    assert_eq!(
+        state.get_section_beats(),
+        vec![Beat::from(0), Beat::from(16)]
+    );
+    assert!(state
+        .get_section_state_by_beat(Beat::from(0))
+        .get_pitch_track_names()
+        .is_empty());
+}
+
+#[test]
+fn test_mute_solo_rename_track() {
+    let state = SectionState::new()
+        .reduce(SectionStateEvent::NewPitchTrack(
+            "a".to_string(),
+            make_track(4),
+        ))
+        .reduce(SectionStateEvent::NewPitchTrack(
+            "b".to_string(),
+            make_track(4),
+        ))
+        .reduce(SectionStateEvent::SetPitchTrackMute("a".to_string(), true));
+    assert!(!state.is_pitch_track_audible("a"));
+    assert!(state.is_pitch_track_audible("b"));
+
+    let state = state.reduce(SectionStateEvent::SetPitchTrackSolo("a".to_string(), true));
+    assert!(state.is_pitch_track_audible("a"));
+    assert!(!state.is_pitch_track_audible("b"));
+
+    let state = state
+        .reduce(SectionStateEvent::RenamePitchTrack(
+            "a".to_string(),
+            "c".to_string(),
+        ))
+        .reduce(SectionStateEvent::RemovePitchTrack("b".to_string()));
+    assert_eq!(state.get_pitch_track_names(), vec!["c".to_string()]);
+    assert!(state.is_pitch_track_audible("c"));
+    assert!(state.soloed_pitch_tracks.contains("c"));
+
+    // 既にある名前へのrenameは上書きせずに無視する
+    let state = state
+        .reduce(SectionStateEvent::NewPitchTrack(
+            "d".to_string(),
+            make_track(2),
+        ))
+        .reduce(SectionStateEvent::RenamePitchTrack(
+            "c".to_string(),
+            "d".to_string(),
+        ));
+    let mut track_names = state.get_pitch_track_names();
+    track_names.sort();
+    assert_eq!(track_names, vec!["c".to_string(), "d".to_string()]);
+    assert!(state.soloed_pitch_tracks.contains("c"));
+    assert!(!state.soloed_pitch_tracks.contains("d"));
+}
+
+#[test]
+fn test_track_event() {
+    let state = SectionState::new()
+        .reduce(SectionStateEvent::NewPitchTrack(
+            "a".to_string(),
+            make_track(4),
+        ))
+        .reduce(SectionStateEvent::PitchTrackEvent(
+            "a".to_string(),
+            TrackEvent::AddEffect(EffectInfo::ToLeftEffect),
+        ))
+        // 無いtrackへのeventは無視する
+        .reduce(SectionStateEvent::PitchTrackEvent(
+            "b".to_string(),
+            TrackEvent::AddEffect(EffectInfo::ToLeftEffect),
+        ));
+    assert_eq!(
+        state.get_pitch_track("a".to_string()).unwrap().effects,
+        vec![EffectInfo::ToLeftEffect]
+    );
+    assert_eq!(state.get_pitch_track_names(), vec!["a".to_string()]);
+
+    let state = state.reduce(SectionStateEvent::PitchTrackEvent(
+        "a".to_string(),
+        TrackEvent::ClearEffects,
+    ));
+    assert!(state
+        .get_pitch_track("a".to_string())
+        .unwrap()
+        .effects
+        .is_empty());
+}
+
+#[test]
+fn test_render_with_render_config() {
+    let player = make_player(make_sin_track(vec![make_note(69, 1.0, 1.0)], 4.0));
+
+    let reader = player.get_reader();
+    reader
+        .write()
+        .unwrap()
+        .apply(WaveReaderEvent::SetRenderConfig(
+            RenderConfig::new(48_000.0, 256).unwrap(),
+        ));
+
+    let mut wave = vec![];
+    for _ in 0..200 {
+        let (left_wave, _) = reader
+            .write()
+            .unwrap()
+            .read(player.get_store(), player.get_resource_manager());
+        assert_eq!(left_wave.len(), 256);
+        wave.extend(left_wave);
+    }
+
+    // 120bpmなので1拍目は0.5秒後、48kHzでは24000 sample目から鳴る
+    let first_sound = wave.iter().position(|&x| x != 0).unwrap();
+    assert!((24_000..24_010).contains(&first_sound));
+
+    // readを始めた後はblock_sizeを変えられない
+    reader
+        .write()
+        .unwrap()
+        .apply(WaveReaderEvent::SetRenderConfig(
+            RenderConfig::new(44_100.0, 1024).unwrap(),
+        ));
+    assert_eq!(
+        reader.read().unwrap().get_render_config(),
+        RenderConfig::new(48_000.0, 256).unwrap()
+    );
+}
+
+#[test]
+fn test_seek_pause_resume_stop() {
+    let player = make_player(make_sin_track(vec![make_note(69, 0.0, 4.0)], 8.0));
+    let reader = player.get_reader();
+    let read = || {
+        reader
+            .write()
+            .unwrap()
+            .read(player.get_store(), player.get_resource_manager())
+    };
+
+    // 4拍の音の途中にseekすると、途中から鳴る
+    reader
+        .write()
+        .unwrap()
+        .apply(WaveReaderEvent::Seek(Beat::from(2)));
+    let (left_wave, _) = read();
+    assert!(left_wave.iter().take(16).any(|&x| x != 0));
+
+    // 4拍の音が終わった後にseekすると鳴らない
+    reader
+        .write()
+        .unwrap()
+        .apply(WaveReaderEvent::Seek(Beat::from(5)));
+    let (left_wave, _) = read();
+    assert!(left_wave.iter().all(|&x| x == 0));
+
+    reader.write().unwrap().apply(WaveReaderEvent::Pause);
+    let paused_beats = reader.read().unwrap().get_current_beats();
+    read();
+    assert_eq!(reader.read().unwrap().get_current_beats(), paused_beats);
+
+    reader.write().unwrap().apply(WaveReaderEvent::Resume);
+    read();
+    assert!(reader.read().unwrap().get_current_beats() > paused_beats);
+
+    reader.write().unwrap().apply(WaveReaderEvent::Stop);
+    assert!(!reader.read().unwrap().is_playing());
+    assert_eq!(reader.read().unwrap().get_current_beats(), Beat::from(0));
+}
+
+#[test]
+fn test_loop_section_with_tempo_change() {
+    let player = make_player(make_sin_track(vec![make_note(69, 0.0, 8.0)], 8.0));
+    player
+        .send_event(MusicStateEvent::NewSection(Beat::from(4)))
+        .unwrap();
+    // 2拍までは120bpm、そこから240bpmなので、sectionは66150sample
+    player
+        .send_event(MusicStateEvent::SchedulingStateEvent(
+            SchedulingStateEvent::ChangeBPM(Beat::from(2), 240.0),
+        ))
+        .unwrap();
+
+    let reader = player.get_reader();
+    reader
+        .write()
+        .unwrap()
+        .apply(WaveReaderEvent::LoopSection(Beat::from(1)));
+
+    let mut wrapped_reads = Vec::new();
+    for i in 0..300 {
+        let previous_beats = reader.read().unwrap().get_current_beats();
+        let (left_wave, _) = reader
+            .write()
+            .unwrap()
+            .read(player.get_store(), player.get_resource_manager());
+        let current_beats = reader.read().unwrap().get_current_beats();
+        assert!(current_beats < Beat::from(4));
+        // 8拍の音はloopの終わりで止まり、始めから鳴り直すので途切れない
+        assert!(left_wave.iter().any(|&x| x != 0));
+        if current_beats < previous_beats {
+            wrapped_reads.push(i);
+        }
+    }
+    // 66150 / 512 = 129.2, 132300 / 512 = 258.4
+    assert_eq!(wrapped_reads, vec![129, 258]);
+}
+
+#[test]
+fn test_queue_event_on_next_bar() {
+    let player: Arc<MusicLocalPlayer> = Arc::new(LocalPlayer::new());
+    let reader = player.get_reader();
+    let read = || {
+        reader
+            .write()
+            .unwrap()
+            .read(player.get_store(), player.get_resource_manager())
+    };
+
+    // 1拍目あたりでqueueすると、4拍目(88200sample)で反映される
+    for _ in 0..50 {
+        read();
+    }
+    reader.write().unwrap().apply(WaveReaderEvent::QueueEvent(
+        Quantize::Bar,
+        Box::new(MusicStateEvent::SectionStateEvent(
+            Beat::from(0),
+            SectionStateEvent::NewPitchTrack(
+                "main".to_string(),
+                make_sin_track(vec![make_note(69, 0.0, 1.0)], 4.0),
+            ),
+        )),
+    ));
+
+    for _ in 50..172 {
+        let (left_wave, _) = read();
+        assert!(left_wave.iter().all(|&x| x == 0));
+        assert!(
+            player.get_store().get_state().unwrap().section_map[&Beat::from(0)]
+                .pitch_track_map
+                .is_empty()
+        );
+    }
+    // 172 * 512 = 88064なので、136sample目が区切り。
+    // eventはaudio threadの外でstoreに反映され、readはその後のstateを使う
+    let (left_wave, _) = read();
+    assert!(left_wave.iter().all(|&x| x == 0));
+    // 反映を待っている間の他の更新も、storeのstateに入る
+    player
+        .send_event(MusicStateEvent::SectionStateEvent(
+            Beat::from(0),
+            SectionStateEvent::NewPitchTrack("other".to_string(), make_track(4)),
+        ))
+        .unwrap();
+    let start = Instant::now();
+    while !player.get_store().get_state().unwrap().section_map[&Beat::from(0)]
+        .pitch_track_map
+        .contains_key("main")
+    {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(1));
+    }
+    let mut track_names =
+        player.get_store().get_state().unwrap().section_map[&Beat::from(0)].get_pitch_track_names();
+    track_names.sort();
+    assert_eq!(track_names, vec!["main".to_string(), "other".to_string()]);
+    // 区切りで始まるnoteは、反映された後のreadの始めから途中として鳴る
+    let (left_wave, _) = read();
+    assert!(left_wave[..16].iter().any(|&x| x != 0));
+}
+
+#[test]
+fn test_deserialize_note_without_velocity() {
+    let note = PitchNote {
+        pitch: Pitch::from(60),
+        duration: Beat::from(1),
+        start: Beat::from(0),
+        velocity: 30,
+    };
+    // velocityが無い古い形式でも読める
+    let mut value = serde_json::to_value(note).unwrap();
+    value.as_object_mut().unwrap().remove("velocity");
+    let deserialized: PitchNote = serde_json::from_value(value).unwrap();
+    assert_eq!(deserialized.velocity, DEFAULT_VELOCITY);
+    assert_eq!(deserialized.pitch, note.pitch);
+    // 古い形式のnoteは、velocityが入る前と同じ音量で鳴る
+    assert_eq!(deserialized.get_gain(), 1.0);
+}
+
+#[test]
+fn test_envelope_release_tail() {
+    let player = make_player(
+        make_sin_track(vec![make_note(69, 0.0, 1.0)], 8.0)
+            .set_envelope(Envelope::new(0.01, 0.0, 1.0, 0.1)),
+    );
+
+    let wave = read_left_wave(&player, 60);
+    // note offは22050sample、releaseは4410sample
+    assert_eq!(wave[0], 0);
+    assert!(wave[22100..26400].iter().any(|&x| x != 0));
+    assert!(wave[26500..].iter().all(|&x| x == 0));
+}
+
+#[test]
+fn test_legato_glide() {
+    let player = make_player(
+        make_sin_track(vec![make_note(69, 0.0, 1.5), make_note(81, 1.0, 1.0)], 8.0)
+            .set_envelope(Envelope::new(0.01, 0.0, 1.0, 0.0))
+            .set_glide(Glide::new(0.05, true)),
+    );
+
+    let wave = read_left_wave(&player, 100);
+    let get_peak = |range: std::ops::Range<usize>| {
+        wave[range].iter().map(|&x| (x as i32).abs()).max().unwrap()
+    };
+    let count_zero_crossing = |range: std::ops::Range<usize>| {
+        wave[range]
+            .windows(2)
+            .filter(|w| w[0] < 0 && w[1] >= 0)
+            .count()
+    };
+
+    // 2つ目のnoteは22050sampleから。attackをやり直さないので、音量は下がらない
+    let peak = get_peak(11025..12025);
+    assert!(get_peak(22050..22150) * 10 > peak * 9);
+    // glideが終わると1オクターブ上になる
+    assert!((43..=45).contains(&count_zero_crossing(11025..15435)));
+    assert!((87..=89).contains(&count_zero_crossing(25000..29410)));
+    // 1つ目のnote offの後も、2つ目のnote offの44100sampleまで鳴る
+    assert!(get_peak(33100..44000) * 10 > peak * 9);
+    assert!(wave[44100..].iter().all(|&x| x == 0));
+}
+
+#[test]
+fn test_synth_track() {
+    let synth = Synth {
+        oscillators: vec![
+            SynthOscillator {
+                unison: 4,
+                unison_detune: 15.0,
+                ..SynthOscillator::new(Waveform::Saw)
+            },
+            SynthOscillator {
+                level: 0.2,
+                ..SynthOscillator::new(Waveform::Noise)
+            },
+        ],
+        ..Synth::default()
+    };
+    let track = Track::new()
+        .set_phrase(make_track(4).phrase)
+        .set_inst(Instrument::Synth(synth.clone()));
+
+    // Trackと一緒にserializeされる
+    let serialized = serde_json::to_string(&track).unwrap();
+    let deserialized: Track<PitchNote> = serde_json::from_str(&serialized).unwrap();
+    match deserialized.instrument {
+        Instrument::Synth(deserialized_synth) => assert_eq!(deserialized_synth, synth),
+        _ => panic!("instrument is not synth"),
+    }
+
+    let player = make_player(track);
+    let (left_wave, right_wave) = player
+        .get_reader()
+        .write()
+        .unwrap()
+        .read(player.get_store(), player.get_resource_manager());
+    assert!(left_wave.iter().any(|&x| x != 0));
+    assert!(right_wave.iter().any(|&x| x != 0));
+}